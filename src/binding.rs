@@ -2,7 +2,8 @@ use radix_trie::TrieKey;
 
 /// Custom event handlers
 use crate::{
-    Cmd, EditMode, InputMode, InputState, KeyCode, KeyEvent, Modifiers, Refresher, RepeatCount,
+    Cmd, EditMode, InputMode, InputState, KeyCode, KeyEvent, Modifiers, MouseButton, Refresher,
+    RepeatCount,
 };
 
 /// Input event
@@ -13,7 +14,10 @@ pub enum Event {
     Any,
     /// Key sequence
     KeySeq(Vec<KeyEvent>),
-    /// TODO Mouse event
+    /// Wildcard for mouse events (checked before [`Event::Any`]).
+    ///
+    /// To bind a specific button, use a [`KeyCode::Mouse`] key: its screen
+    /// position is ignored when matching.
     Mouse(),
 }
 
@@ -49,6 +53,7 @@ const BASE: u32 = 0x0010_ffff + 1;
 const BASE_CONTROL: u32 = 0x0200_0000;
 const BASE_META: u32 = 0x0400_0000;
 const BASE_SHIFT: u32 = 0x0100_0000;
+const BASE_RELEASE: u32 = 0x0800_0000;
 const ESCAPE: u32 = 27;
 const PAGE_UP: u32 = BASE + 1;
 const PAGE_DOWN: u32 = PAGE_UP + 1;
@@ -65,6 +70,7 @@ const MOUSE: u32 = /* F24 + 1 */ INSERT + 25;
 const PASTE_START: u32 = MOUSE + 1;
 const PASTE_FINISH: u32 = PASTE_START + 1;
 const ANY: u32 = PASTE_FINISH + 1;
+const MOUSE_BUTTON: u32 = ANY + 1;
 
 impl KeyEvent {
     fn encode(&self) -> u32 {
@@ -84,6 +90,17 @@ impl KeyEvent {
             KeyCode::Home => HOME,
             KeyCode::Insert => INSERT,
             KeyCode::Left => LEFT,
+            // position is ignored
+            KeyCode::Mouse(m) => {
+                let button = match m.button {
+                    MouseButton::Left => 0,
+                    MouseButton::Middle => 1,
+                    MouseButton::Right => 2,
+                    MouseButton::WheelUp => 3,
+                    MouseButton::WheelDown => 4,
+                };
+                MOUSE_BUTTON + 2 * button + u32::from(!m.pressed)
+            }
             KeyCode::PageDown => PAGE_DOWN,
             KeyCode::PageUp => PAGE_UP,
             KeyCode::Right => RIGHT,
@@ -99,6 +116,9 @@ impl KeyEvent {
         if self.1.contains(Modifiers::SHIFT) {
             u |= BASE_SHIFT;
         }
+        if self.1.contains(Modifiers::RELEASE) {
+            u |= BASE_RELEASE;
+        }
         u
     }
 }
@@ -119,6 +139,17 @@ impl TrieKey for Event {
     }
 }
 
+/// Conflict between a custom binding and another binding
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BindingConflict {
+    /// The custom binding of the event replaces the built-in command
+    Override(Event, Cmd),
+    /// The custom key sequence (first) cannot be reached because its first key
+    /// is bound (second)
+    Unreachable(Event, Event),
+}
+
 /// Event handler
 pub enum EventHandler {
     /// unconditional command
@@ -215,7 +246,7 @@ mod test {
     use radix_trie::Trie;
 
     use super::{Event, EventHandler};
-    use crate::{Cmd, KeyEvent};
+    use crate::{Cmd, KeyCode, KeyEvent, Modifiers};
 
     #[test]
     fn encode() {
@@ -246,6 +277,28 @@ mod test {
         trie.insert(E::from(K(C::Tab, M::CTRL)), H::from(Cmd::Noop));
     }
 
+    #[test]
+    fn key_release() {
+        let mut trie = Trie::new();
+        trie.insert(
+            Event::from(KeyEvent::ctrl('A')),
+            EventHandler::from(Cmd::Noop),
+        );
+        let release = Event::from(KeyEvent::new('A', Modifiers::CTRL | Modifiers::RELEASE));
+        assert!(trie.get(&release).is_none());
+        trie.insert(release.clone(), EventHandler::from(Cmd::Noop));
+        assert!(trie.get(&release).is_some());
+        assert!(trie.get(&Event::from(KeyEvent::ctrl('A'))).is_some());
+        assert_eq!(
+            release,
+            Event::from(KeyEvent(
+                KeyCode::Char('a'),
+                Modifiers::CTRL | Modifiers::RELEASE
+            ))
+            .normalize()
+        );
+    }
+
     #[test]
     #[ignore]
     #[cfg(target_arch = "x86_64")]
@@ -5,6 +5,7 @@ use crate::history::SearchDirection;
 use crate::keymap::{Anchor, At, Cmd, InputState, Movement, Refresher as _, Word};
 use crate::kill_ring::{KillRing, Mode};
 use crate::line_buffer::WordAction;
+use crate::tty::Renderer;
 use crate::{Helper, Prompt, Result, complete_hint_line, error};
 
 pub enum Status {
@@ -12,9 +13,9 @@ pub enum Status {
     Submit,
 }
 
-pub fn execute<H: Helper, P: Prompt + ?Sized>(
+pub fn execute<H: Helper, P: Prompt + ?Sized, R: Renderer>(
     cmd: Cmd,
-    s: &mut State<'_, '_, H, P>,
+    s: &mut State<'_, '_, H, P, R>,
     input_state: &InputState,
     kill_ring: &mut KillRing,
     config: &Config,
@@ -88,20 +89,20 @@ pub fn execute<H: Helper, P: Prompt + ?Sized>(
         }
         Cmd::NextHistory => {
             // Fetch the next command from the history list.
-            s.edit_history_next(false)?;
+            history_next(s, false, config)?;
         }
         Cmd::PreviousHistory => {
             // Fetch the previous command from the history list.
-            s.edit_history_next(true)?;
+            history_next(s, true, config)?;
         }
         Cmd::LineUpOrPreviousHistory(n) => {
             if !s.edit_move_line_up(n)? {
-                s.edit_history_next(true)?;
+                history_next(s, true, config)?;
             }
         }
         Cmd::LineDownOrNextHistory(n) => {
             if !s.edit_move_line_down(n)? {
-                s.edit_history_next(false)?;
+                history_next(s, false, config)?;
             }
         }
         Cmd::HistorySearchBackward => s.edit_history_search(SearchDirection::Reverse)?,
@@ -232,3 +233,15 @@ pub fn execute<H: Helper, P: Prompt + ?Sized>(
     }
     Ok(Proceed)
 }
+
+fn history_next<H: Helper, P: Prompt + ?Sized, R: Renderer>(
+    s: &mut State<'_, '_, H, P, R>,
+    prev: bool,
+    config: &Config,
+) -> Result<()> {
+    if config.history_find_no_dups() {
+        s.edit_history_next_unique(prev)
+    } else {
+        s.edit_history_next(prev)
+    }
+}
@@ -1,16 +1,37 @@
 //! Customize line editor
 use std::default::Default;
+#[cfg(feature = "with-config-file")]
+use std::fs;
+#[cfg(feature = "with-config-file")]
+use std::path::Path;
 
 use crate::Result;
 use crate::layout::GraphemeClusterMode;
 
 /// User preferences
+///
+/// With the `serde` feature, missing fields are deserialized with their
+/// default value and unknown fields are rejected.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
 pub struct Config {
     /// Maximum number of entries in History.
     max_history_size: usize, // history_max_entries
+    /// Maximum length (in bytes) of a history entry
+    max_history_entry_len: Option<usize>,
+    /// Maximum size (in bytes) of all history entries
+    max_history_bytes: Option<usize>,
     history_duplicates: HistoryDuplicates,
     history_ignore_space: bool,
+    /// Skip duplicates while navigating history
+    history_find_no_dups: bool,
+    /// Pick up entries added by other sessions before each prompt
+    history_sharing: bool,
+    /// Save when history entries are entered
+    history_timestamps: bool,
+    /// Order of matches in incremental search
+    history_ranking: HistoryRanking,
     completion_type: CompletionType,
     /// Directly show all alternatives or not when [`CompletionType::List`] is
     /// being used
@@ -42,10 +63,18 @@ pub struct Config {
     check_cursor_position: bool,
     /// Bracketed paste on unix platform
     enable_bracketed_paste: bool,
+    /// Mouse reporting on unix platform
+    enable_mouse: bool,
+    /// Kitty keyboard protocol on unix platform
+    enable_kitty_keyboard: bool,
+    /// Key release events (kitty keyboard protocol)
+    enable_key_release: bool,
     /// Synchronized output on unix platform
     enable_synchronized_output: bool,
     /// Whether to disable or not the signals in termios
     enable_signals: bool,
+    /// Scroll the line horizontally instead of wrapping it
+    horizontal_scroll: bool,
 }
 
 impl Config {
@@ -65,6 +94,24 @@ impl Config {
         self.max_history_size = max_size;
     }
 
+    /// Tell the maximum length (in bytes) of a history entry: longer entries
+    /// are ignored when added or loaded.
+    ///
+    /// By default, there is no limit.
+    #[must_use]
+    pub fn max_history_entry_len(&self) -> Option<usize> {
+        self.max_history_entry_len
+    }
+
+    /// Tell the maximum size (in bytes) of all history entries: oldest
+    /// entries are removed to make room for new ones.
+    ///
+    /// By default, there is no limit.
+    #[must_use]
+    pub fn max_history_bytes(&self) -> Option<usize> {
+        self.max_history_bytes
+    }
+
     /// Tell if lines which match the previous history entry are saved or not
     /// in the history list.
     ///
@@ -82,6 +129,10 @@ impl Config {
         };
     }
 
+    pub(crate) fn set_history_duplicates(&mut self, duplicates: HistoryDuplicates) {
+        self.history_duplicates = duplicates;
+    }
+
     /// Tell if lines which begin with a space character are saved or not in
     /// the history list.
     ///
@@ -95,6 +146,57 @@ impl Config {
         self.history_ignore_space = yes;
     }
 
+    /// Tell if entries already displayed are skipped while navigating
+    /// history with previous / next history commands (like zsh
+    /// `HIST_FIND_NO_DUPS`). Duplicates are still stored.
+    ///
+    /// By default, they are displayed.
+    #[must_use]
+    pub fn history_find_no_dups(&self) -> bool {
+        self.history_find_no_dups
+    }
+
+    /// Tell if entries added by other running sessions (on the same history
+    /// file or database) are picked up (like zsh `SHARE_HISTORY`).
+    /// Otherwise, navigation only goes through entries loaded at startup and
+    /// entries added by this session.
+    ///
+    /// Synchronization happens before each prompt and before each history
+    /// move or search started from the edited line (not while browsing
+    /// history). With a history file, other sessions only see an entry once it
+    /// has been written: call `Editor::append_history` after each
+    /// `Editor::add_history_entry`.
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn history_sharing(&self) -> bool {
+        self.history_sharing
+    }
+
+    /// Tell if the time when history entries are entered (see
+    /// [`History::timestamp`](crate::history::History::timestamp)) is saved
+    /// in history files.
+    ///
+    /// History files are then saved in a timestamped format which older
+    /// versions cannot read. Otherwise, timestamps are only kept in memory
+    /// (unless the file is already timestamped).
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn history_timestamps(&self) -> bool {
+        self.history_timestamps
+    }
+
+    /// Tell in which order incremental search (Ctrl-R) goes through matching
+    /// entries (if supported by the history, see
+    /// [`History::ranked`](crate::history::History::ranked)).
+    ///
+    /// By default, [`HistoryRanking::Recency`].
+    #[must_use]
+    pub fn history_ranking(&self) -> HistoryRanking {
+        self.history_ranking
+    }
+
     /// Completion behaviour.
     ///
     /// By default, [`CompletionType::Circular`].
@@ -225,6 +327,35 @@ impl Config {
         self.enable_bracketed_paste
     }
 
+    /// Mouse reporting on unix platform: click to move the cursor, wheel to
+    /// browse history or completion candidates.
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn enable_mouse(&self) -> bool {
+        self.enable_mouse
+    }
+
+    /// Kitty keyboard protocol on unix platform: keys like Ctrl-Shift-A,
+    /// Ctrl-Enter or Ctrl-Tab are reported unambiguously (on supporting
+    /// terminals).
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn enable_kitty_keyboard(&self) -> bool {
+        self.enable_kitty_keyboard
+    }
+
+    /// Key release events (requires the kitty keyboard protocol): they can
+    /// be bound with a [`Modifiers::RELEASE`](crate::Modifiers::RELEASE) key,
+    /// otherwise they are ignored.
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn enable_key_release(&self) -> bool {
+        self.enable_key_release
+    }
+
     /// Synchronized output on unix platform
     ///
     /// By default, it's enabled.
@@ -244,14 +375,29 @@ impl Config {
     pub(crate) fn set_enable_signals(&mut self, enable_signals: bool) {
         self.enable_signals = enable_signals;
     }
+
+    /// Tell if the line is scrolled horizontally (single-line mode) instead
+    /// of being wrapped.
+    ///
+    /// By default, the line is wrapped.
+    #[must_use]
+    pub fn horizontal_scroll(&self) -> bool {
+        self.horizontal_scroll
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             max_history_size: 100,
+            max_history_entry_len: None,
+            max_history_bytes: None,
             history_duplicates: HistoryDuplicates::IgnoreConsecutive,
             history_ignore_space: false,
+            history_find_no_dups: false,
+            history_sharing: false,
+            history_timestamps: false,
+            history_ranking: HistoryRanking::Recency,
             completion_type: CompletionType::Circular, // TODO Validate
             completion_prompt_limit: 100,
             completion_show_all_if_ambiguous: false,
@@ -266,14 +412,20 @@ impl Default for Config {
             indent_size: 2,
             check_cursor_position: false,
             enable_bracketed_paste: true,
+            enable_mouse: false,
+            enable_kitty_keyboard: false,
+            enable_key_release: false,
             enable_synchronized_output: true,
             enable_signals: false,
+            horizontal_scroll: false,
         }
     }
 }
 
 /// Beep or flash or nothing
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum BellStyle {
     /// Beep
     Audible,
@@ -299,16 +451,39 @@ impl Default for BellStyle {
 
 /// History filter
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum HistoryDuplicates {
     /// No filter
     AlwaysAdd,
     /// a line will not be added to the history if it matches the previous entry
     IgnoreConsecutive,
+    /// all older entries matching a new line are removed from the history, so
+    /// that the line moves to the most recent position (like bash
+    /// `erasedups`)
+    EraseDups,
+}
+
+/// Order of history search matches
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum HistoryRanking {
+    /// Most recent first
+    #[default]
+    Recency,
+    /// Most frequently used first
+    Frequency,
+    /// Frequency weighted by recency first (frecency)
+    Frecency,
 }
 
 /// Tab completion style
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum CompletionType {
     /// Complete the next full match (like in Vim by default)
     Circular,
@@ -328,6 +503,8 @@ pub enum CompletionType {
 /// Style of editing / Standard keymaps
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum EditMode {
     /// Emacs keymap
     Emacs,
@@ -338,6 +515,8 @@ pub enum EditMode {
 /// Colorization mode
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ColorMode {
     /// Activate highlighting if platform/terminal is supported.
     Enabled,
@@ -350,6 +529,8 @@ pub enum ColorMode {
 /// Should the editor use stdio
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 #[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Behavior {
     /// Use stdin / stdout
     #[default]
@@ -357,9 +538,8 @@ pub enum Behavior {
     /// Use terminal-style interaction whenever possible, even if 'stdin' and/or
     /// 'stdout' are not terminals.
     PreferTerm,
-    // TODO
-    // Use file-style interaction, reading input from the given file.
-    // useFile
+    // File-style interaction, reading input from a file: see
+    // `Editor::set_script`
 }
 
 /// Configuration builder
@@ -383,6 +563,24 @@ impl Builder {
         Ok(self)
     }
 
+    /// Set the maximum length (in bytes) of a history entry.
+    ///
+    /// By default, there is no limit.
+    #[must_use]
+    pub fn max_history_entry_len(mut self, max_len: Option<usize>) -> Self {
+        self.p.max_history_entry_len = max_len;
+        self
+    }
+
+    /// Set the maximum size (in bytes) of all history entries.
+    ///
+    /// By default, there is no limit.
+    #[must_use]
+    pub fn max_history_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.p.max_history_bytes = max_bytes;
+        self
+    }
+
     /// Tell if lines which match the previous history entry are saved or not
     /// in the history list.
     ///
@@ -392,6 +590,24 @@ impl Builder {
         Ok(self)
     }
 
+    /// Tell how lines which match other history entries are saved in the
+    /// history list.
+    ///
+    /// By default, [`HistoryDuplicates::IgnoreConsecutive`].
+    pub fn history_duplicates(mut self, duplicates: HistoryDuplicates) -> Result<Self> {
+        self.set_history_duplicates(duplicates)?;
+        Ok(self)
+    }
+
+    /// Skip entries already displayed while navigating history.
+    ///
+    /// By default, they are displayed.
+    #[must_use]
+    pub fn history_find_no_dups(mut self, yes: bool) -> Self {
+        self.set_history_find_no_dups(yes);
+        self
+    }
+
     /// Tell if lines which begin with a space character are saved or not in
     /// the history list.
     ///
@@ -402,6 +618,35 @@ impl Builder {
         self
     }
 
+    /// Pick up entries added by other running sessions before each prompt
+    /// (see [`Config::history_sharing`]).
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn history_sharing(mut self, yes: bool) -> Self {
+        self.set_history_sharing(yes);
+        self
+    }
+
+    /// Save when history entries are entered (see
+    /// [`Config::history_timestamps`]).
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn history_timestamps(mut self, yes: bool) -> Self {
+        self.p.history_timestamps = yes;
+        self
+    }
+
+    /// Order in which incremental search goes through matching entries.
+    ///
+    /// By default, [`HistoryRanking::Recency`].
+    #[must_use]
+    pub fn history_ranking(mut self, ranking: HistoryRanking) -> Self {
+        self.set_history_ranking(ranking);
+        self
+    }
+
     /// Set `completion_type`.
     #[must_use]
     pub fn completion_type(mut self, completion_type: CompletionType) -> Self {
@@ -525,6 +770,33 @@ impl Builder {
         self
     }
 
+    /// Enable or disable mouse reporting on unix platform
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn mouse(mut self, enabled: bool) -> Self {
+        self.enable_mouse(enabled);
+        self
+    }
+
+    /// Enable or disable kitty keyboard protocol on unix platform
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn kitty_keyboard(mut self, enabled: bool) -> Self {
+        self.enable_kitty_keyboard(enabled);
+        self
+    }
+
+    /// Enable or disable key release events (kitty keyboard protocol)
+    ///
+    /// By default, it's disabled.
+    #[must_use]
+    pub fn key_release(mut self, enabled: bool) -> Self {
+        self.enable_key_release(enabled);
+        self
+    }
+
     /// Enable or disable signals in termios
     ///
     /// By default, it's disabled.
@@ -534,6 +806,16 @@ impl Builder {
         self
     }
 
+    /// Scroll the line horizontally (single-line mode, like linenoise) instead
+    /// of wrapping it. `<` and `>` markers show truncated text.
+    ///
+    /// By default, the line is wrapped.
+    #[must_use]
+    pub fn horizontal_scroll(mut self, yes: bool) -> Self {
+        self.set_horizontal_scroll(yes);
+        self
+    }
+
     /// Builds a [`Config`] with the settings specified so far.
     #[must_use]
     pub fn build(self) -> Config {
@@ -541,6 +823,68 @@ impl Builder {
     }
 }
 
+#[cfg(feature = "with-config-file")]
+impl Builder {
+    /// Merge the settings of a TOML file (same keys as the [`Config`] fields),
+    /// like:
+    /// ```toml
+    /// edit_mode = "vi"
+    /// bell_style = "none"
+    /// max_history_size = 1000
+    /// ```
+    /// Settings which are not in the file are left untouched, an empty string
+    /// resets a setting to its default value (TOML has no null: this is how
+    /// an optional setting like `max_history_entry_len` is unset) and an
+    /// unknown setting is an [`InvalidData`](std::io::ErrorKind::InvalidData)
+    /// error.
+    pub fn merge_file<P: AsRef<Path> + ?Sized>(self, path: &P) -> Result<Self> {
+        let table = fs::read_to_string(path)?.parse::<toml::Table>()?;
+        self.merge(table)
+    }
+
+    /// Merge the settings of `RUSTYLINE_*` environment variables, like
+    /// `RUSTYLINE_EDIT_MODE=vi` or `RUSTYLINE_KEYSEQ_TIMEOUT=100` (see
+    /// [`merge_file`](Self::merge_file) for empty values and unknown
+    /// settings).
+    pub fn merge_env(self) -> Result<Self> {
+        self.merge_vars(std::env::vars())
+    }
+
+    fn merge_vars<V: IntoIterator<Item = (String, String)>>(self, vars: V) -> Result<Self> {
+        let mut table = toml::Table::new();
+        for (key, value) in vars {
+            let Some(key) = key.strip_prefix("RUSTYLINE_") else {
+                continue;
+            };
+            // numbers and booleans, strings otherwise
+            let value = value
+                .parse::<toml::Value>()
+                .unwrap_or(toml::Value::String(value));
+            table.insert(key.to_ascii_lowercase(), value);
+        }
+        self.merge(table)
+    }
+
+    fn merge(mut self, table: toml::Table) -> Result<Self> {
+        // like `set_edit_mode`
+        let edit_mode = table.contains_key("edit_mode") && !table.contains_key("keyseq_timeout");
+        let mut config = toml::Table::try_from(&self.p)?;
+        for (key, value) in table {
+            if value.as_str() == Some("") {
+                // missing fields are deserialized with their default value
+                config.remove(&key);
+            } else {
+                config.insert(key, value);
+            }
+        }
+        self.p = config.try_into()?;
+        if edit_mode {
+            self.set_edit_mode(self.p.edit_mode);
+        }
+        Ok(self)
+    }
+}
+
 impl Configurer for Builder {
     fn config_mut(&mut self) -> &mut Config {
         &mut self.p
@@ -567,6 +911,22 @@ pub trait Configurer {
         Ok(())
     }
 
+    /// Tell how lines which match other history entries are saved in the
+    /// history list.
+    ///
+    /// By default, [`HistoryDuplicates::IgnoreConsecutive`].
+    fn set_history_duplicates(&mut self, duplicates: HistoryDuplicates) -> Result<()> {
+        self.config_mut().set_history_duplicates(duplicates);
+        Ok(())
+    }
+
+    /// Skip entries already displayed while navigating history.
+    ///
+    /// By default, they are displayed.
+    fn set_history_find_no_dups(&mut self, yes: bool) {
+        self.config_mut().history_find_no_dups = yes;
+    }
+
     /// Tell if lines which begin with a space character are saved or not in
     /// the history list.
     ///
@@ -574,6 +934,21 @@ pub trait Configurer {
     fn set_history_ignore_space(&mut self, yes: bool) {
         self.config_mut().set_history_ignore_space(yes);
     }
+
+    /// Pick up entries added by other running sessions before each prompt
+    /// (see [`Config::history_sharing`]).
+    ///
+    /// By default, it's disabled.
+    fn set_history_sharing(&mut self, yes: bool) {
+        self.config_mut().history_sharing = yes;
+    }
+
+    /// Order in which incremental search goes through matching entries.
+    ///
+    /// By default, [`HistoryRanking::Recency`].
+    fn set_history_ranking(&mut self, ranking: HistoryRanking) {
+        self.config_mut().history_ranking = ranking;
+    }
     /// Set `completion_type`.
     fn set_completion_type(&mut self, completion_type: CompletionType) {
         self.config_mut().completion_type = completion_type;
@@ -658,6 +1033,27 @@ pub trait Configurer {
         self.config_mut().enable_bracketed_paste = enabled;
     }
 
+    /// Enable or disable mouse reporting on unix platform
+    ///
+    /// By default, it's disabled.
+    fn enable_mouse(&mut self, enabled: bool) {
+        self.config_mut().enable_mouse = enabled;
+    }
+
+    /// Enable or disable kitty keyboard protocol on unix platform
+    ///
+    /// By default, it's disabled.
+    fn enable_kitty_keyboard(&mut self, enabled: bool) {
+        self.config_mut().enable_kitty_keyboard = enabled;
+    }
+
+    /// Enable or disable key release events (kitty keyboard protocol)
+    ///
+    /// By default, it's disabled.
+    fn enable_key_release(&mut self, enabled: bool) {
+        self.config_mut().enable_key_release = enabled;
+    }
+
     /// Enable or disable synchronized output on unix platform
     ///
     /// By default, it's enabled.
@@ -671,4 +1067,104 @@ pub trait Configurer {
     fn set_enable_signals(&mut self, enable_signals: bool) {
         self.config_mut().set_enable_signals(enable_signals);
     }
+
+    /// Scroll the line horizontally (single-line mode) instead of wrapping it
+    ///
+    /// By default, the line is wrapped.
+    fn set_horizontal_scroll(&mut self, yes: bool) {
+        self.config_mut().horizontal_scroll = yes;
+    }
+}
+
+#[cfg(all(test, feature = "with-config-file"))]
+mod test {
+    use std::io::{ErrorKind, Write as _};
+
+    use super::{BellStyle, Builder, CompletionType, Config, EditMode};
+    use crate::error::ReadlineError;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let config = Config::builder()
+            .edit_mode(EditMode::Vi)
+            .bell_style(BellStyle::None)
+            .build();
+        let text = toml::to_string(&config).unwrap();
+        assert_eq!(config, toml::from_str::<Config>(&text).unwrap());
+    }
+
+    #[test]
+    fn merge_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "max_history_size = 1000\ncompletion_type = \"list\"").unwrap();
+        let config = Builder::new()
+            .bell_style(BellStyle::None)
+            .merge_file(file.path())
+            .unwrap()
+            .build();
+        assert_eq!(1000, config.max_history_size());
+        assert_eq!(CompletionType::List, config.completion_type());
+        assert_eq!(BellStyle::None, config.bell_style());
+    }
+
+    #[test]
+    fn merge_vars() {
+        let config = Builder::new()
+            .merge_vars(vars(&[
+                ("RUSTYLINE_EDIT_MODE", "vi"),
+                ("RUSTYLINE_COLOR_MODE", "disabled"),
+                ("HOME", "/root"),
+            ]))
+            .unwrap()
+            .build();
+        assert_eq!(EditMode::Vi, config.edit_mode());
+        assert_eq!(Some(500), config.keyseq_timeout());
+        assert_eq!(super::ColorMode::Disabled, config.color_mode());
+
+        let config = Builder::new()
+            .merge_vars(vars(&[
+                ("RUSTYLINE_EDIT_MODE", "vi"),
+                ("RUSTYLINE_KEYSEQ_TIMEOUT", "100"),
+            ]))
+            .unwrap()
+            .build();
+        assert_eq!(Some(100), config.keyseq_timeout());
+    }
+
+    #[test]
+    fn invalid() {
+        Builder::new()
+            .merge_vars(vars(&[("RUSTYLINE_EDIT_MODE", "emacs-vi")]))
+            .unwrap_err();
+        let err = Builder::new()
+            .merge_vars(vars(&[("RUSTYLINE_EDIT_MOD", "vi")]))
+            .unwrap_err();
+        assert!(
+            matches!(err, ReadlineError::Io(ref e) if e.kind() == ErrorKind::InvalidData),
+            "{err}"
+        );
+        assert!(err.to_string().contains("`edit_mod`"), "{err}");
+    }
+
+    #[test]
+    fn unset() {
+        let config = Builder::new()
+            .max_history_entry_len(Some(100))
+            .edit_mode(EditMode::Vi)
+            .merge_vars(vars(&[
+                ("RUSTYLINE_MAX_HISTORY_ENTRY_LEN", ""),
+                ("RUSTYLINE_KEYSEQ_TIMEOUT", ""),
+            ]))
+            .unwrap()
+            .build();
+        assert_eq!(None, config.max_history_entry_len());
+        assert_eq!(None, config.keyseq_timeout());
+        assert_eq!(EditMode::Vi, config.edit_mode());
+    }
 }
@@ -1,5 +1,6 @@
 //! Command processor
 
+use std::collections::HashSet;
 use std::fmt;
 
 use log::debug;
@@ -17,19 +18,22 @@ use crate::keymap::{
 };
 use crate::layout::{Layout, Position, Unit, cwidh};
 use crate::line_buffer::{DeleteListener, Direction, LineBuffer, MAX_LINE, NoListener, WordAction};
-use crate::tty::{Renderer as _, Term, Terminal};
+use crate::tty::Renderer;
 use crate::undo::Changeset;
 use crate::validate::{ValidationContext, ValidationResult};
 
 /// Represent the state during line editing.
 /// Implement rendering.
-pub struct State<'out, 'prompt, H: Helper, P: Prompt + ?Sized> {
-    pub out: &'out mut <Terminal as Term>::Writer,
+pub struct State<'out, 'prompt, H: Helper, P: Prompt + ?Sized, R: Renderer> {
+    pub out: &'out mut R,
     prompt: &'prompt P,    // Prompt to display (rl_prompt)
     prompt_size: Position, // Prompt Unicode/visible width and height
     pub line: LineBuffer,  // Edited line buffer
     pub layout: Layout,
+    pub prompt_row: Option<Unit>, // Screen row of the first row drawn, for mouse clicks
     saved_line_for_history: LineBuffer, // Current edited line before history browsing
+    history_visited: Vec<(usize, String)>, // Entries displayed while browsing history without dups
+    history_seen: HashSet<String>, // Same entries, for lookup
     byte_buffer: [u8; 4],
     pub changes: Changeset, // changes to line, for undo/redo
     pub helper: Option<&'out H>,
@@ -53,9 +57,9 @@ pub enum RefreshKind {
     All,
 }
 
-impl<'out, 'prompt, H: Helper, P: Prompt + ?Sized> State<'out, 'prompt, H, P> {
+impl<'out, 'prompt, H: Helper, P: Prompt + ?Sized, R: Renderer> State<'out, 'prompt, H, P, R> {
     pub fn new(
-        out: &'out mut <Terminal as Term>::Writer,
+        out: &'out mut R,
         prompt: &'prompt P,
         helper: Option<&'out H>,
         ctx: Context<'out>,
@@ -68,7 +72,10 @@ impl<'out, 'prompt, H: Helper, P: Prompt + ?Sized> State<'out, 'prompt, H, P> {
             prompt_size,
             line: LineBuffer::with_capacity(MAX_LINE).can_growth(true),
             layout: Layout::new(gcm),
+            prompt_row: None,
             saved_line_for_history: LineBuffer::with_capacity(MAX_LINE).can_growth(true),
+            history_visited: Vec::new(),
+            history_seen: HashSet::new(),
             byte_buffer: [0; 4],
             changes: Changeset::new(),
             helper,
@@ -89,7 +96,7 @@ impl<'out, 'prompt, H: Helper, P: Prompt + ?Sized> State<'out, 'prompt, H, P> {
     pub fn next_cmd(
         &mut self,
         input_state: &mut InputState,
-        rdr: &mut <Terminal as Term>::Reader,
+        rdr: &mut R::Reader,
         single_esc_abort: bool,
         ignore_external_print: bool,
     ) -> Result<Cmd> {
@@ -104,11 +111,18 @@ impl<'out, 'prompt, H: Helper, P: Prompt + ?Sized> State<'out, 'prompt, H, P> {
                     }
                     Signal::Resize => {
                         debug!(target: "rustyline", "SIGWINCH");
+                        // lines may have been rewrapped by the terminal
+                        self.prompt_row = None;
                         let old_cols = self.out.get_columns();
                         self.out.update_size();
                         let new_cols = self.out.get_columns();
-                        if new_cols != old_cols
-                            && (self.layout.end.row > 0 || self.layout.end.col >= new_cols)
+                        if (new_cols != old_cols
+                            && (self.layout.end.row > 0
+                                || self.layout.end.col >= new_cols
+                                || self.layout.hscroll.is_some()
+                                || self.layout.right_prompt.is_some()))
+                            || self.layout.viewport.is_some()
+                            || self.layout.end.row >= self.out.get_rows()
                         {
                             self.prompt_size = self
                                 .out
@@ -141,14 +155,20 @@ impl<'out, 'prompt, H: Helper, P: Prompt + ?Sized> State<'out, 'prompt, H, P> {
 
     pub fn move_cursor(&mut self, kind: CmdKind) -> Result<()> {
         // calculate the desired position of the cursor
-        let cursor = self
-            .out
-            .calculate_position(&self.line[..self.line.pos()], self.prompt_size);
+        let cursor = self.out.calculate_input_position(
+            self.prompt,
+            &self.line[..self.line.pos()],
+            1,
+            self.prompt_size,
+        );
         if self.layout.cursor == cursor {
             return Ok(());
         }
         if self.highlight_char(kind) {
             self.refresh(self.prompt, self.prompt_size, true, All, Info::NoHint)?;
+        } else if !self.layout.is_row_visible(cursor.row) || self.layout.hscroll.is_some() {
+            // scroll
+            self.repaint(All)?;
         } else {
             self.out.move_cursor(self.layout.cursor, cursor)?;
             self.layout.prompt_size = self.prompt_size;
@@ -160,15 +180,16 @@ impl<'out, 'prompt, H: Helper, P: Prompt + ?Sized> State<'out, 'prompt, H, P> {
     }
 
     pub fn move_cursor_to_end(&mut self) -> Result<()> {
-        if self.layout.cursor == self.layout.end {
+        let end = self.layout.drawn_end();
+        if self.layout.cursor == end {
             return Ok(());
         }
-        self.out.move_cursor(self.layout.cursor, self.layout.end)?;
-        self.layout.cursor = self.layout.end;
+        self.out.move_cursor(self.layout.cursor, end)?;
+        self.layout.cursor = end;
         Ok(())
     }
 
-    pub fn move_cursor_at_leftmost(&mut self, rdr: &mut <Terminal as Term>::Reader) -> Result<()> {
+    pub fn move_cursor_at_leftmost(&mut self, rdr: &mut R::Reader) -> Result<()> {
         self.out.move_cursor_at_leftmost(rdr)
     }
 
@@ -199,9 +220,19 @@ impl<'out, 'prompt, H: Helper, P: Prompt + ?Sized> State<'out, 'prompt, H, P> {
             self.out
                 .refresh_line(prompt, &self.line, info, None, &self.layout, highlighter)?;
         } else {
-            let new_layout = self
-                .out
-                .compute_layout(prompt_size, default_prompt, &self.line, info);
+            let mut new_layout =
+                self.out
+                    .compute_layout(prompt, prompt_size, default_prompt, &self.line, info);
+            new_layout.scroll(&self.layout, self.out.get_rows());
+            new_layout.right_prompt = prompt.right_raw().and_then(|right_prompt| {
+                self.out.compute_right_prompt(
+                    &new_layout,
+                    right_prompt,
+                    prompt.right_position(),
+                    &self.line,
+                    info,
+                )
+            });
 
             debug!(target: "rustyline", "old layout: {:?}", self.layout);
             debug!(target: "rustyline", "new layout: {new_layout:?}");
@@ -214,6 +245,14 @@ impl<'out, 'prompt, H: Helper, P: Prompt + ?Sized> State<'out, 'prompt, H, P> {
                 highlighter,
             )?;
             self.layout = new_layout;
+            if let Some(row) = self.prompt_row {
+                // the terminal scrolls up when rows are drawn below the last one
+                let rows = self.out.get_rows();
+                let end_row = self.layout.screen_end_row();
+                if row + end_row >= rows {
+                    self.prompt_row = Some(rows.saturating_sub(end_row + 1));
+                }
+            }
         }
 
         Ok(())
@@ -249,6 +288,22 @@ impl<'out, 'prompt, H: Helper, P: Prompt + ?Sized> State<'out, 'prompt, H, P> {
         }
     }
 
+    /// Re-render the accepted line with the transient prompt (if any), without
+    /// hint nor right prompt.
+    pub fn refresh_transient_prompt(&mut self) -> Result<()> {
+        let Some(raw) = self.prompt.transient_raw() else {
+            return Ok(());
+        };
+        let transient = (raw, self.prompt.transient_styled().unwrap_or(raw));
+        let prompt_size = self.out.calculate_position(raw, Position::default());
+        self.line.move_buffer_end();
+        self.hint = None;
+        self.highlight_char(CmdKind::ForcedRefresh);
+        self.refresh(&transient, prompt_size, true, All, Info::NoHint)?;
+        self.prompt_size = prompt_size;
+        Ok(())
+    }
+
     pub fn is_default_prompt(&self) -> bool {
         self.layout.default_prompt
     }
@@ -281,13 +336,13 @@ impl<'out, 'prompt, H: Helper, P: Prompt + ?Sized> State<'out, 'prompt, H, P> {
     }
 }
 
-impl<H: Helper, P: Prompt + ?Sized> Invoke for State<'_, '_, H, P> {
+impl<H: Helper, P: Prompt + ?Sized, R: Renderer> Invoke for State<'_, '_, H, P, R> {
     fn input(&self) -> &str {
         self.line.as_str()
     }
 }
 
-impl<H: Helper, P: Prompt + ?Sized> Refresher for State<'_, '_, H, P> {
+impl<H: Helper, P: Prompt + ?Sized, R: Renderer> Refresher for State<'_, '_, H, P, R> {
     fn refresh_line(&mut self) -> Result<()> {
         self.hint();
         self.highlight_char(CmdKind::Other);
@@ -346,12 +401,14 @@ impl<H: Helper, P: Prompt + ?Sized> Refresher for State<'_, '_, H, P> {
         if !msg.ends_with('\n') {
             self.out.write_and_flush("\n")?;
         }
+        // the prompt is drawn below the message
+        self.prompt_row = None;
         self.repaint(RefreshKind::Min)?;
         self.out.end_synchronized_update()
     }
 }
 
-impl<H: Helper, P: Prompt + ?Sized> fmt::Debug for State<'_, '_, H, P> {
+impl<H: Helper, P: Prompt + ?Sized, R: Renderer> fmt::Debug for State<'_, '_, H, P, R> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("State")
             .field("prompt", &self.prompt.raw())
@@ -364,11 +421,12 @@ impl<H: Helper, P: Prompt + ?Sized> fmt::Debug for State<'_, '_, H, P> {
     }
 }
 
-impl<H: Helper, P: Prompt + ?Sized> State<'_, '_, H, P> {
+impl<H: Helper, P: Prompt + ?Sized, R: Renderer> State<'_, '_, H, P, R> {
     pub fn clear_screen(&mut self) -> Result<()> {
         self.out.clear_screen()?;
         self.layout.cursor = Position::default();
         self.layout.end = Position::default();
+        self.prompt_row = Some(0);
         Ok(())
     }
 
@@ -385,6 +443,11 @@ impl<H: Helper, P: Prompt + ?Sized> State<'_, '_, H, P> {
                     && self.layout.cursor.col + width < self.out.get_columns()
                     && (self.hint.is_none() && no_previous_msg) // TODO refresh only current line
                     && !highlight_char
+                    && self.layout.hscroll.is_none()
+                    && self
+                        .layout
+                        .right_prompt
+                        .is_none_or(|rp| self.layout.end.col + width < rp.col)
                 {
                     // Avoid a full update of the line in the trivial case.
                     self.layout.cursor.col += width;
@@ -506,6 +569,54 @@ impl<H: Helper, P: Prompt + ?Sized> State<'_, '_, H, P> {
         }
     }
 
+    /// Move cursor to the screen position (`row`, `col`) like a mouse click.
+    ///
+    /// Clicks outside of the input, or while [`prompt_row`](Self::prompt_row)
+    /// is unknown, are ignored.
+    pub fn edit_move_to_screen(&mut self, row: Unit, col: Unit) -> Result<()> {
+        let Some(row) = self
+            .prompt_row
+            .and_then(|prompt_row| row.checked_sub(prompt_row))
+            .and_then(|row| self.layout.row_at(row))
+        else {
+            return Ok(());
+        };
+        let target = Position {
+            col: col + self.layout.hscroll.map_or(0, |hs| hs.offset),
+            row,
+        };
+        // last grapheme boundary displayed before the target
+        let mut new_pos = 0;
+        let mut pos = self.prompt_size;
+        let mut line_number = 1;
+        for (i, g) in self.line.grapheme_indices(true) {
+            if pos > target {
+                break;
+            }
+            new_pos = i;
+            pos = if self.layout.hscroll.is_some() {
+                Position {
+                    col: pos.col + self.layout.width(g),
+                    ..pos
+                }
+            } else {
+                self.out
+                    .calculate_input_position(self.prompt, g, line_number, pos)
+            };
+            if g == "\n" {
+                line_number += 1;
+            }
+        }
+        if pos <= target {
+            new_pos = self.line.len();
+        }
+        if new_pos == self.line.pos() {
+            return Ok(());
+        }
+        self.line.set_pos(new_pos);
+        self.move_cursor(CmdKind::MoveCursor)
+    }
+
     /// Move cursor to the start of the buffer.
     pub fn edit_move_buffer_start(&mut self) -> Result<()> {
         if self.line.move_buffer_start() {
@@ -543,7 +654,7 @@ impl<H: Helper, P: Prompt + ?Sized> State<'_, '_, H, P> {
             end: self.line.len(),
             cursor_shift: 0,
             end_shift: 0,
-            trivial: self.layout.cursor.row == self.layout.end.row,
+            trivial: self.layout.cursor.row == self.layout.end.row && self.layout.hscroll.is_none(),
         };
         impl DeleteListener for Proxy<'_> {
             fn start_killing(&mut self) {
@@ -587,6 +698,8 @@ impl<H: Helper, P: Prompt + ?Sized> State<'_, '_, H, P> {
                 && end_shift <= self.layout.end.col
                 && (self.hint.is_none() && no_previous_msg)
                 && !highlight_char
+                && self.layout.right_prompt.is_none()
+            // cleared with EOL
             {
                 // Avoid a full update of the line in the trivial case.
                 debug_assert!(self.line.is_cursor_at_end());
@@ -686,11 +799,12 @@ impl<H: Helper, P: Prompt + ?Sized> State<'_, '_, H, P> {
     /// Substitute the currently edited line with the next or previous history
     /// entry.
     pub fn edit_history_next(&mut self, prev: bool) -> Result<()> {
-        let history = self.ctx.history;
-        if history.is_empty() {
+        self.ctx.sync()?;
+        let len = self.ctx.history.len();
+        if len == 0 {
             return Ok(());
         }
-        if self.ctx.history_index == history.len() {
+        if self.ctx.history_index == len {
             if prev {
                 // Save the current edited line before overwriting it
                 self.backup();
@@ -706,15 +820,19 @@ impl<H: Helper, P: Prompt + ?Sized> State<'_, '_, H, P> {
             self.ctx.history_index += 1;
             (self.ctx.history_index, SearchDirection::Forward)
         };
-        if idx < history.len() {
-            if let Some(r) = history.get(idx, dir)? {
+        if idx < len {
+            if let Some(r) = self.ctx.history.get(idx, dir)? {
                 let buf = r.entry;
                 self.ctx.history_index = r.idx;
                 self.changes.begin();
                 self.line.update(&buf, buf.len(), &mut self.changes);
                 self.changes.end();
-            } else {
+            } else if prev {
                 return Ok(());
+            } else {
+                // no more entries (in the current context): restore current edited line
+                self.ctx.history_index = len;
+                self.restore();
             }
         } else {
             // Restore current edited line
@@ -723,9 +841,62 @@ impl<H: Helper, P: Prompt + ?Sized> State<'_, '_, H, P> {
         self.refresh_line()
     }
 
+    /// Like [`Self::edit_history_next`] but entries already displayed are
+    /// skipped.
+    pub fn edit_history_next_unique(&mut self, prev: bool) -> Result<()> {
+        if self.history_visited.last().map(|(idx, _)| *idx) != Some(self.ctx.history_index) {
+            // history index moved by another command
+            self.history_visited.clear();
+            self.history_seen.clear();
+        }
+        self.ctx.sync()?;
+        let len = self.ctx.history.len();
+        if !prev {
+            let Some((_, entry)) = self.history_visited.pop() else {
+                return self.edit_history_next(false);
+            };
+            self.history_seen.remove(&entry);
+            if let Some((idx, entry)) = self.history_visited.last() {
+                self.ctx.history_index = *idx;
+                let entry = entry.clone();
+                self.changes.begin();
+                self.line.update(&entry, entry.len(), &mut self.changes);
+                self.changes.end();
+            } else {
+                self.ctx.history_index = len;
+                // Restore current edited line
+                self.restore();
+            }
+            return self.refresh_line();
+        }
+        let mut idx = self.ctx.history_index;
+        while idx > 0 {
+            let Some(r) = self.ctx.history.get(idx - 1, SearchDirection::Reverse)? else {
+                break;
+            };
+            if !self.history_seen.contains(r.entry.as_ref()) {
+                let (idx, entry) = (r.idx, r.entry.into_owned());
+                if self.ctx.history_index == len {
+                    // Save the current edited line before overwriting it
+                    self.backup();
+                }
+                self.ctx.history_index = idx;
+                self.changes.begin();
+                self.line.update(&entry, entry.len(), &mut self.changes);
+                self.changes.end();
+                self.history_seen.insert(entry.clone());
+                self.history_visited.push((idx, entry));
+                return self.refresh_line();
+            }
+            idx = r.idx;
+        }
+        Ok(())
+    }
+
     // Non-incremental, anchored search
     pub fn edit_history_search(&mut self, dir: SearchDirection) -> Result<()> {
-        let history = self.ctx.history;
+        self.ctx.sync()?;
+        let history = &*self.ctx.history;
         if history.is_empty() {
             return self.out.beep();
         }
@@ -756,11 +927,12 @@ impl<H: Helper, P: Prompt + ?Sized> State<'_, '_, H, P> {
 
     /// Substitute the currently edited line with the first/last history entry.
     pub fn edit_history(&mut self, first: bool) -> Result<()> {
-        let history = self.ctx.history;
-        if history.is_empty() {
+        self.ctx.sync()?;
+        let len = self.ctx.history.len();
+        if len == 0 {
             return Ok(());
         }
-        if self.ctx.history_index == history.len() {
+        if self.ctx.history_index == len {
             if first {
                 // Save the current edited line before overwriting it
                 self.backup();
@@ -771,7 +943,7 @@ impl<H: Helper, P: Prompt + ?Sized> State<'_, '_, H, P> {
             return Ok(());
         }
         if first {
-            if let Some(r) = history.get(0, SearchDirection::Forward)? {
+            if let Some(r) = self.ctx.history.get(0, SearchDirection::Forward)? {
                 let buf = r.entry;
                 self.ctx.history_index = r.idx;
                 self.changes.begin();
@@ -781,7 +953,7 @@ impl<H: Helper, P: Prompt + ?Sized> State<'_, '_, H, P> {
                 return Ok(());
             }
         } else {
-            self.ctx.history_index = history.len();
+            self.ctx.history_index = len;
             // Restore current edited line
             self.restore();
         }
@@ -800,19 +972,22 @@ impl<H: Helper, P: Prompt + ?Sized> State<'_, '_, H, P> {
 
 #[cfg(test)]
 pub fn init_state<'out, H: Helper>(
-    out: &'out mut <Terminal as Term>::Writer,
+    out: &'out mut crate::tty::Sink,
     line: &str,
     pos: usize,
     helper: Option<&'out H>,
     history: &'out crate::history::DefaultHistory,
-) -> State<'out, 'static, H, str> {
+) -> State<'out, 'static, H, str, crate::tty::Sink> {
     State {
         out,
         prompt: "",
         prompt_size: Position::default(),
         line: LineBuffer::init(line, pos),
         layout: Layout::default(),
+        prompt_row: None,
         saved_line_for_history: LineBuffer::with_capacity(100),
+        history_visited: Vec::new(),
+        history_seen: HashSet::new(),
         byte_buffer: [0; 4],
         changes: Changeset::new(),
         helper,
@@ -824,9 +999,13 @@ pub fn init_state<'out, H: Helper>(
 
 #[cfg(test)]
 mod test {
-    use super::init_state;
+    use super::{State, init_state};
     use crate::history::{DefaultHistory, History as _};
+    use crate::keymap::Refresher as _;
+    use crate::layout::Position;
+    use crate::line_buffer::NoListener;
     use crate::tty::Sink;
+    use crate::{Context, Prompt};
 
     #[test]
     fn edit_history_next() {
@@ -866,4 +1045,37 @@ mod test {
         assert_eq!(2, s.ctx.history_index);
         assert_eq!(line, s.line.as_str());
     }
+
+    #[test]
+    fn refresh_transient_prompt() {
+        struct TransientPrompt;
+        impl Prompt for TransientPrompt {
+            fn raw(&self) -> &str {
+                "user@host ~/dir (main) > "
+            }
+
+            fn transient_raw(&self) -> Option<&str> {
+                Some("> ")
+            }
+        }
+
+        let mut out = Sink::default();
+        let history = DefaultHistory::new();
+        let helper: Option<()> = None;
+        let mut s = State::new(
+            &mut out,
+            &TransientPrompt,
+            helper.as_ref(),
+            Context::new(&history),
+        );
+        s.line.update("ls -l", 0, &mut NoListener);
+        s.refresh_line().unwrap();
+        assert_eq!(25, s.layout.prompt_size.col);
+
+        s.refresh_transient_prompt().unwrap();
+        assert_eq!(2, s.layout.prompt_size.col);
+        assert_eq!(Position { col: 7, row: 0 }, s.layout.cursor);
+        assert_eq!(s.layout.cursor, s.layout.end);
+        assert!(s.line.is_cursor_at_end());
+    }
 }
@@ -31,6 +31,9 @@ pub enum ReadlineError {
     /// Error related to SQLite history backend
     #[cfg(feature = "with-sqlite-history")]
     SQLiteError(rusqlite::Error),
+    /// Input read from a script rejected by the `Validator` (with its
+    /// message, if any) or incomplete at the end of the script
+    Validation(String),
 }
 
 impl fmt::Display for ReadlineError {
@@ -48,6 +51,7 @@ impl fmt::Display for ReadlineError {
             Self::SystemError(ref err) => err.fmt(f),
             #[cfg(feature = "with-sqlite-history")]
             Self::SQLiteError(ref err) => err.fmt(f),
+            Self::Validation(ref msg) => write!(f, "Invalid input: {msg}"),
         }
     }
 }
@@ -67,6 +71,7 @@ impl Error for ReadlineError {
             Self::SystemError(_) => None,
             #[cfg(feature = "with-sqlite-history")]
             Self::SQLiteError(ref err) => Some(err),
+            Self::Validation(_) => None,
         }
     }
 }
@@ -177,3 +182,17 @@ impl From<rusqlite::Error> for ReadlineError {
         Self::SQLiteError(err)
     }
 }
+
+#[cfg(feature = "with-config-file")]
+impl From<toml::de::Error> for ReadlineError {
+    fn from(err: toml::de::Error) -> Self {
+        Self::Io(io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+#[cfg(feature = "with-config-file")]
+impl From<toml::ser::Error> for ReadlineError {
+    fn from(err: toml::ser::Error) -> Self {
+        Self::Io(io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
@@ -1,7 +1,9 @@
 //! Hints (suggestions at the right of the prompt as you type).
 
-use crate::Context;
-use crate::history::SearchDirection;
+use std::io;
+
+use crate::history::{SearchDirection, SearchMode};
+use crate::{Context, HistoryRanking, ReadlineError};
 
 /// A hint returned by Hinter
 pub trait Hint {
@@ -41,15 +43,25 @@ impl Hinter for () {
 }
 
 /// Add suggestion based on previous history entries matching current user
-/// input.
+/// input: the most recent one or the best one according to a ranking (see
+/// [`History::ranked`](crate::history::History::ranked)).
 #[derive(Default)]
-pub struct HistoryHinter {}
+pub struct HistoryHinter {
+    ranking: HistoryRanking,
+}
 
 impl HistoryHinter {
     /// Create a new `HistoryHinter`
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Create a new `HistoryHinter` suggesting the best entry according to
+    /// `ranking` (if supported by the history, the most recent one otherwise)
+    #[must_use]
+    pub fn with_ranking(ranking: HistoryRanking) -> Self {
+        Self { ranking }
+    }
 }
 
 impl Hinter for HistoryHinter {
@@ -59,6 +71,22 @@ impl Hinter for HistoryHinter {
         if line.is_empty() || pos < line.len() {
             return None;
         }
+        if self.ranking != HistoryRanking::Recency {
+            match ctx
+                .history
+                .ranked(line, SearchMode::Prefix, self.ranking, 1)
+            {
+                Ok(matches) => {
+                    return matches
+                        .first()
+                        .filter(|sr| sr.entry != line)
+                        .map(|sr| sr.entry[pos..].to_owned());
+                }
+                // unsupported by a custom history
+                Err(ReadlineError::Io(err)) if err.kind() == io::ErrorKind::Unsupported => {}
+                Err(_) => return None,
+            }
+        }
         let start = if ctx.history_index() == ctx.history().len() {
             ctx.history_index().saturating_sub(1)
         } else {
@@ -88,8 +116,25 @@ mod test {
     pub fn empty_history() {
         let history = DefaultHistory::new();
         let ctx = Context::new(&history);
-        let hinter = HistoryHinter {};
+        let hinter = HistoryHinter::new();
         let hint = hinter.hint("test", 4, &ctx);
         assert_eq!(None, hint);
     }
+
+    #[test]
+    pub fn ranking() {
+        use crate::HistoryRanking;
+        use crate::history::History as _;
+
+        let mut history = DefaultHistory::new();
+        for line in ["make test", "ls", "make test", "make"] {
+            history.add(line).unwrap();
+        }
+        let ctx = Context::new(&history);
+        let hint = HistoryHinter::new().hint("ma", 2, &ctx);
+        assert_eq!(Some("ke".to_owned()), hint);
+        let hinter = HistoryHinter::with_ranking(HistoryRanking::Frequency);
+        let hint = hinter.hint("ma", 2, &ctx);
+        assert_eq!(Some("ke test".to_owned()), hint);
+    }
 }
@@ -1,21 +1,21 @@
 //! History API
 
 use std::borrow::Cow;
-use std::collections::{VecDeque, vec_deque};
+use std::collections::{HashMap, VecDeque, vec_deque};
 #[cfg(feature = "with-file-history")]
 use std::fs::{File, OpenOptions};
-#[cfg(feature = "with-file-history")]
-use std::io::SeekFrom;
 use std::ops::Index;
 use std::path::Path;
-#[cfg(feature = "with-file-history")]
+use std::sync::Arc;
 use std::time::SystemTime;
 
 #[cfg(feature = "with-file-history")]
 use log::{debug, warn};
 
 use super::Result;
-use crate::config::{Config, HistoryDuplicates};
+use crate::config::{Config, HistoryDuplicates, HistoryRanking};
+#[cfg(feature = "with-file-history")]
+use crate::error::ReadlineError;
 
 /// Search direction
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -26,6 +26,40 @@ pub enum SearchDirection {
     Reverse,
 }
 
+/// How [`History::search`] matches entries
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SearchMode {
+    /// Entries containing the search term
+    #[default]
+    Substring,
+    /// Entries starting with the search term
+    Prefix,
+    /// Entries containing the search term, ignoring case
+    CaseInsensitive,
+    /// Entries matching the search term as a regular expression (requires
+    /// the `regex` feature)
+    Regex,
+    /// Entries matching the search term as a full-text query: with
+    /// `SQLiteHistory`, an FTS4 query (whose last word is a prefix);
+    /// otherwise, entries containing all its words, ignoring case
+    Fts,
+}
+
+impl SearchMode {
+    /// Next mode (used to cycle through modes in incremental search)
+    #[must_use]
+    pub fn next(self) -> Self {
+        match self {
+            Self::Substring => Self::Prefix,
+            Self::Prefix => Self::CaseInsensitive,
+            Self::CaseInsensitive => Self::Regex,
+            Self::Regex => Self::Fts,
+            Self::Fts => Self::Substring,
+        }
+    }
+}
+
 /// History search result
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct SearchResult<'a> {
@@ -37,6 +71,37 @@ pub struct SearchResult<'a> {
     pub pos: usize,
 }
 
+/// What to do with a new history entry (see [`HistoryFilter`])
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FilterResult {
+    /// Add the entry as is
+    Keep,
+    /// Do not add the entry
+    Reject,
+    /// Add this entry instead (like the original one with secrets redacted)
+    Rewrite(String),
+    /// Add the entry but never save it: it can only be navigated in this
+    /// session (`SQLiteHistory` keeps it in memory, out of the database)
+    SessionOnly,
+}
+
+/// Filter consulted before an entry is added to the history (after
+/// [`History::set_filter`]), in addition to the `ignore_space` / `ignore_dups`
+/// rules which are applied to the filtered entry.
+///
+/// Entries loaded from a file or database are not filtered.
+pub trait HistoryFilter: Send + Sync {
+    /// Decide what to do with `line`
+    fn filter(&self, line: &str) -> FilterResult;
+}
+
+impl<F: Fn(&str) -> FilterResult + Send + Sync> HistoryFilter for F {
+    fn filter(&self, line: &str) -> FilterResult {
+        self(line)
+    }
+}
+
 /// Interface for navigating/loading/storing history
 // TODO Split navigation part from backend part
 pub trait History {
@@ -76,6 +141,20 @@ pub trait History {
     /// Return false if the `line` has been ignored (blank line / duplicate /
     /// ...).
     fn add_owned(&mut self, line: String) -> Result<bool>; // TODO check AsRef<str> + Into<String> vs object safe
+    /// Add a new entry in the history with the time it was entered (like
+    /// when entries are imported from another history).
+    ///
+    /// By default, the timestamp is ignored.
+    fn add_with_timestamp(&mut self, line: String, _: SystemTime) -> Result<bool> {
+        self.add_owned(line)
+    }
+
+    /// Return the time when the entry at position `index` was entered.
+    ///
+    /// By default, `None` (unknown).
+    fn timestamp(&self, _: usize) -> Result<Option<SystemTime>> {
+        Ok(None)
+    }
 
     /// Return the number of entries in the history.
     #[must_use]
@@ -115,12 +194,18 @@ pub trait History {
     /// Ignore consecutive duplicates
     fn ignore_dups(&mut self, yes: bool) -> Result<()>;
 
+    /// Set how duplicates are handled.
+    ///
+    /// By default, [`HistoryDuplicates::EraseDups`] is handled like
+    /// [`HistoryDuplicates::IgnoreConsecutive`].
+    fn set_duplicates(&mut self, duplicates: HistoryDuplicates) -> Result<()> {
+        self.ignore_dups(duplicates != HistoryDuplicates::AlwaysAdd)
+    }
+
     /// Ignore lines which begin with a space or not
     fn ignore_space(&mut self, yes: bool);
 
     /// Save the history in the specified file.
-    // TODO history_truncate_file
-    // https://tiswww.case.edu/php/chet/readline/history.html#IDX31
     fn save(&mut self, path: &Path) -> Result<()>; // FIXME Path vs AsRef<Path>
 
     /// Append new entries in the specified file.
@@ -133,9 +218,54 @@ pub trait History {
     /// Will return `Err` if path does not already exist or could not be read.
     fn load(&mut self, path: &Path) -> Result<()>; // FIXME Path vs AsRef<Path>
 
+    /// Truncate the history stored in the specified file to its last `len`
+    /// entries. In-memory history is not modified.
+    ///
+    /// Like [history_truncate_file](https://tiswww.case.edu/php/chet/readline/history.html#IDX31).
+    ///
+    /// By default, it's unsupported.
+    fn truncate_file(&mut self, _path: &Path, _len: usize) -> Result<()> {
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported).into())
+    }
+
     /// Clear in-memory history
     fn clear(&mut self) -> Result<()>;
 
+    /// Set the context (like the working directory, the host or an
+    /// application-defined tag) stored with entries added from now on.
+    ///
+    /// By default, it's unsupported.
+    fn set_context(&mut self, _context: Option<String>) -> Result<()> {
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported).into())
+    }
+
+    /// Restrict history queries ([`History::get`], [`History::search`],
+    /// [`History::starts_with`] and so navigation, incremental search and
+    /// hints) to entries added in `context` (see [`History::set_context`]).
+    /// With `None`, all entries are returned.
+    ///
+    /// By default, it's unsupported.
+    fn set_context_filter(&mut self, _context: Option<String>) -> Result<()> {
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported).into())
+    }
+
+    /// Set (or remove) the filter applied to new entries.
+    ///
+    /// By default, it's unsupported.
+    fn set_filter(&mut self, _filter: Option<Box<dyn HistoryFilter>>) -> Result<()> {
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported).into())
+    }
+
+    /// Pick up entries added by other sessions to the history file / database
+    /// since last load / save / sync (see [`Config::history_sharing`]).
+    ///
+    /// Return true if the history has been reloaded.
+    ///
+    /// By default, there is no other session.
+    fn sync(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+
     // termwiz: fn search(
     //         &self,
     //         idx: HistoryIndex,
@@ -161,6 +291,28 @@ pub trait History {
         dir: SearchDirection,
     ) -> Result<Option<SearchResult<'_>>>;
 
+    /// Search history like [`search`](History::search) but matching entries
+    /// according to `mode`.
+    ///
+    /// An unsupported `mode` gives an [`std::io::ErrorKind::Unsupported`]
+    /// error.
+    ///
+    /// By default, only [`SearchMode::Substring`] is supported (by
+    /// [`search`](History::search)).
+    fn search_with_mode(
+        &self,
+        term: &str,
+        start: usize,
+        dir: SearchDirection,
+        mode: SearchMode,
+    ) -> Result<Option<SearchResult<'_>>> {
+        if mode == SearchMode::Substring {
+            self.search(term, start, dir)
+        } else {
+            Err(std::io::Error::from(std::io::ErrorKind::Unsupported).into())
+        }
+    }
+
     /// Anchored search
     fn starts_with(
         &self,
@@ -169,6 +321,23 @@ pub trait History {
         dir: SearchDirection,
     ) -> Result<Option<SearchResult<'_>>>;
 
+    /// Return at most `limit` distinct entries matching `term` (according to
+    /// `mode`), best first according to `ranking`.
+    ///
+    /// Used by incremental search (see [`Config::history_ranking`]) and
+    /// [`HistoryHinter`](crate::hint::HistoryHinter).
+    ///
+    /// By default, it's unsupported.
+    fn ranked(
+        &self,
+        _term: &str,
+        _mode: SearchMode,
+        _ranking: HistoryRanking,
+        _limit: usize,
+    ) -> Result<Vec<SearchResult<'_>>> {
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported).into())
+    }
+
     /* TODO How ? DoubleEndedIterator may be difficult to implement (for an SQLite backend)
     /// Return a iterator.
     #[must_use]
@@ -179,9 +348,50 @@ pub trait History {
 /// Transient in-memory history implementation.
 pub struct MemHistory {
     entries: VecDeque<String>,
+    infos: VecDeque<EntryInfo>,
+    /// Size (in bytes) of all entries
+    bytes: usize,
     max_len: usize,
+    max_entry_len: Option<usize>,
+    max_bytes: Option<usize>,
     ignore_space: bool,
-    ignore_dups: bool,
+    duplicates: HistoryDuplicates,
+    /// Sequence number of each entry (only with
+    /// [`HistoryDuplicates::EraseDups`])
+    index: HashMap<String, u64>,
+    next_seq: u64,
+    /// Index of the entry erased by the last insertion
+    erased: Option<usize>,
+    filter: Option<Box<dyn HistoryFilter>>,
+    /// Context of new entries
+    context: Option<Arc<str>>,
+    /// Context of returned entries
+    context_filter: Option<String>,
+}
+
+/// Details of a [`MemHistory`] entry
+#[derive(Clone)]
+struct EntryInfo {
+    /// When the entry was entered (unknown for entries loaded from legacy
+    /// files)
+    time: Option<SystemTime>,
+    /// `false` for [`FilterResult::SessionOnly`] entries
+    persistent: bool,
+    /// Increasing number assigned on insertion
+    seq: u64,
+    /// See [`History::set_context`]
+    context: Option<Arc<str>>,
+}
+
+impl EntryInfo {
+    fn new(time: Option<SystemTime>) -> Self {
+        Self {
+            time,
+            persistent: true,
+            seq: 0,
+            context: None,
+        }
+    }
 }
 
 impl MemHistory {
@@ -193,15 +403,27 @@ impl MemHistory {
 
     /// Customized constructor with:
     /// - [`Config::max_history_size()`],
+    /// - [`Config::max_history_entry_len()`],
+    /// - [`Config::max_history_bytes()`],
     /// - [`Config::history_ignore_space()`],
     /// - [`Config::history_duplicates()`].
     #[must_use]
     pub fn with_config(config: &Config) -> Self {
         Self {
             entries: VecDeque::new(),
+            infos: VecDeque::new(),
+            bytes: 0,
             max_len: config.max_history_size(),
+            max_entry_len: config.max_history_entry_len(),
+            max_bytes: config.max_history_bytes(),
             ignore_space: config.history_ignore_space(),
-            ignore_dups: config.history_duplicates() == HistoryDuplicates::IgnoreConsecutive,
+            duplicates: config.history_duplicates(),
+            index: HashMap::new(),
+            next_seq: 0,
+            erased: None,
+            filter: None,
+            context: None,
+            context_filter: None,
         }
     }
 
@@ -227,6 +449,9 @@ impl MemHistory {
                     .skip(self.len() - 1 - start)
                     .enumerate()
                 {
+                    if !self.in_context(start - idx) {
+                        continue;
+                    }
                     if let Some(cursor) = test(entry) {
                         return Some(SearchResult {
                             idx: start - idx,
@@ -239,6 +464,9 @@ impl MemHistory {
             }
             SearchDirection::Forward => {
                 for (idx, entry) in self.entries.iter().skip(start).enumerate() {
+                    if !self.in_context(idx + start) {
+                        continue;
+                    }
                     if let Some(cursor) = test(entry) {
                         return Some(SearchResult {
                             idx: idx + start,
@@ -252,6 +480,13 @@ impl MemHistory {
         }
     }
 
+    /// Tell if the entry at `idx` matches the context filter
+    fn in_context(&self, idx: usize) -> bool {
+        self.context_filter
+            .as_deref()
+            .is_none_or(|filter| self.infos[idx].context.as_deref() == Some(filter))
+    }
+
     fn ignore(&self, line: &str) -> bool {
         if self.max_len == 0 {
             return true;
@@ -261,7 +496,12 @@ impl MemHistory {
         {
             return true;
         }
-        if self.ignore_dups
+        if self.max_entry_len.is_some_and(|max| line.len() > max)
+            || self.max_bytes.is_some_and(|max| line.len() > max)
+        {
+            return true;
+        }
+        if self.duplicates == HistoryDuplicates::IgnoreConsecutive
             && let Some(s) = self.entries.back()
             && s == line
         {
@@ -270,11 +510,76 @@ impl MemHistory {
         false
     }
 
-    fn insert(&mut self, line: String) {
+    fn insert(&mut self, line: String, mut info: EntryInfo) {
+        info.seq = self.next_seq;
+        self.next_seq += 1;
+        self.erased = None;
+        if self.duplicates == HistoryDuplicates::EraseDups
+            && let Some(seq) = self.index.insert(line.clone(), info.seq)
+            && let Ok(idx) = self.infos.binary_search_by_key(&seq, |info| info.seq)
+        {
+            if let Some(entry) = self.entries.remove(idx) {
+                self.bytes -= entry.len();
+            }
+            self.infos.remove(idx);
+            self.erased = Some(idx);
+        }
         if self.entries.len() == self.max_len {
-            self.entries.pop_front();
+            self.pop_front();
+        }
+        if let Some(max) = self.max_bytes {
+            while self.bytes + line.len() > max {
+                self.pop_front();
+            }
         }
+        self.bytes += line.len();
         self.entries.push_back(line);
+        self.infos.push_back(info);
+    }
+
+    fn pop_front(&mut self) {
+        let info = self.infos.pop_front();
+        if let Some(entry) = self.entries.pop_front() {
+            self.bytes -= entry.len();
+            if info.is_some_and(|info| self.index.get(&entry) == Some(&info.seq)) {
+                self.index.remove(&entry);
+            }
+        }
+    }
+
+    /// Add an unfiltered entry
+    fn add_entry(&mut self, line: String, info: EntryInfo) -> bool {
+        if self.ignore(&line) {
+            return false;
+        }
+        self.insert(line, info);
+        true
+    }
+
+    /// Add a new entry entered at `time`
+    fn add_new(&mut self, line: String, time: SystemTime) -> bool {
+        let mut info = EntryInfo::new(Some(time));
+        info.context.clone_from(&self.context);
+        let line = match self.filter.as_ref().map(|filter| filter.filter(&line)) {
+            None | Some(FilterResult::Keep) => line,
+            Some(FilterResult::Reject) => return false,
+            Some(FilterResult::Rewrite(line)) => line,
+            Some(FilterResult::SessionOnly) => {
+                info.persistent = false;
+                line
+            }
+        };
+        self.add_entry(line, info)
+    }
+
+    /// Replace all entries by the ones of `other`
+    #[cfg(feature = "with-file-history")]
+    fn replace_entries(&mut self, other: Self) {
+        self.entries = other.entries;
+        self.infos = other.infos;
+        self.bytes = other.bytes;
+        self.index = other.index;
+        self.next_seq = other.next_seq;
     }
 }
 
@@ -285,7 +590,17 @@ impl Default for MemHistory {
 }
 
 impl History for MemHistory {
-    fn get(&self, index: usize, _: SearchDirection) -> Result<Option<SearchResult<'_>>> {
+    fn get(&self, index: usize, dir: SearchDirection) -> Result<Option<SearchResult<'_>>> {
+        let index = if index >= self.len() || self.context_filter.is_none() {
+            Some(index)
+        } else if dir == SearchDirection::Forward {
+            (index..self.len()).find(|&idx| self.in_context(idx))
+        } else {
+            (0..=index).rev().find(|&idx| self.in_context(idx))
+        };
+        let Some(index) = index else {
+            return Ok(None);
+        };
         Ok(self
             .entries
             .get(index)
@@ -299,19 +614,22 @@ impl History for MemHistory {
     }
 
     fn add(&mut self, line: &str) -> Result<bool> {
-        if self.ignore(line) {
+        if self.filter.is_none() && self.ignore(line) {
             return Ok(false);
         }
-        self.insert(line.to_owned());
-        Ok(true)
+        Ok(self.add_new(line.to_owned(), SystemTime::now()))
     }
 
     fn add_owned(&mut self, line: String) -> Result<bool> {
-        if self.ignore(&line) {
-            return Ok(false);
-        }
-        self.insert(line);
-        Ok(true)
+        Ok(self.add_new(line, SystemTime::now()))
+    }
+
+    fn add_with_timestamp(&mut self, line: String, timestamp: SystemTime) -> Result<bool> {
+        Ok(self.add_new(line, timestamp))
+    }
+
+    fn timestamp(&self, index: usize) -> Result<Option<SystemTime>> {
+        Ok(self.infos.get(index).and_then(|info| info.time))
     }
 
     fn len(&self) -> usize {
@@ -324,14 +642,34 @@ impl History for MemHistory {
 
     fn set_max_len(&mut self, len: usize) -> Result<()> {
         self.max_len = len;
-        if self.len() > len {
-            self.entries.drain(..self.len() - len);
+        while self.len() > len {
+            self.pop_front();
         }
         Ok(())
     }
 
     fn ignore_dups(&mut self, yes: bool) -> Result<()> {
-        self.ignore_dups = yes;
+        self.set_duplicates(if yes {
+            HistoryDuplicates::IgnoreConsecutive
+        } else {
+            HistoryDuplicates::AlwaysAdd
+        })
+    }
+
+    fn set_duplicates(&mut self, duplicates: HistoryDuplicates) -> Result<()> {
+        let erase = duplicates == HistoryDuplicates::EraseDups;
+        let reindex = erase != (self.duplicates == HistoryDuplicates::EraseDups);
+        self.duplicates = duplicates;
+        if reindex {
+            // remove existing duplicates
+            let entries = std::mem::take(&mut self.entries);
+            let infos = std::mem::take(&mut self.infos);
+            self.bytes = 0;
+            self.index.clear();
+            for (entry, info) in entries.into_iter().zip(infos) {
+                self.insert(entry, info);
+            }
+        }
         Ok(())
     }
 
@@ -353,6 +691,24 @@ impl History for MemHistory {
 
     fn clear(&mut self) -> Result<()> {
         self.entries.clear();
+        self.infos.clear();
+        self.bytes = 0;
+        self.index.clear();
+        Ok(())
+    }
+
+    fn set_context(&mut self, context: Option<String>) -> Result<()> {
+        self.context = context.map(Arc::from);
+        Ok(())
+    }
+
+    fn set_context_filter(&mut self, context: Option<String>) -> Result<()> {
+        self.context_filter = context;
+        Ok(())
+    }
+
+    fn set_filter(&mut self, filter: Option<Box<dyn HistoryFilter>>) -> Result<()> {
+        self.filter = filter;
         Ok(())
     }
 
@@ -362,26 +718,25 @@ impl History for MemHistory {
         start: usize,
         dir: SearchDirection,
     ) -> Result<Option<SearchResult<'_>>> {
-        cfg_select! {
-            feature = "case_insensitive_history_search" => {
-                use regex::{escape, RegexBuilder};
-                Ok(
-                    if let Ok(re) = RegexBuilder::new(&escape(term))
-                        .case_insensitive(true)
-                        .build()
-                    {
-                        let test = |entry: &str| re.find(entry).map(|m| m.start());
-                        self.search_match(term, start, dir, test)
-                    } else {
-                        None
-                    },
-                )
-            }
-            _ => {
-                let test = |entry: &str| entry.find(term);
-                Ok(self.search_match(term, start, dir, test))
-            }
-        }
+        let mode = if cfg!(feature = "case_insensitive_history_search") {
+            SearchMode::CaseInsensitive
+        } else {
+            SearchMode::Substring
+        };
+        self.search_with_mode(term, start, dir, mode)
+    }
+
+    fn search_with_mode(
+        &self,
+        term: &str,
+        start: usize,
+        dir: SearchDirection,
+        mode: SearchMode,
+    ) -> Result<Option<SearchResult<'_>>> {
+        let Some(test) = matcher(term, mode)? else {
+            return Ok(None);
+        };
+        Ok(self.search_match(term, start, dir, test))
     }
 
     fn starts_with(
@@ -392,22 +747,8 @@ impl History for MemHistory {
     ) -> Result<Option<SearchResult<'_>>> {
         cfg_select! {
             feature = "case_insensitive_history_search" => {
-                use regex::{escape, RegexBuilder};
-                Ok(
-                    if let Ok(re) = RegexBuilder::new(&escape(term))
-                        .case_insensitive(true)
-                        .build()
-                    {
-                        let test = |entry: &str| {
-                            re.find(entry)
-                                .filter(|m| m.start() == 0)
-                                .map(|m| m.end())
-                        };
-                        self.search_match(term, start, dir, test)
-                    } else {
-                        None
-                    },
-                )
+                let test = |entry: &str| starts_with_ignore_case(entry, term);
+                Ok(self.search_match(term, start, dir, test))
             }
             _ => {
                 let test = |entry: &str| {
@@ -421,6 +762,56 @@ impl History for MemHistory {
             }
         }
     }
+
+    fn ranked(
+        &self,
+        term: &str,
+        mode: SearchMode,
+        ranking: HistoryRanking,
+        limit: usize,
+    ) -> Result<Vec<SearchResult<'_>>> {
+        if term.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+        let Some(test) = matcher(term, mode)? else {
+            return Ok(Vec::new());
+        };
+        let now = SystemTime::now();
+        // distinct entries: index of the most recent use, match position and score
+        let mut scores: HashMap<&str, Option<(usize, usize, u64)>> = HashMap::new();
+        for (idx, entry) in self.entries.iter().enumerate().rev() {
+            if !self.in_context(idx) {
+                continue;
+            }
+            let score = match ranking {
+                HistoryRanking::Recency => 0,
+                HistoryRanking::Frequency => 1,
+                HistoryRanking::Frecency => frecency(self.infos[idx].time, now),
+            };
+            scores
+                .entry(entry)
+                .and_modify(|m| {
+                    if let Some((_, _, total)) = m {
+                        *total += score;
+                    }
+                })
+                .or_insert_with(|| test(entry).map(|pos| (idx, pos, score)));
+        }
+        let mut matches: Vec<_> = scores
+            .into_iter()
+            .filter_map(|(entry, m)| m.map(|(idx, pos, score)| (entry, idx, pos, score)))
+            .collect();
+        matches.sort_unstable_by(|a, b| b.3.cmp(&a.3).then(b.1.cmp(&a.1)));
+        Ok(matches
+            .into_iter()
+            .take(limit)
+            .map(|(entry, idx, pos, _)| SearchResult {
+                entry: Cow::Borrowed(entry),
+                idx,
+                pos,
+            })
+            .collect())
+    }
 }
 
 impl Index<usize> for MemHistory {
@@ -441,6 +832,12 @@ impl<'a> IntoIterator for &'a MemHistory {
 }
 
 /// Current state of the history stored in a file.
+///
+/// The file can be shared by concurrent processes: accesses are serialized by
+/// an advisory lock on `<path>.lock`, entries appended by other processes
+/// since last load / append are kept by [`History::append`] (but not by
+/// [`History::save`] which replaces them) and the file is atomically replaced
+/// on rewrite.
 #[derive(Default)]
 #[cfg(feature = "with-file-history")]
 pub struct FileHistory {
@@ -449,19 +846,33 @@ pub struct FileHistory {
     new_entries: usize,
     /// last path used by either `load` or `save`
     path_info: Option<PathInfo>,
+    /// `true` if the file at `path_info` is in V3 format
+    timestamped: bool,
+    /// See [`Config::history_timestamps`]
+    timestamps: bool,
 }
 
 // TODO impl Deref<MemHistory> for FileHistory ?
 
-/// Last histo path, modified timestamp and size
+/// Last histo path, modified timestamp, size (`usize::MAX` when the file must
+/// be rewritten on next append), length in bytes and file identity (changed by
+/// a rewrite)
 #[cfg(feature = "with-file-history")]
-struct PathInfo(std::path::PathBuf, SystemTime, usize);
+struct PathInfo(std::path::PathBuf, SystemTime, usize, u64, FileId);
 
 #[cfg(feature = "with-file-history")]
 impl FileHistory {
     // New multiline-aware history files start with `#V2\n` and have newlines
     // and backslashes escaped in them.
     const FILE_VERSION_V2: &'static str = "#V2";
+    // Timestamped history files start with `#V3\n` (only written when
+    // timestamps are enabled or entries have a context). Each entry is
+    // prefixed by the milliseconds since the Unix epoch when it was entered
+    // (empty if unknown) and a tab. Newlines, tabs and backslashes are escaped.
+    // Optional `key=value` fields may be inserted between the timestamp and the
+    // entry (each followed by a tab): only `context` (escaped) is currently
+    // supported, others are ignored.
+    const FILE_VERSION_V3: &'static str = "#V3";
 
     /// Default constructor
     #[must_use]
@@ -471,18 +882,33 @@ impl FileHistory {
 
     /// Customized constructor with:
     /// - [`Config::max_history_size()`],
+    /// - [`Config::max_history_entry_len()`],
+    /// - [`Config::max_history_bytes()`],
     /// - [`Config::history_ignore_space()`],
-    /// - [`Config::history_duplicates()`].
+    /// - [`Config::history_duplicates()`],
+    /// - [`Config::history_timestamps()`].
     #[must_use]
     pub fn with_config(config: &Config) -> Self {
         Self {
             mem: MemHistory::with_config(config),
             new_entries: 0,
             path_info: None,
+            timestamped: false,
+            timestamps: config.history_timestamps(),
         }
     }
 
-    fn save_to(&mut self, file: &File, append: bool) -> Result<()> {
+    /// Tell if entries from `first` need the V3 format: timestamps are only
+    /// saved if enabled or if the file is already timestamped.
+    fn needs_v3(&self, first: usize) -> bool {
+        let timestamps = self.timestamps || self.timestamped;
+        self.mem.infos.iter().skip(first).any(|info| {
+            info.persistent && ((timestamps && info.time.is_some()) || info.context.is_some())
+        })
+    }
+
+    /// Return the number of entries written
+    fn save_to(&mut self, file: &File, append: bool) -> Result<usize> {
         use std::io::{BufWriter, Write as _};
 
         fix_perm(file);
@@ -490,33 +916,44 @@ impl FileHistory {
         let first_new_entry = if append {
             self.mem.len().saturating_sub(self.new_entries)
         } else {
-            wtr.write_all(Self::FILE_VERSION_V2.as_bytes())?;
+            self.timestamped = self.needs_v3(0);
+            let version = if self.timestamped {
+                Self::FILE_VERSION_V3
+            } else {
+                Self::FILE_VERSION_V2
+            };
+            wtr.write_all(version.as_bytes())?;
             wtr.write_all(b"\n")?;
             0
         };
-        for entry in self.mem.entries.iter().skip(first_new_entry) {
-            let mut bytes = entry.as_bytes();
-            while let Some(i) = memchr::memchr2(b'\\', b'\n', bytes) {
-                let (head, tail) = bytes.split_at(i);
-                wtr.write_all(head)?;
-
-                let (&escapable_byte, tail) = tail
-                    .split_first()
-                    .expect("memchr guarantees i is a valid index");
-                if escapable_byte == b'\n' {
-                    wtr.write_all(br"\n")?; // escaped line feed
-                } else {
-                    debug_assert_eq!(escapable_byte, b'\\');
-                    wtr.write_all(br"\\")?; // escaped backslash
+        let v3 = self.timestamped;
+        let mut count = 0;
+        for (entry, info) in self
+            .mem
+            .entries
+            .iter()
+            .zip(&self.mem.infos)
+            .skip(first_new_entry)
+            .filter(|(_, info)| info.persistent)
+        {
+            count += 1;
+            if v3 {
+                if let Some(millis) = info.time.and_then(to_millis) {
+                    write!(wtr, "{millis}")?;
+                }
+                wtr.write_all(b"\t")?;
+                if let Some(ref context) = info.context {
+                    wtr.write_all(b"context=")?;
+                    write_escaped(&mut wtr, context, true)?;
+                    wtr.write_all(b"\t")?;
                 }
-                bytes = tail;
             }
-            wtr.write_all(bytes)?; // remaining bytes with no \n or \
+            write_escaped(&mut wtr, entry, v3)?;
             wtr.write_all(b"\n")?;
         }
         // https://github.com/rust-lang/rust/issues/32677#issuecomment-204833485
         wtr.flush()?;
-        Ok(())
+        Ok(count)
     }
 
     fn load_from(&mut self, file: &File) -> Result<bool> {
@@ -524,69 +961,78 @@ impl FileHistory {
 
         let rdr = BufReader::new(file);
         let mut lines = rdr.lines();
-        let mut v2 = false;
+        let mut version = 1;
         if let Some(first) = lines.next() {
             let line = first?;
             if line == Self::FILE_VERSION_V2 {
-                v2 = true;
+                version = 2;
+            } else if line == Self::FILE_VERSION_V3 {
+                version = 3;
             } else {
-                self.add_owned(line)?;
+                self.mem.add_entry(line, EntryInfo::new(None));
             }
         }
-        let mut appendable = v2;
+        self.timestamped = version == 3;
+        let appendable = self.load_lines(lines, version)?;
+        self.new_entries = 0; // TODO we may lost new entries if loaded lines < max_len
+        // V1 files are rewritten on next save
+        Ok(appendable && version > 1)
+    }
+
+    /// Load entries (without file header) in `version` format.
+    ///
+    /// Return false if some entries have been ignored.
+    fn load_lines<I>(&mut self, lines: I, version: u8) -> Result<bool>
+    where
+        I: Iterator<Item = std::io::Result<String>>,
+    {
+        let mut appendable = true;
+        let mut last_context: Option<Arc<str>> = None; // shared by consecutive entries
         for line in lines {
-            let mut line = line?;
+            let line = line?;
             if line.is_empty() {
                 continue;
             }
-            if v2 {
-                let mut copy = None; // lazily copy line if unescaping is needed
-                let mut str = line.as_str();
-                while let Some(i) = str.find('\\') {
-                    if copy.is_none() {
-                        copy = Some(String::with_capacity(line.len()));
-                    }
-                    let s = copy.as_mut().unwrap();
-                    s.push_str(&str[..i]);
-                    let j = i + 1; // escaped char idx
-                    let b = if j < str.len() {
-                        str.as_bytes()[j]
-                    } else {
-                        0 // unexpected if History::save works properly
-                    };
-                    match b {
-                        b'n' => {
-                            s.push('\n'); // unescaped line feed
-                        }
-                        b'\\' => {
-                            s.push('\\'); // unescaped back slash
-                        }
-                        _ => {
-                            // only line feed and back slash should have been escaped
-                            warn!(target: "rustyline", "bad escaped line: {line}");
-                            copy = None;
-                            break;
+            let mut info = EntryInfo::new(None);
+            let line = match version {
+                1 => line,
+                2 => unescape(line, false),
+                _ => {
+                    if let Some((fields, entry)) = line.rsplit_once('\t') {
+                        let mut fields = fields.split('\t');
+                        info.time = fields.next().and_then(from_millis);
+                        for field in fields {
+                            if let Some(context) = field.strip_prefix("context=") {
+                                let context = unescape(context.to_owned(), true);
+                                if last_context.as_deref() != Some(&context) {
+                                    last_context = Some(Arc::from(context));
+                                }
+                                info.context.clone_from(&last_context);
+                            }
                         }
+                        unescape(entry.to_owned(), true)
+                    } else {
+                        warn!(target: "rustyline", "bad timestamped line: {line}");
+                        line
                     }
-                    str = &str[j + 1..];
-                }
-                if let Some(mut s) = copy {
-                    s.push_str(str); // remaining bytes with no escaped char
-                    line = s;
                 }
-            }
-            appendable &= self.add_owned(line)?; // TODO truncate to MAX_LINE
+            };
+            appendable &= self.mem.add_entry(line, info);
         }
-        self.new_entries = 0; // TODO we may lost new entries if loaded lines < max_len
         Ok(appendable)
     }
 
     fn update_path(&mut self, path: &Path, file: &File, size: usize) -> Result<()> {
-        let modified = file.metadata()?.modified()?;
+        let metadata = file.metadata()?;
+        let modified = metadata.modified()?;
+        let len = metadata.len();
+        let id = file_id(file)?;
         if let Some(PathInfo(
             ref mut previous_path,
             ref mut previous_modified,
             ref mut previous_size,
+            ref mut previous_len,
+            ref mut previous_id,
         )) = self.path_info
         {
             if previous_path.as_path() != path {
@@ -594,15 +1040,17 @@ impl FileHistory {
             }
             *previous_modified = modified;
             *previous_size = size;
+            *previous_len = len;
+            *previous_id = id;
         } else {
-            self.path_info = Some(PathInfo(path.to_owned(), modified, size));
+            self.path_info = Some(PathInfo(path.to_owned(), modified, size, len, id));
         }
-        debug!(target: "rustyline", "PathInfo({path:?}, {modified:?}, {size})");
+        debug!(target: "rustyline", "PathInfo({path:?}, {modified:?}, {size}, {len})");
         Ok(())
     }
 
     fn can_just_append(&self, path: &Path, file: &File) -> Result<bool> {
-        if let Some(PathInfo(ref previous_path, ref previous_modified, ref previous_size)) =
+        if let Some(PathInfo(ref previous_path, ref previous_modified, ref previous_size, ..)) =
             self.path_info
         {
             if previous_path.as_path() != path {
@@ -617,6 +1065,11 @@ impl FileHistory {
                 debug!(target: "rustyline", "cannot append: {:?} < {:?} or {} < {} + {}",
                        previous_modified, modified, self.mem.max_len, previous_size, self.new_entries);
                 Ok(false)
+            } else if !self.timestamped
+                && self.needs_v3(self.mem.len().saturating_sub(self.new_entries))
+            {
+                debug!(target: "rustyline", "cannot append: V2 file");
+                Ok(false)
             } else {
                 Ok(true)
             }
@@ -625,6 +1078,130 @@ impl FileHistory {
         }
     }
 
+    /// Take an advisory lock on the history file at `path` (created if
+    /// missing and `exclusive`).
+    ///
+    /// Entries must be read or appended through the returned file (locks are
+    /// mandatory on Windows). The lock is released when it is dropped.
+    fn lock(path: &Path, exclusive: bool) -> Result<File> {
+        loop {
+            let file = if exclusive {
+                let old_umask = umask();
+                let f = OpenOptions::new()
+                    .read(true)
+                    .append(true)
+                    .create(true)
+                    .open(path);
+                restore_umask(old_umask);
+                f?
+            } else {
+                File::open(path)?
+            };
+            if exclusive {
+                file.lock()?;
+            } else {
+                file.lock_shared()?;
+            }
+            // the file may have been replaced by `rewrite` while waiting
+            if is_same_file(&file, path)? {
+                return Ok(file);
+            }
+            debug!(target: "rustyline", "history file replaced while waiting for lock: {path:?}");
+        }
+    }
+
+    /// Update `new_entries` after an entry has been added
+    fn count_new_entry(&mut self, first_new_entry: usize) {
+        // an unsaved duplicate may have been erased
+        if self.mem.erased.is_none_or(|idx| idx < first_new_entry) {
+            self.new_entries = self.new_entries.saturating_add(1).min(self.len());
+        }
+    }
+
+    /// Empty history with the same settings
+    fn empty(&self) -> Self {
+        Self {
+            mem: MemHistory {
+                entries: VecDeque::new(),
+                infos: VecDeque::new(),
+                bytes: 0,
+                max_len: self.mem.max_len,
+                max_entry_len: self.mem.max_entry_len,
+                max_bytes: self.mem.max_bytes,
+                ignore_space: self.mem.ignore_space,
+                duplicates: self.mem.duplicates,
+                index: HashMap::new(),
+                next_seq: 0,
+                erased: None,
+                filter: None,
+                context: None,
+                context_filter: None,
+            },
+            new_entries: 0,
+            path_info: None,
+            timestamped: false,
+            timestamps: self.timestamps,
+        }
+    }
+
+    /// Reload the locked `file` at `path` and rewrite it with new entries
+    /// appended.
+    fn merge(&mut self, path: &Path, file: &File) -> Result<()> {
+        let mut other = self.empty();
+        other.load_from(file)?;
+        let first_new_entry = self.mem.len().saturating_sub(self.new_entries);
+        for (entry, info) in self
+            .mem
+            .entries
+            .iter()
+            .zip(&self.mem.infos)
+            .skip(first_new_entry)
+        {
+            other.mem.add_entry(entry.clone(), info.clone());
+        }
+        other.rewrite(path)?;
+        self.path_info = other.path_info;
+        self.timestamped = other.timestamped;
+        self.new_entries = 0;
+        Ok(())
+    }
+
+    /// Atomically replace the file at `path` by all entries: they are written
+    /// to a temporary file which is then renamed.
+    ///
+    /// Lock must be held.
+    fn rewrite(&mut self, path: &Path) -> Result<()> {
+        // follow symlink so that it is not replaced by a regular file
+        let target = std::fs::canonicalize(path)
+            .or_else(|_| {
+                // dangling symlink
+                std::fs::read_link(path).map(|link| match path.parent() {
+                    Some(dir) => dir.join(link),
+                    None => link,
+                })
+            })
+            .unwrap_or_else(|_| path.to_owned());
+        let mut tmp_path = target.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let old_umask = umask();
+        let f = File::create(&tmp_path);
+        restore_umask(old_umask);
+        let file = f?;
+        let size = match self.save_to(&file, false).and_then(|size| {
+            file.sync_all()?;
+            Ok(size)
+        }) {
+            Ok(size) => size,
+            Err(err) => {
+                let _ = std::fs::remove_file(&tmp_path);
+                return Err(err);
+            }
+        };
+        std::fs::rename(&tmp_path, &target)?;
+        self.new_entries = 0;
+        self.update_path(path, &file, size)
+    }
+
     /// Return a forward iterator.
     #[must_use]
     pub fn iter(&self) -> impl DoubleEndedIterator<Item = &String> + '_ {
@@ -650,8 +1227,9 @@ impl History for FileHistory {
     }
 
     fn add(&mut self, line: &str) -> Result<bool> {
+        let first_new_entry = self.len() - self.new_entries;
         if self.mem.add(line)? {
-            self.new_entries = self.new_entries.saturating_add(1).min(self.len());
+            self.count_new_entry(first_new_entry);
             Ok(true)
         } else {
             Ok(false)
@@ -659,14 +1237,41 @@ impl History for FileHistory {
     }
 
     fn add_owned(&mut self, line: String) -> Result<bool> {
+        let first_new_entry = self.len() - self.new_entries;
         if self.mem.add_owned(line)? {
-            self.new_entries = self.new_entries.saturating_add(1).min(self.len());
+            self.count_new_entry(first_new_entry);
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    fn add_with_timestamp(&mut self, line: String, timestamp: SystemTime) -> Result<bool> {
+        let first_new_entry = self.len() - self.new_entries;
+        if self.mem.add_with_timestamp(line, timestamp)? {
+            self.count_new_entry(first_new_entry);
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn timestamp(&self, index: usize) -> Result<Option<SystemTime>> {
+        self.mem.timestamp(index)
+    }
+
+    fn set_context(&mut self, context: Option<String>) -> Result<()> {
+        self.mem.set_context(context)
+    }
+
+    fn set_context_filter(&mut self, context: Option<String>) -> Result<()> {
+        self.mem.set_context_filter(context)
+    }
+
+    fn set_filter(&mut self, filter: Option<Box<dyn HistoryFilter>>) -> Result<()> {
+        self.mem.set_filter(filter)
+    }
+
     fn len(&self) -> usize {
         self.mem.len()
     }
@@ -685,6 +1290,12 @@ impl History for FileHistory {
         self.mem.ignore_dups(yes)
     }
 
+    fn set_duplicates(&mut self, duplicates: HistoryDuplicates) -> Result<()> {
+        self.mem.set_duplicates(duplicates)?;
+        self.new_entries = self.new_entries.min(self.len());
+        Ok(())
+    }
+
     fn ignore_space(&mut self, yes: bool) {
         self.mem.ignore_space(yes);
     }
@@ -693,74 +1304,47 @@ impl History for FileHistory {
         if self.is_empty() || self.new_entries == 0 {
             return Ok(());
         }
-        let old_umask = umask();
-        let f = File::create(path);
-        restore_umask(old_umask);
-        let file = f?;
-        file.lock()?;
-        self.save_to(&file, false)?;
-        self.new_entries = 0;
-        self.update_path(path, &file, self.len())
+        let lock = Self::lock(path, true)?;
+        if self
+            .path_info
+            .as_ref()
+            .is_some_and(|PathInfo(previous_path, ..)| previous_path.as_path() == path)
+        {
+            // keep entries appended by other processes since last load / save
+            self.merge(path, &lock)
+        } else {
+            self.rewrite(path)
+        }
     }
 
     fn append(&mut self, path: &Path) -> Result<()> {
-        use std::io::Seek as _;
-
         if self.is_empty() || self.new_entries == 0 {
             return Ok(());
         }
-        if !path.exists() || self.new_entries == self.mem.max_len {
-            return self.save(path);
+        let file = Self::lock(path, true)?;
+        if file.metadata()?.len() == 0 || self.new_entries == self.mem.max_len {
+            return self.rewrite(path);
         }
-        let mut file = OpenOptions::new().write(true).read(true).open(path)?;
-        file.lock()?;
         if self.can_just_append(path, &file)? {
-            file.seek(SeekFrom::End(0))?;
-            self.save_to(&file, true)?;
-            let size = self
-                .path_info
-                .as_ref()
-                .unwrap()
-                .2
-                .saturating_add(self.new_entries);
+            let count = self.save_to(&file, true)?;
+            let size = self.path_info.as_ref().unwrap().2.saturating_add(count);
             self.new_entries = 0;
             return self.update_path(path, &file, size);
         }
         // we may need to truncate file before appending new entries
-        let mut other = Self {
-            mem: MemHistory {
-                entries: VecDeque::new(),
-                max_len: self.mem.max_len,
-                ignore_space: self.mem.ignore_space,
-                ignore_dups: self.mem.ignore_dups,
-            },
-            new_entries: 0,
-            path_info: None,
-        };
-        other.load_from(&file)?;
-        let first_new_entry = self.mem.len().saturating_sub(self.new_entries);
-        for entry in self.mem.entries.iter().skip(first_new_entry) {
-            other.add(entry)?;
-        }
-        file.seek(SeekFrom::Start(0))?;
-        file.set_len(0)?; // if new size < old size
-        other.save_to(&file, false)?;
-        self.update_path(path, &file, other.len())?;
-        self.new_entries = 0;
-        Ok(())
+        self.merge(path, &file)
     }
 
     fn load(&mut self, path: &Path) -> Result<()> {
-        let file = File::open(path)?;
-        file.lock_shared()?;
+        let file = Self::lock(path, false)?;
         let len = self.len();
-        if self.load_from(&file)? {
-            self.update_path(path, &file, self.len() - len)
+        let size = if self.load_from(&file)? {
+            self.len() - len
         } else {
-            // discard old version on next save
-            self.path_info = None;
-            Ok(())
-        }
+            // discard old version on next append
+            usize::MAX
+        };
+        self.update_path(path, &file, size)
     }
 
     fn clear(&mut self) -> Result<()> {
@@ -769,6 +1353,141 @@ impl History for FileHistory {
         Ok(())
     }
 
+    fn truncate_file(&mut self, path: &Path, len: usize) -> Result<()> {
+        // do not create a missing history file
+        std::fs::metadata(path)?;
+        let file = Self::lock(path, true)?;
+        let mut other = self.empty();
+        other.mem.max_len = len;
+        other.load_from(&file)?;
+        other.rewrite(path)?;
+        if self
+            .path_info
+            .as_ref()
+            .is_some_and(|PathInfo(previous_path, ..)| previous_path.as_path() == path)
+        {
+            self.path_info = other.path_info;
+            self.timestamped = other.timestamped;
+        }
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<bool> {
+        use std::io::{BufRead as _, BufReader, Seek as _, SeekFrom};
+
+        let Some(PathInfo(ref path, modified, size, len, id)) = self.path_info else {
+            return Ok(false);
+        };
+        let path = path.clone();
+        let mut file = match Self::lock(&path, false) {
+            Err(ReadlineError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                // recreated with all entries on next append
+                warn!(target: "rustyline", "history file removed: {path:?}");
+                self.path_info = None;
+                return Ok(false);
+            }
+            file => file?,
+        };
+        let metadata = file.metadata()?;
+        // mtime granularity may hide an append so length is also checked
+        if metadata.modified()? == modified && metadata.len() == len {
+            return Ok(false);
+        }
+        // the file contains all saved entries so only unsaved ones are re-added
+        // after the entries of the file
+        let first_new_entry = self.mem.len().saturating_sub(self.new_entries);
+        let saved: Vec<_> = self
+            .mem
+            .entries
+            .iter()
+            .zip(&self.mem.infos)
+            .take(first_new_entry)
+            .collect();
+        let mut other = self.empty();
+        let size = if size != usize::MAX && metadata.len() > len && file_id(&file)? == id {
+            // only appended by other sessions: just read new entries
+            file.seek(SeekFrom::Start(len))?;
+            let mut appended = self.empty();
+            let version = if self.timestamped { 3 } else { 2 };
+            let appendable = appended.load_lines(BufReader::new(&file).lines(), version)?;
+            for (entry, info) in saved {
+                other.mem.add_entry(entry.clone(), info.clone());
+            }
+            let count = appended.len();
+            for (entry, info) in appended.mem.entries.into_iter().zip(appended.mem.infos) {
+                other.mem.add_entry(entry, info);
+            }
+            other.timestamped = self.timestamped;
+            if appendable {
+                size.saturating_add(count)
+            } else {
+                usize::MAX
+            }
+        } else {
+            let mut reloaded = self.empty();
+            let appendable = reloaded.load_from(&file)?;
+            other.timestamped = reloaded.timestamped;
+            let size = if appendable {
+                reloaded.len()
+            } else {
+                // older version or ignored entries: rewrite on next append
+                usize::MAX
+            };
+            // session-only entries are kept after the same file entry
+            let mut next = 0; // next saved entry
+            while let Some((entry, info)) = saved.get(next).filter(|(_, info)| !info.persistent) {
+                other.mem.add_entry((*entry).clone(), (*info).clone());
+                next += 1;
+            }
+            for (entry, info) in reloaded.mem.entries.into_iter().zip(reloaded.mem.infos) {
+                if let Some(skip) = saved[next..]
+                    .iter()
+                    .position(|(e, i)| i.persistent && **e == entry)
+                {
+                    // skipped entries have been removed from the file
+                    for (entry, info) in &saved[next..next + skip] {
+                        if !info.persistent {
+                            other.mem.add_entry((*entry).clone(), (*info).clone());
+                        }
+                    }
+                    next += skip + 1;
+                    other.mem.add_entry(entry, info);
+                    while let Some((entry, info)) =
+                        saved.get(next).filter(|(_, info)| !info.persistent)
+                    {
+                        other.mem.add_entry((*entry).clone(), (*info).clone());
+                        next += 1;
+                    }
+                } else {
+                    other.mem.add_entry(entry, info);
+                }
+            }
+            for (entry, info) in &saved[next..] {
+                if !info.persistent {
+                    other.mem.add_entry((*entry).clone(), (*info).clone());
+                }
+            }
+            size
+        };
+        let mut new_entries = 0;
+        for (entry, info) in self
+            .mem
+            .entries
+            .iter()
+            .zip(&self.mem.infos)
+            .skip(first_new_entry)
+        {
+            if other.mem.add_entry(entry.clone(), info.clone()) {
+                new_entries += 1;
+            }
+        }
+        self.timestamped = other.timestamped;
+        self.mem.replace_entries(other.mem);
+        self.new_entries = new_entries;
+        self.update_path(&path, &file, size)?;
+        Ok(true)
+    }
+
     fn search(
         &self,
         term: &str,
@@ -778,6 +1497,26 @@ impl History for FileHistory {
         self.mem.search(term, start, dir)
     }
 
+    fn search_with_mode(
+        &self,
+        term: &str,
+        start: usize,
+        dir: SearchDirection,
+        mode: SearchMode,
+    ) -> Result<Option<SearchResult<'_>>> {
+        self.mem.search_with_mode(term, start, dir, mode)
+    }
+
+    fn ranked(
+        &self,
+        term: &str,
+        mode: SearchMode,
+        ranking: HistoryRanking,
+        limit: usize,
+    ) -> Result<Vec<SearchResult<'_>>> {
+        self.mem.ranked(term, mode, ranking, limit)
+    }
+
     fn starts_with(
         &self,
         term: &str,
@@ -807,6 +1546,220 @@ impl<'a> IntoIterator for &'a FileHistory {
     }
 }
 
+/// Match position of an entry
+pub(crate) type Matcher<'t> = Box<dyn Fn(&str) -> Option<usize> + 't>;
+
+/// Matcher of `term` according to `mode` (`None` for an incomplete regex)
+pub(crate) fn matcher(term: &str, mode: SearchMode) -> Result<Option<Matcher<'_>>> {
+    Ok(Some(match mode {
+        SearchMode::Substring => Box::new(move |entry: &str| entry.find(term)),
+        SearchMode::Prefix => {
+            Box::new(move |entry: &str| entry.starts_with(term).then_some(term.len()))
+        }
+        SearchMode::CaseInsensitive => Box::new(move |entry: &str| find_ignore_case(entry, term)),
+        SearchMode::Regex => cfg_select! {
+            feature = "regex" => {{
+                let Ok(re) = regex::Regex::new(term) else {
+                    return Ok(None); // incomplete pattern
+                };
+                Box::new(move |entry: &str| re.find(entry).map(|m| m.start()))
+            }}
+            _ => return Err(std::io::Error::from(std::io::ErrorKind::Unsupported).into()),
+        },
+        SearchMode::Fts => {
+            let words: Vec<&str> = term.split_whitespace().collect();
+            if words.is_empty() {
+                return Ok(None);
+            }
+            Box::new(move |entry: &str| {
+                let mut pos = None;
+                for word in &words {
+                    pos.get_or_insert(find_ignore_case(entry, word)?);
+                }
+                pos
+            })
+        }
+    }))
+}
+
+/// Frecency score of one use of an entry: from 100 (less than 4 days ago) down
+/// to 10 (more than 90 days ago or unknown)
+pub(crate) fn frecency(time: Option<SystemTime>, now: SystemTime) -> u64 {
+    const DAY: u64 = 24 * 60 * 60;
+    let age = time.map_or(u64::MAX, |time| {
+        now.duration_since(time)
+            .map_or(0, |age| age.as_secs() / DAY)
+    });
+    match age {
+        0..4 => 100,
+        4..14 => 70,
+        14..31 => 50,
+        31..90 => 30,
+        _ => 10,
+    }
+}
+
+/// Like `entry.find(term)` but ignoring case
+pub(crate) fn find_ignore_case(entry: &str, term: &str) -> Option<usize> {
+    entry
+        .char_indices()
+        .map(|(i, _)| i)
+        .find(|&i| starts_with_ignore_case(&entry[i..], term).is_some())
+}
+
+/// Return the length of the prefix of `entry` matching `term` ignoring case
+fn starts_with_ignore_case(entry: &str, term: &str) -> Option<usize> {
+    let mut chars = entry.char_indices();
+    for tc in term.chars() {
+        let (_, ec) = chars.next()?;
+        if ec != tc && !ec.to_lowercase().eq(tc.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(chars.next().map_or(entry.len(), |(i, _)| i))
+}
+
+/// Escape line feeds, tabs and backslashes
+#[cfg(feature = "with-file-history")]
+fn write_escaped<W: std::io::Write>(wtr: &mut W, s: &str, tab: bool) -> Result<()> {
+    let mut bytes = s.as_bytes();
+    let find = |bytes: &[u8]| {
+        if tab {
+            memchr::memchr3(b'\\', b'\n', b'\t', bytes)
+        } else {
+            memchr::memchr2(b'\\', b'\n', bytes)
+        }
+    };
+    while let Some(i) = find(bytes) {
+        let (head, tail) = bytes.split_at(i);
+        wtr.write_all(head)?;
+
+        let (&escapable_byte, tail) = tail
+            .split_first()
+            .expect("memchr guarantees i is a valid index");
+        match escapable_byte {
+            b'\n' => wtr.write_all(br"\n")?, // escaped line feed
+            b'\t' => wtr.write_all(br"\t")?, // escaped tab
+            _ => {
+                debug_assert_eq!(escapable_byte, b'\\');
+                wtr.write_all(br"\\")?; // escaped backslash
+            }
+        }
+        bytes = tail;
+    }
+    wtr.write_all(bytes)?; // remaining bytes with no \n, \t or \
+    Ok(())
+}
+
+/// Unescape line feeds, backslashes (and tabs in V3 files)
+#[cfg(feature = "with-file-history")]
+fn unescape(line: String, tab: bool) -> String {
+    let mut copy = None; // lazily copy line if unescaping is needed
+    let mut str = line.as_str();
+    while let Some(i) = str.find('\\') {
+        if copy.is_none() {
+            copy = Some(String::with_capacity(line.len()));
+        }
+        let s = copy.as_mut().unwrap();
+        s.push_str(&str[..i]);
+        let j = i + 1; // escaped char idx
+        let b = if j < str.len() {
+            str.as_bytes()[j]
+        } else {
+            0 // unexpected if History::save works properly
+        };
+        match b {
+            b'n' => {
+                s.push('\n'); // unescaped line feed
+            }
+            b't' if tab => {
+                s.push('\t'); // unescaped tab
+            }
+            b'\\' => {
+                s.push('\\'); // unescaped back slash
+            }
+            _ => {
+                // only line feed, tab and back slash should have been escaped
+                warn!(target: "rustyline", "bad escaped line: {line}");
+                copy = None;
+                break;
+            }
+        }
+        str = &str[j + 1..];
+    }
+    if let Some(mut s) = copy {
+        s.push_str(str); // remaining bytes with no escaped char
+        s
+    } else {
+        line
+    }
+}
+
+/// Milliseconds since the Unix epoch
+#[cfg(feature = "with-file-history")]
+fn to_millis(time: SystemTime) -> Option<u128> {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_millis())
+}
+
+#[cfg(feature = "with-file-history")]
+fn from_millis(millis: &str) -> Option<SystemTime> {
+    let millis = millis.parse().ok()?;
+    SystemTime::UNIX_EPOCH.checked_add(std::time::Duration::from_millis(millis))
+}
+
+/// Tell if `path` still refers to `file` (not replaced nor removed)
+#[cfg(feature = "with-file-history")]
+fn is_same_file(file: &File, path: &Path) -> Result<bool> {
+    match File::open(path) {
+        Ok(other) => Ok(file_id(file)? == file_id(&other)?),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(feature = "with-file-history")]
+cfg_select! {
+    windows => {
+        type FileId = (u32, u32, u32);
+
+        fn file_id(file: &File) -> std::io::Result<FileId> {
+            use std::os::windows::io::AsRawHandle as _;
+            use windows_sys::Win32::Storage::FileSystem::{
+                BY_HANDLE_FILE_INFORMATION, GetFileInformationByHandle,
+            };
+
+            let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { std::mem::zeroed() };
+            if unsafe { GetFileInformationByHandle(file.as_raw_handle(), &mut info) } == 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok((
+                info.dwVolumeSerialNumber,
+                info.nFileIndexHigh,
+                info.nFileIndexLow,
+            ))
+        }
+    }
+    unix => {
+        type FileId = (u64, u64);
+
+        fn file_id(file: &File) -> std::io::Result<FileId> {
+            use std::os::unix::fs::MetadataExt as _;
+
+            let metadata = file.metadata()?;
+            Ok((metadata.dev(), metadata.ino()))
+        }
+    }
+    _ => {
+        type FileId = ();
+
+        fn file_id(_: &File) -> std::io::Result<FileId> {
+            Ok(())
+        }
+    }
+}
+
 #[cfg(feature = "with-file-history")]
 cfg_select! {
     any(windows, target_arch = "wasm32") => {
@@ -836,7 +1789,7 @@ cfg_select! {
 
 #[cfg(test)]
 mod tests {
-    use super::{DefaultHistory, History as _, SearchDirection, SearchResult};
+    use super::{DefaultHistory, History as _, SearchDirection, SearchMode, SearchResult};
     use crate::Result;
     use crate::config::Config;
 
@@ -875,6 +1828,155 @@ mod tests {
         assert_eq!(Some(&"line3".to_owned()), history.into_iter().last());
     }
 
+    #[test]
+    fn max_bytes() -> Result<()> {
+        let config = Config::builder()
+            .max_history_entry_len(Some(5))
+            .max_history_bytes(Some(10))
+            .build();
+        let mut history = DefaultHistory::with_config(&config);
+        assert!(!history.add("123456")?);
+        assert!(history.add("12345")?);
+        assert!(history.add("67890")?);
+        assert!(history.add("abc")?);
+        assert_eq!(
+            vec!["67890", "abc"],
+            history.into_iter().map(String::as_str).collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn filter() -> Result<()> {
+        use super::FilterResult;
+
+        let mut history = DefaultHistory::new();
+        history.set_filter(Some(Box::new(|line: &str| {
+            if line.starts_with("secret") {
+                FilterResult::Reject
+            } else if let Some(token) = line.strip_prefix("token=") {
+                FilterResult::Rewrite(format!("token={}", "*".repeat(token.len())))
+            } else if line == "ls" {
+                FilterResult::SessionOnly
+            } else {
+                FilterResult::Keep
+            }
+        })))?;
+        assert!(!history.add("secret 1234")?);
+        assert!(history.add("token=1234")?);
+        assert!(history.add("ls")?);
+        assert!(history.add("pwd")?);
+        assert_eq!(
+            vec!["token=****", "ls", "pwd"],
+            history.into_iter().map(String::as_str).collect::<Vec<_>>()
+        );
+        #[cfg(feature = "with-file-history")]
+        {
+            let tf = tempfile::NamedTempFile::new()?;
+            history.save(tf.path())?;
+            let mut history = DefaultHistory::new();
+            history.load(tf.path())?;
+            assert_eq!(
+                vec!["token=****", "pwd"],
+                history.iter().collect::<Vec<_>>()
+            );
+            tf.close()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn erase_dups() -> Result<()> {
+        use crate::config::HistoryDuplicates;
+
+        let mut history = DefaultHistory::new();
+        history.ignore_dups(false)?;
+        for line in ["a", "b", "a", "c", "b"] {
+            history.add(line)?;
+        }
+        history.set_duplicates(HistoryDuplicates::EraseDups)?;
+        let entries = |h: &DefaultHistory| h.into_iter().cloned().collect::<Vec<_>>();
+        assert_eq!(vec!["a", "c", "b"], entries(&history));
+        assert!(history.add("a")?);
+        assert!(history.add("a")?);
+        assert_eq!(vec!["c", "b", "a"], entries(&history));
+        history.set_max_len(2)?;
+        assert!(history.add("c")?);
+        assert_eq!(vec!["a", "c"], entries(&history));
+        assert!(history.add("b")?);
+        assert_eq!(vec!["c", "b"], entries(&history));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "with-file-history")]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn erase_dups_append() -> Result<()> {
+        use crate::config::HistoryDuplicates;
+
+        let tf = tempfile::NamedTempFile::new()?;
+        let config = Config::builder()
+            .history_duplicates(HistoryDuplicates::EraseDups)?
+            .build();
+        let mut history = DefaultHistory::with_config(&config);
+        history.add("a")?;
+        history.add("b")?;
+        history.append(tf.path())?;
+        history.add("c")?;
+        history.add("a")?; // saved entry erased
+        history.add("c")?; // unsaved entry erased
+        assert_eq!(2, history.new_entries);
+        history.append(tf.path())?;
+
+        let mut history = DefaultHistory::with_config(&config);
+        history.load(tf.path())?;
+        assert_eq!(vec!["b", "a", "c"], history.iter().collect::<Vec<_>>());
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn context() -> Result<()> {
+        let mut history = DefaultHistory::new();
+        history.set_context(Some("/tmp".to_owned()))?;
+        history.add("cd tmp")?;
+        history.set_context(Some("/home\tme".to_owned()))?;
+        history.add("cd home")?;
+        history.set_context(None)?;
+        history.add("pwd")?;
+        let check = |h: &DefaultHistory| -> Result<()> {
+            assert_eq!(Some(0), h.get(2, SearchDirection::Reverse)?.map(|r| r.idx));
+            assert!(h.get(1, SearchDirection::Forward)?.is_none());
+            assert!(
+                h.search("cd", 2, SearchDirection::Reverse)?
+                    .is_some_and(|r| r.idx == 0)
+            );
+            assert!(h.starts_with("p", 0, SearchDirection::Forward)?.is_none());
+            Ok(())
+        };
+        history.set_context_filter(Some("/tmp".to_owned()))?;
+        check(&history)?;
+        #[cfg(feature = "with-file-history")]
+        {
+            let tf = tempfile::NamedTempFile::new()?;
+            history.save(tf.path())?;
+            let mut history = DefaultHistory::new();
+            history.load(tf.path())?;
+            assert_eq!(3, history.len());
+            history.set_context_filter(Some("/tmp".to_owned()))?;
+            check(&history)?;
+            history.set_context_filter(Some("/home\tme".to_owned()))?;
+            assert_eq!(
+                Some(1),
+                history.get(2, SearchDirection::Reverse)?.map(|r| r.idx)
+            );
+            tf.close()?;
+        }
+        Ok(())
+    }
+
     #[test]
     fn clear() -> Result<()> {
         let mut history = init();
@@ -962,6 +2064,100 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg(feature = "with-file-history")]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn load_v2() -> Result<()> {
+        use std::io::Write as _;
+        let tf = tempfile::NamedTempFile::new()?;
+        std::fs::File::create(tf.path())?.write_all(b"#V2\nline\\nfour \\\\ a\tb\n")?;
+        let mut history = DefaultHistory::new();
+        history.load(tf.path())?;
+        assert_eq!(history[0], "line\nfour \\ a\tb");
+        assert_eq!(None, history.timestamp(0)?);
+        // V2 is kept without timestamps
+        history.add("line5")?;
+        history.append(tf.path())?;
+        assert_eq!(
+            "#V2\nline\\nfour \\\\ a\tb\nline5\n",
+            std::fs::read_to_string(tf.path())?
+        );
+        // V2 file is rewritten with timestamps
+        let config = Config::builder().history_timestamps(true).build();
+        let mut history = DefaultHistory::with_config(&config);
+        history.load(tf.path())?;
+        history.add("line6")?;
+        history.append(tf.path())?;
+        let content = std::fs::read_to_string(tf.path())?;
+        assert!(
+            content.starts_with("#V3\n\tline\\nfour \\\\ a\\tb\n\tline5\n"),
+            "{content}"
+        );
+        assert!(content.ends_with("\tline6\n"), "{content}");
+        assert!(!content.ends_with("\n\tline6\n"), "{content}");
+
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "with-file-history")]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn timestamps() -> Result<()> {
+        use std::io::Write as _;
+        use std::time::{Duration, SystemTime};
+
+        let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        let tf = tempfile::NamedTempFile::new()?;
+        // timestamps are not saved by default
+        let mut history = DefaultHistory::new();
+        assert!(history.add_with_timestamp("a\tb".to_owned(), time)?);
+        history.save(tf.path())?;
+        assert_eq!("#V2\na\tb\n", std::fs::read_to_string(tf.path())?);
+
+        let config = Config::builder().history_timestamps(true).build();
+        let mut history = DefaultHistory::with_config(&config);
+        assert!(history.add_with_timestamp("a\tb".to_owned(), time)?);
+        history.save(tf.path())?;
+        assert_eq!(
+            "#V3\n1700000000123\ta\\tb\n",
+            std::fs::read_to_string(tf.path())?
+        );
+
+        let mut history2 = DefaultHistory::new();
+        history2.load(tf.path())?;
+        assert_eq!(history2[0], "a\tb");
+        assert_eq!(Some(time), history2.timestamp(0)?);
+        // metadata is ignored
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(tf.path())?
+            .write_all(b"1700000000124\tcwd=/tmp\tls\n")?;
+        let mut history3 = DefaultHistory::with_config(&config);
+        history3.load(tf.path())?;
+        assert_eq!(history3[1], "ls");
+        assert_eq!(
+            Some(time + Duration::from_millis(1)),
+            history3.timestamp(1)?
+        );
+
+        // only new entries are appended
+        history3.add("pwd")?;
+        history3.append(tf.path())?;
+        let content = std::fs::read_to_string(tf.path())?;
+        assert!(
+            content.starts_with("#V3\n1700000000123\ta\\tb\n1700000000124\tcwd=/tmp\tls\n"),
+            "{content}"
+        );
+        let mut history4 = DefaultHistory::new();
+        history4.load(tf.path())?;
+        assert_eq!(3, history4.len());
+        assert!(history4.timestamp(2)?.is_some_and(|t| t > time));
+
+        tf.close()?;
+        Ok(())
+    }
+
     #[test]
     #[cfg(feature = "with-file-history")]
     #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
@@ -987,6 +2183,257 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg(feature = "with-file-history")]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn merge_on_append() -> Result<()> {
+        let tf = tempfile::NamedTempFile::new()?;
+        init().save(tf.path())?;
+
+        let mut session1 = DefaultHistory::new();
+        session1.load(tf.path())?;
+        let mut session2 = DefaultHistory::new();
+        session2.load(tf.path())?;
+        session1.add("line4")?;
+        session2.add("line5")?;
+        session1.append(tf.path())?;
+        session2.append(tf.path())?;
+        session1.add("line6")?;
+        session1.append(tf.path())?;
+
+        let mut history = DefaultHistory::new();
+        history.load(tf.path())?;
+        assert_eq!(
+            vec!["line1", "line2", "line3", "line4", "line5", "line6"],
+            history.iter().collect::<Vec<_>>()
+        );
+
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "with-file-history")]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn merge_on_save() -> Result<()> {
+        let tf = tempfile::NamedTempFile::new()?;
+        init().save(tf.path())?;
+
+        let mut session1 = DefaultHistory::new();
+        session1.load(tf.path())?;
+        let mut session2 = DefaultHistory::new();
+        session2.load(tf.path())?;
+        session2.add("line4")?;
+        session2.append(tf.path())?;
+        session1.add("line5")?;
+        session1.save(tf.path())?;
+        let mut history = DefaultHistory::new();
+        history.load(tf.path())?;
+        assert_eq!(
+            vec!["line1", "line2", "line3", "line4", "line5"],
+            history.iter().collect::<Vec<_>>()
+        );
+
+        // nothing new: file untouched
+        std::fs::write(tf.path(), "#V2\nother\n")?;
+        session1.save(tf.path())?;
+        assert_eq!("#V2\nother\n", std::fs::read_to_string(tf.path())?);
+
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "with-file-history")]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn truncate_file() -> Result<()> {
+        let tf = tempfile::NamedTempFile::new()?;
+        let mut history = init();
+        history.add("a much longer line")?;
+        history.save(tf.path())?;
+        history.truncate_file(tf.path(), 3)?;
+        assert_eq!(4, history.len());
+        history.add("line5")?;
+        history.append(tf.path())?;
+
+        let config = Config::builder().max_history_entry_len(Some(5)).build();
+        let mut history = DefaultHistory::with_config(&config);
+        history.load(tf.path())?;
+        assert_eq!(
+            vec!["line2", "line3", "line5"],
+            history.iter().collect::<Vec<_>>()
+        );
+
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "with-file-history")]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn sync() -> Result<()> {
+        let tf = tempfile::NamedTempFile::new()?;
+        init().save(tf.path())?;
+
+        let mut session1 = DefaultHistory::new();
+        session1.load(tf.path())?;
+        assert!(!session1.sync()?);
+        let mut session2 = DefaultHistory::new();
+        session2.load(tf.path())?;
+        session1.add("line4")?; // not saved yet
+        session2.add("line5")?;
+        session2.append(tf.path())?;
+        assert!(session1.sync()?);
+        assert_eq!(
+            vec!["line1", "line2", "line3", "line5", "line4"],
+            session1.iter().collect::<Vec<_>>()
+        );
+        session1.append(tf.path())?;
+        assert!(session2.sync()?);
+        assert_eq!(
+            session1.iter().collect::<Vec<_>>(),
+            session2.iter().collect::<Vec<_>>()
+        );
+
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "with-file-history")]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn sync_session_only() -> Result<()> {
+        use super::FilterResult;
+
+        let tf = tempfile::NamedTempFile::new()?;
+        init().save(tf.path())?;
+
+        let mut session1 = DefaultHistory::new();
+        session1.set_filter(Some(Box::new(|line: &str| {
+            if line == "ls" {
+                FilterResult::SessionOnly
+            } else {
+                FilterResult::Keep
+            }
+        })))?;
+        session1.load(tf.path())?;
+        session1.add("ls")?;
+        session1.append(tf.path())?;
+        let mut session2 = DefaultHistory::new();
+        session2.load(tf.path())?;
+        // appended
+        session2.add("line4")?;
+        session2.append(tf.path())?;
+        assert!(session1.sync()?);
+        assert_eq!(
+            vec!["line1", "line2", "line3", "ls", "line4"],
+            session1.iter().collect::<Vec<_>>()
+        );
+        // rewritten
+        session2.add("line5")?;
+        session2.save(tf.path())?;
+        assert!(session1.sync()?);
+        assert_eq!(
+            vec!["line1", "line2", "line3", "ls", "line4", "line5"],
+            session1.iter().collect::<Vec<_>>()
+        );
+
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "with-file-history")]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn sync_reload() -> Result<()> {
+        let tf = tempfile::NamedTempFile::new()?;
+        init().save(tf.path())?;
+
+        let mut history = DefaultHistory::new();
+        history.load(tf.path())?;
+        // overwritten by an older version, possibly within the same mtime
+        std::fs::write(tf.path(), "line1\nline6\n")?;
+        assert!(history.sync()?);
+        assert_eq!(vec!["line1", "line6"], history.iter().collect::<Vec<_>>());
+        history.add("line7")?;
+        history.append(tf.path())?;
+        assert_eq!(
+            "#V2\nline1\nline6\nline7\n",
+            std::fs::read_to_string(tf.path())?
+        );
+
+        std::fs::remove_file(tf.path())?;
+        assert!(!history.sync()?);
+        history.add("line8")?;
+        history.append(tf.path())?;
+        let mut other = DefaultHistory::new();
+        other.load(tf.path())?;
+        assert_eq!(
+            history.iter().collect::<Vec<_>>(),
+            other.iter().collect::<Vec<_>>()
+        );
+
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "with-file-history")]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn concurrent_append() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("history");
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || -> Result<()> {
+                    let mut history = DefaultHistory::new();
+                    for j in 0..10 {
+                        history.add(&format!("{i}-{j}"))?;
+                        history.append(&path)?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap()?;
+        }
+        let mut history = DefaultHistory::new();
+        history.load(&path)?;
+        assert_eq!(40, history.len());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "with-file-history")]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn load_missing() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("history.txt");
+        let mut history = DefaultHistory::new();
+        assert!(history.load(&path).is_err());
+        assert!(history.truncate_file(&path, 1).is_err());
+        assert_eq!(0, std::fs::read_dir(dir.path())?.count());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "with-file-history"))]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn save_symlink() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let target = dir.path().join("target");
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&target, &link)?;
+        init().save(&link)?;
+        assert!(link.symlink_metadata()?.file_type().is_symlink());
+        let mut history = DefaultHistory::new();
+        history.load(&target)?;
+        assert_eq!(3, history.len());
+        Ok(())
+    }
+
     #[test]
     #[cfg(feature = "with-file-history")]
     #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
@@ -1081,6 +2528,67 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn search_modes() -> Result<()> {
+        let mut history = DefaultHistory::new();
+        history.add("git commit -m Fix")?;
+        history.add("ls Fixtures")?;
+        let search = |term, mode| -> Result<Option<(usize, usize)>> {
+            Ok(history
+                .search_with_mode(term, 1, SearchDirection::Reverse, mode)?
+                .map(|r| (r.idx, r.pos)))
+        };
+        assert_eq!(None, search("fix", SearchMode::Substring)?);
+        assert_eq!(Some((1, 3)), search("Fix", SearchMode::Substring)?);
+        assert_eq!(Some((0, 3)), search("git", SearchMode::Prefix)?);
+        assert_eq!(Some((1, 3)), search("fIX", SearchMode::CaseInsensitive)?);
+        assert_eq!(Some((0, 4)), search("commit fix", SearchMode::Fts)?);
+        if cfg!(feature = "regex") {
+            assert_eq!(Some((0, 14)), search("Fix$", SearchMode::Regex)?);
+            assert_eq!(None, search("(Fix", SearchMode::Regex)?);
+        } else {
+            assert!(search("Fix$", SearchMode::Regex).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn ranked() -> Result<()> {
+        use std::time::{Duration, SystemTime};
+
+        use crate::config::HistoryRanking;
+
+        let mut history = DefaultHistory::new();
+        history.ignore_dups(false)?;
+        let old = SystemTime::now() - Duration::from_secs(100 * 24 * 60 * 60);
+        for _ in 0..3 {
+            history.add_with_timestamp("make".to_owned(), old)?;
+        }
+        for line in ["make test", "make test", "ls", "make doc"] {
+            history.add(line)?;
+        }
+        let ranked = |ranking| -> Result<Vec<(usize, String)>> {
+            Ok(history
+                .ranked("make", SearchMode::Prefix, ranking, 2)?
+                .into_iter()
+                .map(|sr| (sr.idx, sr.entry.into_owned()))
+                .collect())
+        };
+        assert_eq!(
+            vec![(6, "make doc".to_owned()), (4, "make test".to_owned())],
+            ranked(HistoryRanking::Recency)?
+        );
+        assert_eq!(
+            vec![(2, "make".to_owned()), (4, "make test".to_owned())],
+            ranked(HistoryRanking::Frequency)?
+        );
+        assert_eq!(
+            vec![(4, "make test".to_owned()), (6, "make doc".to_owned())],
+            ranked(HistoryRanking::Frecency)?
+        );
+        Ok(())
+    }
+
     #[test]
     #[cfg(feature = "case_insensitive_history_search")]
     fn anchored_search() -> Result<()> {
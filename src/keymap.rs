@@ -3,8 +3,8 @@ use log::debug;
 
 use super::Result;
 use crate::highlight::CmdKind;
-use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E, Modifiers as M};
-use crate::tty::{self, RawReader, Term, Terminal};
+use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E, Modifiers as M, MouseButton};
+use crate::tty::{self, RawReader};
 use crate::{Config, EditMode};
 #[cfg(feature = "custom-bindings")]
 use crate::{Event, EventContext, EventHandler};
@@ -37,8 +37,13 @@ pub enum Cmd {
     CompleteBackward,
     /// complete-hint
     CompleteHint,
+    /// Cycle through search modes during incremental search (see
+    /// [`SearchMode`](crate::history::SearchMode))
+    CycleSearchMode,
     /// Dedent current line
     Dedent(Movement),
+    /// describe-key: display the command bound to the next key sequence
+    DescribeKey,
     /// downcase-word
     DowncaseWord,
     /// vi-eof-maybe
@@ -103,6 +108,13 @@ pub enum Cmd {
     Yank(RepeatCount, Anchor),
     /// yank-pop
     YankPop,
+    /// Move the cursor to the screen position (like a mouse click)
+    MoveCursorTo {
+        /// Screen row (the highest row is number 0)
+        row: u16,
+        /// Screen column (the leftmost column is number 0)
+        col: u16,
+    },
     /// moves cursor to the line above or switches to prev history entry if
     /// the cursor is already on the first line
     LineUpOrPreviousHistory(RepeatCount),
@@ -135,6 +147,7 @@ impl Cmd {
         match *self {
             Self::Kill(Movement::BackwardChar(_) | Movement::ForwardChar(_)) => true,
             Self::ClearScreen
+            | Self::DescribeKey
             | Self::Kill(_)
             | Self::Replace(..)
             | Self::Noop
@@ -357,6 +370,7 @@ pub struct InputState<'b> {
     num_args: i16,
     last_cmd: Cmd,                        // vi only
     last_char_search: Option<CharSearch>, // vi only
+    custom_cmd: bool,                     // last command from a custom binding
 }
 
 /// Provide indirect mutation to user input.
@@ -412,6 +426,7 @@ impl<'b> InputState<'b> {
             num_args: 0,
             last_cmd: Cmd::Noop,
             last_char_search: None,
+            custom_cmd: false,
         }
     }
 
@@ -426,9 +441,9 @@ impl<'b> InputState<'b> {
     /// Parse user input into one command
     /// `single_esc_abort` is used in emacs mode on unix platform when a single
     /// esc key is expected to abort current action.
-    pub fn next_cmd(
+    pub fn next_cmd<R: RawReader>(
         &mut self,
-        rdr: &mut <Terminal as Term>::Reader,
+        rdr: &mut R,
         wrt: &mut dyn Refresher,
         single_esc_abort: bool,
         ignore_external_print: bool,
@@ -448,11 +463,22 @@ impl<'b> InputState<'b> {
                     tty::Event::ExternalPrint(msg) => {
                         wrt.external_print(msg)?;
                     }
+                    // only explicitly bound, ignored otherwise
+                    #[cfg(feature = "custom-bindings")]
+                    tty::Event::KeyRelease(E(k, m)) => {
+                        let evt = Event::from(E(k, m | M::RELEASE));
+                        if let Some(cmd) = self.custom_binding(wrt, &evt, 1, true) {
+                            return Ok(cmd);
+                        }
+                    }
+                    #[cfg(not(feature = "custom-bindings"))]
+                    tty::Event::KeyRelease(_) => {}
                     #[cfg(target_os = "macos")]
                     _ => {}
                 }
             }
         }
+        self.custom_cmd = false;
         match self.mode {
             EditMode::Emacs => self.emacs(rdr, wrt, key),
             EditMode::Vi if self.input_mode != InputMode::Command => self.vi_insert(rdr, wrt, key),
@@ -460,6 +486,44 @@ impl<'b> InputState<'b> {
         }
     }
 
+    /// Read the next key sequence and describe the command it is bound to
+    /// (without executing it)
+    pub fn describe_key<R: RawReader>(
+        &mut self,
+        rdr: &mut R,
+        wrt: &mut dyn Refresher,
+    ) -> Result<String> {
+        let input_mode = self.input_mode;
+        let last_cmd = self.last_cmd.clone();
+        let last_char_search = self.last_char_search;
+        let mut rdr = KeyRecorder {
+            rdr,
+            keys: Vec::new(),
+        };
+        let cmd = self.next_cmd(&mut rdr, wrt, false, true)?;
+        // restore input state
+        if self.input_mode != input_mode {
+            if input_mode == InputMode::Command {
+                wrt.done_inserting();
+            } else {
+                wrt.doing_insert();
+            }
+            self.input_mode = input_mode;
+        }
+        self.last_cmd = last_cmd;
+        self.last_char_search = last_char_search;
+        let keys: Vec<String> = rdr.keys.iter().map(|k| format!("{k:?}")).collect();
+        Ok(format!(
+            "{} runs {cmd:?} ({})",
+            keys.join(" "),
+            if self.custom_cmd {
+                "custom binding"
+            } else {
+                "built-in"
+            }
+        ))
+    }
+
     fn single_esc_abort(&self, single_esc_abort: bool) -> bool {
         match self.mode {
             EditMode::Emacs => single_esc_abort,
@@ -1062,6 +1126,7 @@ impl<'b> InputState<'b> {
             },
             E(K::Down, M::NONE) => Cmd::LineDownOrNextHistory(1),
             E(K::Up, M::NONE) => Cmd::LineUpOrPreviousHistory(1),
+            E(K::Char('O'), M::CTRL) => Cmd::CycleSearchMode,
             E(K::Char('R'), M::CTRL) => Cmd::ReverseSearchHistory,
             // most terminals override Ctrl+S to suspend execution
             E(K::Char('S'), M::CTRL) => Cmd::ForwardSearchHistory,
@@ -1095,6 +1160,17 @@ impl<'b> InputState<'b> {
                 let paste = rdr.read_pasted_text()?;
                 Cmd::Insert(1, paste)
             }
+            E(K::Mouse(mouse), M::NONE) => match mouse.button {
+                // on release: no pending input when the cursor location is requested
+                MouseButton::Left if !mouse.pressed => Cmd::MoveCursorTo {
+                    row: mouse.row,
+                    col: mouse.col,
+                },
+                MouseButton::WheelUp => Cmd::LineUpOrPreviousHistory(1),
+                MouseButton::WheelDown => Cmd::LineDownOrNextHistory(1),
+                _ => Cmd::Noop,
+            },
+            E(K::Mouse(_), _) => Cmd::Noop,
             _ => self
                 .custom_seq_binding(rdr, wrt, &mut evt, n, positive)?
                 .unwrap_or(Cmd::Unknown),
@@ -1134,11 +1210,52 @@ impl<'b> InputState<'b> {
     }
 }
 
+/// Keep track of the keys read by [`InputState::describe_key`]
+struct KeyRecorder<'r, R: RawReader> {
+    rdr: &'r mut R,
+    keys: Vec<KeyEvent>,
+}
+
+impl<R: RawReader> RawReader for KeyRecorder<'_, R> {
+    type Buffer = R::Buffer;
+
+    fn wait_for_input(&mut self, single_esc_abort: bool) -> Result<tty::Event> {
+        let event = self.rdr.wait_for_input(single_esc_abort)?;
+        if let tty::Event::KeyPress(key) = event {
+            self.keys.push(key);
+        }
+        Ok(event)
+    }
+
+    fn next_key(&mut self, single_esc_abort: bool) -> Result<KeyEvent> {
+        let key = self.rdr.next_key(single_esc_abort)?;
+        self.keys.push(key);
+        Ok(key)
+    }
+
+    #[cfg(unix)]
+    fn next_char(&mut self) -> Result<char> {
+        self.rdr.next_char()
+    }
+
+    fn read_pasted_text(&mut self) -> Result<String> {
+        self.rdr.read_pasted_text()
+    }
+
+    fn find_binding(&self, key: &KeyEvent) -> Option<Cmd> {
+        self.rdr.find_binding(key)
+    }
+
+    fn unbuffer(self) -> Option<Self::Buffer> {
+        None
+    }
+}
+
 impl InputState<'_> {
     /// Application customized binding
     #[allow(unused_variables)]
     fn custom_binding(
-        &self,
+        &mut self,
         wrt: &dyn Refresher,
         evt: &Event,
         n: RepeatCount,
@@ -1147,8 +1264,21 @@ impl InputState<'_> {
         cfg_select! {
             feature = "custom-bindings" => {
                 let bindings = self.custom_bindings;
-                let handler = bindings.get(evt).or_else(|| bindings.get(&Event::Any));
-                if let Some(handler) = handler {
+                // mouse position is ignored
+                let key = evt.clone().normalize();
+                let handler = bindings
+                    .get(&key)
+                    .or_else(|| {
+                        matches!(evt.get(0), Some(E(K::Mouse(_), _)))
+                            .then(|| bindings.get(&Event::Mouse()))
+                            .flatten()
+                    })
+                    .or_else(|| {
+                        (!matches!(evt.get(0), Some(E(_, m)) if m.contains(M::RELEASE)))
+                            .then(|| bindings.get(&Event::Any))
+                            .flatten()
+                    });
+                let cmd = if let Some(handler) = handler {
                     match handler {
                         EventHandler::Simple(cmd) => Some(cmd.clone()),
                         EventHandler::Conditional(handler) => {
@@ -1158,7 +1288,9 @@ impl InputState<'_> {
                     }
                 } else {
                     None
-                }
+                };
+                self.custom_cmd = cmd.is_some();
+                cmd
             }
             _ => None
         }
@@ -1166,7 +1298,7 @@ impl InputState<'_> {
 
     #[allow(unused_variables)]
     fn custom_seq_binding<R: RawReader>(
-        &self,
+        &mut self,
         rdr: &mut R,
         wrt: &dyn Refresher,
         evt: &mut Event,
@@ -1175,7 +1307,8 @@ impl InputState<'_> {
     ) -> Result<Option<Cmd>> {
         cfg_select! {
             feature = "custom-bindings" => {
-                while let Some(subtrie) = self.custom_bindings.get_raw_descendant(evt) {
+                let bindings = self.custom_bindings;
+                while let Some(subtrie) = bindings.get_raw_descendant(evt) {
                     let snd_key = rdr.next_key(true)?;
                     if let Event::KeySeq(key_seq) = evt {
                         key_seq.push(snd_key);
@@ -1192,6 +1325,7 @@ impl InputState<'_> {
                             }
                         };
                         if cmd.is_some() {
+                            self.custom_cmd = true;
                             return Ok(cmd);
                         }
                     }
@@ -1203,6 +1337,373 @@ impl InputState<'_> {
     }
 }
 
+/// Built-in key sequence and command (with default repeat count, on an empty
+/// line without hint)
+#[cfg(feature = "custom-bindings")]
+type Builtin = (&'static [KeyEvent], Cmd);
+
+#[cfg(all(feature = "custom-bindings", not(windows)))]
+const CTRL_V: Cmd = Cmd::QuotedInsert;
+#[cfg(all(feature = "custom-bindings", windows))]
+const CTRL_V: Cmd = Cmd::PasteFromClipboard;
+
+/// Wheel event (at any position)
+#[cfg(feature = "custom-bindings")]
+const fn wheel(button: MouseButton) -> KeyEvent {
+    let mouse = crate::keys::Mouse {
+        button,
+        pressed: true,
+        col: 0,
+        row: 0,
+    };
+    E(K::Mouse(mouse), M::NONE)
+}
+
+/// Bindings handled by [`InputState::emacs`]
+#[cfg(feature = "custom-bindings")]
+static EMACS: &[Builtin] = &[
+    (
+        &[E(K::Char('A'), M::CTRL)],
+        Cmd::Move(Movement::BeginningOfLine),
+    ),
+    (
+        &[E(K::Char('B'), M::CTRL)],
+        Cmd::Move(Movement::BackwardChar(1)),
+    ),
+    (&[E(K::Char('E'), M::CTRL)], Cmd::Move(Movement::EndOfLine)),
+    (
+        &[E(K::Char('F'), M::CTRL)],
+        Cmd::Move(Movement::ForwardChar(1)),
+    ),
+    (&[E(K::Char('G'), M::CTRL)], Cmd::Abort),
+    (&[E(K::Char('G'), M::CTRL_ALT)], Cmd::Abort),
+    (&[E::ESC], Cmd::Abort),
+    (
+        &[E(K::Char('H'), M::CTRL)],
+        Cmd::Kill(Movement::BackwardChar(1)),
+    ),
+    (&[E::BACKSPACE], Cmd::Kill(Movement::BackwardChar(1))),
+    (&[E(K::BackTab, M::NONE)], Cmd::CompleteBackward),
+    (&[E(K::Char('I'), M::CTRL)], Cmd::Complete),
+    (&[E(K::Tab, M::NONE)], Cmd::Complete),
+    (&[E(K::Char('K'), M::CTRL)], Cmd::Kill(Movement::EndOfLine)),
+    (&[E(K::Char('L'), M::CTRL)], Cmd::ClearScreen),
+    (&[E(K::Char('N'), M::CTRL)], Cmd::NextHistory),
+    (&[E(K::Char('P'), M::CTRL)], Cmd::PreviousHistory),
+    (
+        &[E(K::Char('X'), M::CTRL), E(K::Char('G'), M::CTRL)],
+        Cmd::Abort,
+    ),
+    (&[E(K::Char('X'), M::CTRL), E::ESC], Cmd::Abort),
+    (
+        &[E(K::Char('X'), M::CTRL), E(K::Char('U'), M::CTRL)],
+        Cmd::Undo(1),
+    ),
+    (
+        &[E(K::Char('X'), M::CTRL), E::BACKSPACE],
+        Cmd::Kill(Movement::BeginningOfLine),
+    ),
+    (
+        &[E(K::Backspace, M::ALT)],
+        Cmd::Kill(Movement::BackwardWord(1, Word::Emacs)),
+    ),
+    (&[E(K::Char('<'), M::ALT)], Cmd::BeginningOfHistory),
+    (&[E(K::Char('>'), M::ALT)], Cmd::EndOfHistory),
+    (
+        &[E(K::Char('B'), M::ALT)],
+        Cmd::Move(Movement::BackwardWord(1, Word::Emacs)),
+    ),
+    (
+        &[E(K::Char('b'), M::ALT)],
+        Cmd::Move(Movement::BackwardWord(1, Word::Emacs)),
+    ),
+    (
+        &[E(K::Left, M::ALT)],
+        Cmd::Move(Movement::BackwardWord(1, Word::Emacs)),
+    ),
+    (
+        &[E(K::Left, M::CTRL)],
+        Cmd::Move(Movement::BackwardWord(1, Word::Emacs)),
+    ),
+    (&[E(K::Char('C'), M::ALT)], Cmd::CapitalizeWord),
+    (&[E(K::Char('c'), M::ALT)], Cmd::CapitalizeWord),
+    (
+        &[E(K::Char('D'), M::ALT)],
+        Cmd::Kill(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+    ),
+    (
+        &[E(K::Char('d'), M::ALT)],
+        Cmd::Kill(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+    ),
+    (
+        &[E(K::Char('F'), M::ALT)],
+        Cmd::Move(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+    ),
+    (
+        &[E(K::Char('f'), M::ALT)],
+        Cmd::Move(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+    ),
+    (
+        &[E(K::Right, M::ALT)],
+        Cmd::Move(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+    ),
+    (
+        &[E(K::Right, M::CTRL)],
+        Cmd::Move(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+    ),
+    (&[E(K::Char('L'), M::ALT)], Cmd::DowncaseWord),
+    (&[E(K::Char('l'), M::ALT)], Cmd::DowncaseWord),
+    (&[E(K::Char('T'), M::ALT)], Cmd::TransposeWords(1)),
+    (&[E(K::Char('t'), M::ALT)], Cmd::TransposeWords(1)),
+    (&[E(K::Char('U'), M::ALT)], Cmd::UpcaseWord),
+    (&[E(K::Char('u'), M::ALT)], Cmd::UpcaseWord),
+    (&[E(K::Char('Y'), M::ALT)], Cmd::YankPop),
+    (&[E(K::Char('y'), M::ALT)], Cmd::YankPop),
+];
+
+/// Bindings handled by [`InputState::vi_command`]
+#[cfg(feature = "custom-bindings")]
+static VI_COMMAND: &[Builtin] = &[
+    (&[E(K::Char('$'), M::NONE)], Cmd::Move(Movement::EndOfLine)),
+    (
+        &[E(K::Char('0'), M::NONE)],
+        Cmd::Move(Movement::BeginningOfLine),
+    ),
+    (
+        &[E(K::Char('^'), M::NONE)],
+        Cmd::Move(Movement::ViFirstPrint),
+    ),
+    (
+        &[E(K::Char('a'), M::NONE)],
+        Cmd::Move(Movement::ForwardChar(1)),
+    ),
+    (&[E(K::Char('A'), M::NONE)], Cmd::Move(Movement::EndOfLine)),
+    (
+        &[E(K::Char('b'), M::NONE)],
+        Cmd::Move(Movement::BackwardWord(1, Word::Vi)),
+    ),
+    (
+        &[E(K::Char('B'), M::NONE)],
+        Cmd::Move(Movement::BackwardWord(1, Word::Big)),
+    ),
+    (
+        &[E(K::Char('c'), M::NONE), E(K::Char('c'), M::NONE)],
+        Cmd::Replace(Movement::WholeLine, None),
+    ),
+    (
+        &[E(K::Char('C'), M::NONE)],
+        Cmd::Replace(Movement::EndOfLine, None),
+    ),
+    (
+        &[E(K::Char('d'), M::NONE), E(K::Char('d'), M::NONE)],
+        Cmd::Kill(Movement::WholeLine),
+    ),
+    (&[E(K::Char('D'), M::NONE)], Cmd::Kill(Movement::EndOfLine)),
+    (&[E(K::Char('K'), M::CTRL)], Cmd::Kill(Movement::EndOfLine)),
+    (
+        &[E(K::Char('e'), M::NONE)],
+        Cmd::Move(Movement::ForwardWord(1, At::BeforeEnd, Word::Vi)),
+    ),
+    (
+        &[E(K::Char('E'), M::NONE)],
+        Cmd::Move(Movement::ForwardWord(1, At::BeforeEnd, Word::Big)),
+    ),
+    (&[E(K::Char('i'), M::NONE)], Cmd::Noop),
+    (
+        &[E(K::Char('I'), M::NONE)],
+        Cmd::Move(Movement::BeginningOfLine),
+    ),
+    (&[E(K::Char('p'), M::NONE)], Cmd::Yank(1, Anchor::After)),
+    (&[E(K::Char('P'), M::NONE)], Cmd::Yank(1, Anchor::Before)),
+    (
+        &[E(K::Char('R'), M::NONE)],
+        Cmd::Replace(Movement::ForwardChar(0), None),
+    ),
+    (
+        &[E(K::Char('s'), M::NONE)],
+        Cmd::Replace(Movement::ForwardChar(1), None),
+    ),
+    (
+        &[E(K::Char('S'), M::NONE)],
+        Cmd::Replace(Movement::WholeLine, None),
+    ),
+    (&[E(K::Char('u'), M::NONE)], Cmd::Undo(1)),
+    (
+        &[E(K::Char('w'), M::NONE)],
+        Cmd::Move(Movement::ForwardWord(1, At::Start, Word::Vi)),
+    ),
+    (
+        &[E(K::Char('W'), M::NONE)],
+        Cmd::Move(Movement::ForwardWord(1, At::Start, Word::Big)),
+    ),
+    (
+        &[E(K::Char('x'), M::NONE)],
+        Cmd::Kill(Movement::ForwardChar(1)),
+    ),
+    (
+        &[E(K::Char('X'), M::NONE)],
+        Cmd::Kill(Movement::BackwardChar(1)),
+    ),
+    (
+        &[E(K::Char('y'), M::NONE), E(K::Char('y'), M::NONE)],
+        Cmd::ViYankTo(Movement::WholeLine),
+    ),
+    (
+        &[E(K::Char('h'), M::NONE)],
+        Cmd::Move(Movement::BackwardChar(1)),
+    ),
+    (
+        &[E(K::Char('H'), M::CTRL)],
+        Cmd::Move(Movement::BackwardChar(1)),
+    ),
+    (&[E::BACKSPACE], Cmd::Move(Movement::BackwardChar(1))),
+    (&[E(K::Char('G'), M::CTRL)], Cmd::Abort),
+    (
+        &[E(K::Char('l'), M::NONE)],
+        Cmd::Move(Movement::ForwardChar(1)),
+    ),
+    (
+        &[E(K::Char(' '), M::NONE)],
+        Cmd::Move(Movement::ForwardChar(1)),
+    ),
+    (&[E(K::Char('L'), M::CTRL)], Cmd::ClearScreen),
+    (&[E(K::Char('+'), M::NONE)], Cmd::LineDownOrNextHistory(1)),
+    (&[E(K::Char('j'), M::NONE)], Cmd::LineDownOrNextHistory(1)),
+    (&[E(K::Char('N'), M::CTRL)], Cmd::NextHistory),
+    (&[E(K::Char('-'), M::NONE)], Cmd::LineUpOrPreviousHistory(1)),
+    (&[E(K::Char('k'), M::NONE)], Cmd::LineUpOrPreviousHistory(1)),
+    (&[E(K::Char('P'), M::CTRL)], Cmd::PreviousHistory),
+    (
+        &[E(K::Char('<'), M::NONE), E(K::Char('<'), M::NONE)],
+        Cmd::Dedent(Movement::WholeLine),
+    ),
+    (
+        &[E(K::Char('>'), M::NONE), E(K::Char('>'), M::NONE)],
+        Cmd::Indent(Movement::WholeLine),
+    ),
+    (&[E::ESC], Cmd::Noop),
+];
+
+/// Bindings handled by [`InputState::vi_insert`]
+#[cfg(feature = "custom-bindings")]
+static VI_INSERT: &[Builtin] = &[
+    (
+        &[E(K::Char('H'), M::CTRL)],
+        Cmd::Kill(Movement::BackwardChar(1)),
+    ),
+    (&[E::BACKSPACE], Cmd::Kill(Movement::BackwardChar(1))),
+    (&[E(K::BackTab, M::NONE)], Cmd::CompleteBackward),
+    (&[E(K::Char('I'), M::CTRL)], Cmd::Complete),
+    (&[E(K::Tab, M::NONE)], Cmd::Complete),
+    (&[E::ESC], Cmd::Move(Movement::BackwardChar(1))),
+];
+
+/// Bindings handled by [`InputState::common`]
+#[cfg(feature = "custom-bindings")]
+static COMMON: &[Builtin] = &[
+    (&[E(K::Home, M::NONE)], Cmd::Move(Movement::BeginningOfLine)),
+    (&[E(K::Left, M::NONE)], Cmd::Move(Movement::BackwardChar(1))),
+    (&[E(K::Char('D'), M::CTRL)], Cmd::EndOfFile),
+    (
+        &[E(K::Delete, M::NONE)],
+        Cmd::Kill(Movement::ForwardChar(1)),
+    ),
+    (&[E(K::End, M::NONE)], Cmd::Move(Movement::EndOfLine)),
+    (&[E(K::Right, M::NONE)], Cmd::Move(Movement::ForwardChar(1))),
+    (
+        &[E(K::Char('J'), M::CTRL)],
+        Cmd::AcceptOrInsertLine {
+            accept_in_the_middle: true,
+        },
+    ),
+    (
+        &[E(K::Char('M'), M::CTRL)],
+        Cmd::AcceptOrInsertLine {
+            accept_in_the_middle: true,
+        },
+    ),
+    (
+        &[E::ENTER],
+        Cmd::AcceptOrInsertLine {
+            accept_in_the_middle: true,
+        },
+    ),
+    (&[E(K::Down, M::NONE)], Cmd::LineDownOrNextHistory(1)),
+    (&[E(K::Up, M::NONE)], Cmd::LineUpOrPreviousHistory(1)),
+    (
+        &[wheel(MouseButton::WheelDown)],
+        Cmd::LineDownOrNextHistory(1),
+    ),
+    (
+        &[wheel(MouseButton::WheelUp)],
+        Cmd::LineUpOrPreviousHistory(1),
+    ),
+    (&[E(K::Char('O'), M::CTRL)], Cmd::CycleSearchMode),
+    (&[E(K::Char('R'), M::CTRL)], Cmd::ReverseSearchHistory),
+    (&[E(K::Char('S'), M::CTRL)], Cmd::ForwardSearchHistory),
+    (&[E(K::Char('T'), M::CTRL)], Cmd::TransposeChars),
+    (
+        &[E(K::Char('U'), M::CTRL)],
+        Cmd::Kill(Movement::BeginningOfLine),
+    ),
+    (&[E(K::Char('Q'), M::CTRL)], Cmd::QuotedInsert),
+    (&[E(K::Char('V'), M::CTRL)], CTRL_V),
+    (
+        &[E(K::Char('W'), M::CTRL)],
+        Cmd::Kill(Movement::BackwardWord(1, Word::Big)),
+    ),
+    (&[E(K::Char('Y'), M::CTRL)], Cmd::Yank(1, Anchor::Before)),
+    (&[E(K::Char('_'), M::CTRL)], Cmd::Undo(1)),
+];
+
+/// Built-in bindings of `mode` / `input_mode`.
+///
+/// Commands which need a character argument (like vi `f`) or which depend on
+/// the input state (like vi `.`) are not listed, commands which need a
+/// motion are only listed for the whole line (like vi `dd`).
+#[cfg(feature = "custom-bindings")]
+pub(crate) fn builtin_bindings(
+    mode: EditMode,
+    input_mode: InputMode,
+) -> impl Iterator<Item = &'static Builtin> {
+    let bindings = match mode {
+        EditMode::Emacs => EMACS,
+        EditMode::Vi if input_mode == InputMode::Command => VI_COMMAND,
+        EditMode::Vi => VI_INSERT,
+    };
+    bindings.iter().chain(COMMON.iter().filter(|(keys, _)| {
+        // shadowed by mode specific bindings
+        !bindings.iter().any(|(k, _)| k[0] == keys[0])
+    }))
+}
+
+/// Tell if the first key of a custom sequence is consumed by the built-in
+/// keymap (so the sequence cannot be reached)
+#[cfg(feature = "custom-bindings")]
+pub(crate) fn is_builtin_prefix(mode: EditMode, input_mode: InputMode, key: KeyEvent) -> bool {
+    if mode == EditMode::Emacs && key == E(K::Char('X'), M::CTRL) {
+        return false; // custom sequences are looked up first
+    }
+    match key {
+        // self-insert / digit argument
+        E(K::Char(_), M::NONE) if input_mode != InputMode::Command => return true,
+        E(K::Char('0'..='9' | '-'), M::ALT) if mode == EditMode::Emacs => return true,
+        E(K::Char(']'), M::CTRL | M::CTRL_ALT) if mode == EditMode::Emacs => return true,
+        E(K::Char(_), M::ALT) if mode == EditMode::Vi && input_mode != InputMode::Command => {
+            return true;
+        }
+        // vi character argument / repeat
+        E(K::Char('1'..='9' | 'f' | 'F' | 't' | 'T' | 'r' | '.' | ';' | ','), M::NONE)
+            if input_mode == InputMode::Command =>
+        {
+            return true;
+        }
+        E(K::BracketedPasteStart | K::UnknownEscSeq, M::NONE) => return true,
+        _ => {}
+    }
+    builtin_bindings(mode, input_mode).any(|(keys, _)| keys[0] == key)
+}
+
 cfg_select! {
     feature = "custom-bindings" => {
 pub type Bindings = radix_trie::Trie<Event, EventHandler>;
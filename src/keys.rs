@@ -95,6 +95,7 @@ impl KeyEvent {
     /// ctrl-a => ctrl-A (uppercase)
     /// shift-A => A (no SHIFT modifier)
     /// shift-Tab => `BackTab`
+    /// mouse event => position ignored (top-left)
     #[must_use]
     pub fn normalize(e: Self) -> Self {
         use self::{KeyCode as K, KeyEvent as E, Modifiers as M};
@@ -108,6 +109,14 @@ impl KeyEvent {
                 E(K::Char(c), m ^ M::SHIFT)
             }
             E(K::Tab, m) if m.contains(M::SHIFT) => E(K::BackTab, m ^ M::SHIFT),
+            E(K::Mouse(mouse), m) => E(
+                K::Mouse(Mouse {
+                    col: 0,
+                    row: 0,
+                    ..mouse
+                }),
+                m,
+            ),
             _ => e,
         }
     }
@@ -153,6 +162,8 @@ pub enum KeyCode {
     Insert,
     /// ← arrow key
     Left,
+    /// Mouse event (when mouse reporting is enabled on unix platform)
+    Mouse(Mouse),
     /// \0
     Null,
     /// ⇟
@@ -167,6 +178,35 @@ pub enum KeyCode {
     Up,
 }
 
+/// Mouse button (or wheel)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MouseButton {
+    /// Left button
+    Left,
+    /// Middle button
+    Middle,
+    /// Right button
+    Right,
+    /// Wheel scrolled up
+    WheelUp,
+    /// Wheel scrolled down
+    WheelDown,
+}
+
+/// Mouse button pressed or released at a screen position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Mouse {
+    /// Button
+    pub button: MouseButton,
+    /// `false` when the button is released
+    pub pressed: bool,
+    /// Screen column (the leftmost column is number 0)
+    pub col: u16,
+    /// Screen row (the highest row is number 0)
+    pub row: u16,
+}
+
 bitflags::bitflags! {
     /// The set of modifier keys that were triggered along with a key press.
     #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -177,6 +217,10 @@ bitflags::bitflags! {
         const ALT  = 1<<2;
         /// Shift modifier
         const SHIFT = 1<<1;
+        /// Key released instead of pressed, only reported when
+        /// [`Config::enable_key_release`](crate::Config::enable_key_release)
+        /// is set and supported by the terminal
+        const RELEASE = 1<<4;
 
         /// No modifier
         const NONE = 0;
@@ -193,7 +237,7 @@ bitflags::bitflags! {
 
 #[cfg(test)]
 mod tests {
-    use super::{KeyCode as K, KeyEvent as E, Modifiers as M};
+    use super::{KeyCode as K, KeyEvent as E, Modifiers as M, Mouse, MouseButton};
 
     #[test]
     fn new() {
@@ -218,5 +262,28 @@ mod tests {
         assert_eq!(E::ctrl('A'), E::normalize(E::ctrl('a')));
         assert_eq!(E::from('A'), E::normalize(E(K::Char('A'), M::SHIFT)));
         assert_eq!(E(K::BackTab, M::NONE), E::normalize(E(K::Tab, M::SHIFT)));
+        assert_eq!(
+            E(K::Char('A'), M::CTRL_SHIFT),
+            E::normalize(E(K::Char('a'), M::CTRL_SHIFT))
+        );
+        // existing ctrl-shift-letter bindings
+        assert_eq!(E::ctrl('A'), E::normalize(E(K::Char('A'), M::CTRL_SHIFT)));
+        let mouse = Mouse {
+            button: MouseButton::Left,
+            pressed: true,
+            col: 0,
+            row: 0,
+        };
+        assert_eq!(
+            E(K::Mouse(mouse), M::CTRL),
+            E::normalize(E(
+                K::Mouse(Mouse {
+                    col: 3,
+                    row: 2,
+                    ..mouse
+                }),
+                M::CTRL
+            ))
+        );
     }
 }
@@ -2,6 +2,8 @@ use std::cmp::Ordering;
 
 /// Tell how grapheme clusters are supported / rendered.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum GraphemeClusterMode {
     /// Support grapheme clustering
     Unicode,
@@ -83,10 +85,13 @@ fn no_zwj(s: &str) -> Unit {
     width
 }
 
+/// Position on the screen, relative to the start of the prompt
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct Position {
-    pub col: Unit, // The leftmost column is number 0.
-    pub row: Unit, // The highest row is number 0.
+    /// Column: the leftmost column is number 0.
+    pub col: Unit,
+    /// Row: the highest row is number 0.
+    pub row: Unit,
 }
 
 impl PartialOrd for Position {
@@ -104,12 +109,48 @@ impl Ord for Position {
     }
 }
 
+/// Rows displayed when prompt + input + info are taller than the terminal
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Viewport {
+    /// First row displayed (relative to the start of the prompt)
+    pub first_row: Unit,
+    /// Last row displayed (inclusive)
+    pub last_row: Unit,
+}
+
+/// Columns displayed when the line is scrolled horizontally
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HScroll {
+    /// First column of line + info displayed (the column of the `<` marker
+    /// if any)
+    pub offset: Unit,
+    /// Number of columns available for line + info (markers included)
+    pub width: Unit,
+    /// Number of columns of line + info
+    pub text_width: Unit,
+}
+
+impl HScroll {
+    /// Some text is hidden on the left
+    pub fn has_left_marker(&self) -> bool {
+        self.offset > 0
+    }
+
+    /// Some text is hidden on the right
+    pub fn has_right_marker(&self) -> bool {
+        self.text_width > self.offset + self.width
+    }
+}
+
+/// Where prompt, input and info are displayed
 #[derive(Debug)]
 #[cfg_attr(test, derive(Default))]
 pub struct Layout {
+    /// How grapheme clusters width is computed
     pub grapheme_cluster_mode: GraphemeClusterMode,
     /// Prompt Unicode/visible width and height
     pub prompt_size: Position,
+    /// Prompt is the default one (not a search or a completion prompt)
     pub default_prompt: bool,
     /// Cursor position (relative to the start of the prompt)
     pub cursor: Position,
@@ -117,9 +158,19 @@ pub struct Layout {
     pub end: Position,
     /// Has some hint or message at the end of input
     pub has_info: bool,
+    /// Only some rows around the cursor are displayed
+    pub viewport: Option<Viewport>,
+    /// Only some columns around the cursor are displayed (single-line mode)
+    pub hscroll: Option<HScroll>,
+    /// Position where the right prompt is displayed (row relative to the
+    /// start of the prompt, `None` when there is no right prompt or when the
+    /// input overlaps it)
+    pub right_prompt: Option<Position>,
 }
 
 impl Layout {
+    /// Empty layout
+    #[must_use]
     pub fn new(grapheme_cluster_mode: GraphemeClusterMode) -> Self {
         Self {
             grapheme_cluster_mode,
@@ -128,18 +179,338 @@ impl Layout {
             cursor: Position::default(),
             end: Position::default(),
             has_info: false,
+            viewport: None,
+            hscroll: None,
+            right_prompt: None,
         }
     }
 
+    /// Scroll (vertically or horizontally) such that the cursor is visible,
+    /// trying to keep the same rows / columns as the `old` layout.
+    pub fn scroll(&mut self, old: &Layout, rows: Unit) {
+        if self.hscroll.is_some() {
+            self.update_hscroll(old.hscroll.map_or(0, |hs| hs.offset));
+        } else {
+            self.update_viewport(old.first_row(), rows);
+        }
+    }
+
+    /// Choose the columns to display such that the cursor is visible, trying
+    /// to keep `offset` (of the previous layout) as the first column.
+    ///
+    /// Before, `cursor` and `end` columns are not scrolled (they may exceed
+    /// the terminal width). After, they are relative to the screen.
+    pub fn update_hscroll(&mut self, offset: Unit) {
+        let Some(ref mut hs) = self.hscroll else {
+            return;
+        };
+        let prompt_width = self.prompt_size.col;
+        let cursor = self.cursor.col - prompt_width;
+        let mut offset = if hs.text_width < hs.width {
+            0
+        } else {
+            // do not leave empty columns at the right
+            offset.min(hs.text_width + 1 - hs.width)
+        };
+        if offset > 0 && cursor <= offset {
+            // cursor hidden on the left (or by the `<` marker)
+            offset = cursor.saturating_sub(1);
+        }
+        let right = if hs.text_width > offset + hs.width {
+            offset + hs.width - 2 // `>` marker
+        } else {
+            offset + hs.width - 1
+        };
+        if cursor > right {
+            // cursor hidden on the right: cursor on the last column
+            offset = cursor + 1 - hs.width;
+            if hs.text_width > offset + hs.width {
+                offset += 1; // `>` marker
+            }
+        }
+        hs.offset = offset;
+        self.cursor.col = prompt_width + cursor - offset;
+        self.end.col = prompt_width + (hs.text_width - offset).min(hs.width);
+        debug_assert!(self.cursor <= self.end);
+    }
+
+    /// Width of `s` on the screen
+    #[must_use]
     pub fn width(&self, s: &str) -> Unit {
         self.grapheme_cluster_mode.width(s)
     }
+
+    /// Choose the rows to display on a terminal with `rows` rows such that
+    /// the cursor is visible, trying to keep `first_row` (of the previous
+    /// viewport) at the top.
+    ///
+    /// When some rows are hidden above (or below), one row is used to display
+    /// a scroll indicator.
+    pub fn update_viewport(&mut self, first_row: Unit, rows: Unit) {
+        if rows < 3 || self.end.row < rows {
+            // everything fits (or the terminal is too small to scroll)
+            self.viewport = None;
+            return;
+        }
+        let cursor_row = self.cursor.row;
+        // do not leave empty rows at the bottom
+        let mut first_row = first_row.min(self.end.row + 2 - rows).min(cursor_row);
+        let mut last_row = {
+            let height = rows - Unit::from(first_row > 0);
+            if self.end.row - first_row < height {
+                self.end.row
+            } else {
+                first_row + height - 2
+            }
+        };
+        if cursor_row > last_row {
+            // scroll down: cursor on the last row displayed
+            last_row = cursor_row;
+            let below = Unit::from(last_row < self.end.row);
+            first_row = last_row + 2 + below - rows;
+        }
+        debug_assert!(first_row <= cursor_row && cursor_row <= last_row);
+        self.viewport = Some(Viewport {
+            first_row,
+            last_row,
+        });
+    }
+
+    /// First row displayed
+    pub fn first_row(&self) -> Unit {
+        self.viewport.map_or(0, |vp| vp.first_row)
+    }
+
+    /// Number of rows hidden above the first row displayed
+    pub fn rows_above(&self) -> Unit {
+        self.first_row()
+    }
+
+    /// Number of rows hidden below the last row displayed
+    pub fn rows_below(&self) -> Unit {
+        self.viewport
+            .map_or(0, |vp| self.end.row.saturating_sub(vp.last_row))
+    }
+
+    /// Tell if `row` is displayed
+    pub fn is_row_visible(&self, row: Unit) -> bool {
+        self.viewport
+            .is_none_or(|vp| vp.first_row <= row && row <= vp.last_row)
+    }
+
+    /// Convert `row` (relative to the start of the prompt) into a row
+    /// relative to the first row drawn on screen (scroll indicator included)
+    pub fn screen_row(&self, row: Unit) -> Unit {
+        match self.viewport {
+            None => row,
+            Some(vp) => row - vp.first_row + Unit::from(vp.first_row > 0),
+        }
+    }
+
+    /// Convert `screen_row` (relative to the first row drawn on screen) back
+    /// into a row relative to the start of the prompt (`None` for scroll
+    /// indicators and rows not drawn)
+    pub fn row_at(&self, screen_row: Unit) -> Option<Unit> {
+        let row = match self.viewport {
+            None => screen_row,
+            Some(vp) => (screen_row + vp.first_row).checked_sub(Unit::from(vp.first_row > 0))?,
+        };
+        (row <= self.end.row && self.is_row_visible(row)).then_some(row)
+    }
+
+    /// Cursor row relative to the first row drawn on screen
+    pub fn screen_cursor_row(&self) -> Unit {
+        self.screen_row(self.cursor.row)
+    }
+
+    /// Last row drawn on screen (scroll indicator included)
+    pub fn screen_end_row(&self) -> Unit {
+        match self.viewport {
+            None => self.end.row,
+            Some(vp) => self.screen_row(vp.last_row) + Unit::from(self.rows_below() > 0),
+        }
+    }
+
+    /// Position after the last thing drawn on screen: either the end of the
+    /// input or the bottom scroll indicator.
+    pub fn drawn_end(&self) -> Position {
+        match self.viewport {
+            Some(vp) if vp.last_row < self.end.row => Position {
+                col: 0,
+                row: vp.last_row + 1,
+            },
+            _ => self.end,
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use super::{HScroll, Layout, Position, Viewport};
     use crate::GraphemeClusterMode;
 
+    fn layout(cursor_row: u16, end_row: u16) -> Layout {
+        Layout {
+            cursor: Position {
+                col: 0,
+                row: cursor_row,
+            },
+            end: Position {
+                col: 0,
+                row: end_row,
+            },
+            ..Layout::default()
+        }
+    }
+
+    #[test]
+    fn viewport() {
+        // everything fits
+        let mut l = layout(2, 9);
+        l.update_viewport(0, 10);
+        assert_eq!(None, l.viewport);
+        assert_eq!(9, l.screen_end_row());
+
+        // cursor at the top: scroll indicator at the bottom
+        let mut l = layout(0, 20);
+        l.update_viewport(0, 10);
+        assert_eq!(
+            Some(Viewport {
+                first_row: 0,
+                last_row: 8
+            }),
+            l.viewport
+        );
+        assert_eq!(0, l.rows_above());
+        assert_eq!(12, l.rows_below());
+        assert_eq!(9, l.screen_end_row());
+
+        // cursor at the bottom: scroll indicator at the top
+        let mut l = layout(20, 20);
+        l.update_viewport(0, 10);
+        assert_eq!(
+            Some(Viewport {
+                first_row: 12,
+                last_row: 20
+            }),
+            l.viewport
+        );
+        assert_eq!(12, l.rows_above());
+        assert_eq!(0, l.rows_below());
+        assert_eq!(9, l.screen_cursor_row());
+        assert_eq!(9, l.screen_end_row());
+
+        // cursor in the middle: both scroll indicators
+        let mut l = layout(10, 20);
+        l.update_viewport(0, 10);
+        assert_eq!(
+            Some(Viewport {
+                first_row: 3,
+                last_row: 10
+            }),
+            l.viewport
+        );
+        assert_eq!(8, l.screen_cursor_row());
+        assert_eq!(9, l.screen_end_row());
+        assert_eq!(Position { col: 0, row: 11 }, l.drawn_end());
+
+        // previous viewport is kept while the cursor is visible
+        let mut l = layout(5, 20);
+        l.update_viewport(3, 10);
+        assert_eq!(
+            Some(Viewport {
+                first_row: 3,
+                last_row: 10
+            }),
+            l.viewport
+        );
+        // cursor moved above
+        let mut l = layout(2, 20);
+        l.update_viewport(3, 10);
+        assert_eq!(
+            Some(Viewport {
+                first_row: 2,
+                last_row: 9
+            }),
+            l.viewport
+        );
+        assert!(l.is_row_visible(2));
+        assert!(!l.is_row_visible(1));
+        assert!(!l.is_row_visible(10));
+    }
+
+    fn hscroll(cursor: u16, text_width: u16) -> Layout {
+        Layout {
+            prompt_size: Position { col: 2, row: 0 },
+            cursor: Position {
+                col: 2 + cursor,
+                row: 0,
+            },
+            end: Position {
+                col: 2 + text_width,
+                row: 0,
+            },
+            hscroll: Some(HScroll {
+                offset: 0,
+                width: 10,
+                text_width,
+            }),
+            ..Layout::default()
+        }
+    }
+
+    #[test]
+    fn horizontal_scroll() {
+        // everything fits
+        let mut l = hscroll(5, 9);
+        l.update_hscroll(0);
+        assert_eq!(0, l.hscroll.unwrap().offset);
+        assert_eq!(7, l.cursor.col);
+        assert_eq!(11, l.end.col);
+
+        // cursor at the end
+        let mut l = hscroll(20, 20);
+        l.update_hscroll(0);
+        let hs = l.hscroll.unwrap();
+        assert_eq!(11, hs.offset);
+        assert!(hs.has_left_marker());
+        assert!(!hs.has_right_marker());
+        assert_eq!(11, l.cursor.col);
+        assert_eq!(11, l.end.col);
+
+        // cursor at the start
+        let mut l = hscroll(0, 20);
+        l.update_hscroll(11);
+        let hs = l.hscroll.unwrap();
+        assert_eq!(0, hs.offset);
+        assert!(!hs.has_left_marker());
+        assert!(hs.has_right_marker());
+        assert_eq!(2, l.cursor.col);
+        assert_eq!(12, l.end.col);
+
+        // cursor in the middle, previous offset kept
+        let mut l = hscroll(10, 20);
+        l.update_hscroll(5);
+        let hs = l.hscroll.unwrap();
+        assert_eq!(5, hs.offset);
+        assert!(hs.has_left_marker());
+        assert!(hs.has_right_marker());
+        assert_eq!(7, l.cursor.col);
+
+        // cursor hidden by the `>` marker
+        let mut l = hscroll(9, 20);
+        l.update_hscroll(0);
+        let hs = l.hscroll.unwrap();
+        assert_eq!(1, hs.offset);
+        assert_eq!(10, l.cursor.col);
+
+        // cursor hidden by the `<` marker
+        let mut l = hscroll(5, 20);
+        l.update_hscroll(5);
+        assert_eq!(4, l.hscroll.unwrap().offset);
+        assert_eq!(3, l.cursor.col);
+    }
+
     #[test]
     fn unicode_width() {
         assert_eq!(1, super::uwidth("a"));
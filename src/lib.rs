@@ -35,38 +35,52 @@ mod kill_ring;
 mod layout;
 pub mod line_buffer;
 mod prompt;
+#[cfg(all(unix, not(target_arch = "wasm32")))]
+pub mod pty;
+pub mod shell_history;
 #[cfg(feature = "with-sqlite-history")]
 pub mod sqlite_history;
+pub mod stream;
+pub mod term;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 mod tty;
 mod undo;
 pub mod validate;
 
+use std::borrow::Cow;
 use std::io::{self, BufRead, Write};
 use std::path::Path;
 use std::{fmt, result};
 
 use log::debug;
+#[cfg(feature = "custom-bindings")]
+use radix_trie::TrieCommon as _;
 #[cfg(feature = "derive")]
 pub use rustyline_derive::{Completer, Helper, Highlighter, Hinter, Validator};
 
 #[cfg(feature = "custom-bindings")]
-pub use crate::binding::{ConditionalEventHandler, Event, EventContext, EventHandler};
+pub use crate::binding::{
+    BindingConflict, ConditionalEventHandler, Event, EventContext, EventHandler,
+};
 use crate::completion::{Candidate, Completer, longest_common_prefix};
-pub use crate::config::{Behavior, ColorMode, CompletionType, Config, EditMode, HistoryDuplicates};
+pub use crate::config::{
+    Behavior, ColorMode, CompletionType, Config, EditMode, HistoryDuplicates, HistoryRanking,
+};
 use crate::edit::{RefreshKind, State};
 use crate::error::ReadlineError;
 use crate::highlight::{CmdKind, Highlighter};
 use crate::hint::Hinter;
-use crate::history::{DefaultHistory, History, SearchDirection};
+use crate::history::{DefaultHistory, History, SearchDirection, SearchMode, SearchResult};
 pub use crate::keymap::{Anchor, At, CharSearch, Cmd, InputMode, Movement, RepeatCount, Word};
 use crate::keymap::{Bindings, InputState, Refresher};
-pub use crate::keys::{KeyCode, KeyEvent, Modifiers};
+pub use crate::keys::{KeyCode, KeyEvent, Modifiers, Mouse, MouseButton};
 use crate::kill_ring::KillRing;
 pub use crate::layout::GraphemeClusterMode;
 use crate::layout::Unit;
-pub use crate::prompt::Prompt;
+pub use crate::prompt::{Prompt, RightPromptPosition};
 pub use crate::tty::ExternalPrinter;
-use crate::tty::{Buffer, RawMode as _, RawReader as _, Renderer as _, Term, Terminal};
+use crate::tty::{RawMode, RawReader as _, Renderer, Term, Terminal};
 pub use crate::undo::Changeset;
 use crate::validate::Validator;
 
@@ -74,9 +88,9 @@ use crate::validate::Validator;
 pub type Result<T> = result::Result<T, ReadlineError>;
 
 /// Completes the line/word
-fn complete_line<H: Helper, P: Prompt + ?Sized>(
-    rdr: &mut <Terminal as Term>::Reader,
-    s: &mut State<'_, '_, H, P>,
+fn complete_line<H: Helper, P: Prompt + ?Sized, R: Renderer>(
+    rdr: &mut R::Reader,
+    s: &mut State<'_, '_, H, P, R>,
     input_state: &mut InputState,
     config: &Config,
 ) -> Result<Option<Cmd>> {
@@ -171,9 +185,10 @@ fn complete_line<H: Helper, P: Prompt + ?Sized>(
                 return Ok(Some(cmd));
             }
         }
-        // move cursor to EOL to avoid overwriting the command line
+        // move cursor to the end of the input to avoid overwriting the command
+        // line (and to scroll the viewport down)
         let save_pos = s.line.pos();
-        s.edit_move_end()?;
+        s.edit_move_buffer_end(CmdKind::Other)?;
         s.line.set_pos(save_pos);
         // we got a second tab, maybe show list of possible completions
         let asked = candidates.len() > config.completion_prompt_limit();
@@ -271,7 +286,9 @@ fn complete_line<H: Helper, P: Prompt + ?Sized>(
 }
 
 /// Completes the current hint
-fn complete_hint_line<H: Helper, P: Prompt + ?Sized>(s: &mut State<'_, '_, H, P>) -> Result<()> {
+fn complete_hint_line<H: Helper, P: Prompt + ?Sized, R: Renderer>(
+    s: &mut State<'_, '_, H, P, R>,
+) -> Result<()> {
     let Some(hint) = s.hint.as_ref() else {
         return Ok(());
     };
@@ -286,9 +303,9 @@ fn complete_hint_line<H: Helper, P: Prompt + ?Sized>(s: &mut State<'_, '_, H, P>
     s.refresh_line()
 }
 
-fn page_completions<C: Candidate, H: Helper, P: Prompt + ?Sized>(
-    rdr: &mut <Terminal as Term>::Reader,
-    s: &mut State<'_, '_, H, P>,
+fn page_completions<C: Candidate, H: Helper, P: Prompt + ?Sized, R: Renderer>(
+    rdr: &mut R::Reader,
+    s: &mut State<'_, '_, H, P, R>,
     input_state: &mut InputState,
     candidates: &[C],
 ) -> Result<Option<Cmd>> {
@@ -325,6 +342,7 @@ fn page_completions<C: Candidate, H: Helper, P: Prompt + ?Sized>(
                 && cmd != Cmd::Kill(Movement::BackwardChar(1))
                 && cmd != Cmd::AcceptLine
                 && cmd != Cmd::Newline
+                && cmd != Cmd::LineDownOrNextHistory(1) // Down or mouse wheel
                 && !matches!(cmd, Cmd::AcceptOrInsertLine { .. })
             {
                 cmd = s.next_cmd(input_state, rdr, false, true)?;
@@ -333,7 +351,10 @@ fn page_completions<C: Candidate, H: Helper, P: Prompt + ?Sized>(
                 Cmd::SelfInsert(1, 'y' | 'Y' | ' ') => {
                     pause_row += s.out.get_rows() - 1;
                 }
-                Cmd::AcceptLine | Cmd::Newline | Cmd::AcceptOrInsertLine { .. } => {
+                Cmd::AcceptLine
+                | Cmd::Newline
+                | Cmd::LineDownOrNextHistory(1)
+                | Cmd::AcceptOrInsertLine { .. } => {
                     pause_row += 1;
                 }
                 _ => break,
@@ -365,14 +386,22 @@ fn page_completions<C: Candidate, H: Helper, P: Prompt + ?Sized>(
     Ok(None)
 }
 
+/// Maximum number of ranked matches visited by incremental search
+const MAX_RANKED_MATCHES: usize = 100;
+
 /// Incremental search
-fn reverse_incremental_search<H: Helper, I: History, P: Prompt + ?Sized>(
-    rdr: &mut <Terminal as Term>::Reader,
-    s: &mut State<'_, '_, H, P>,
+///
+/// `CycleSearchMode` (Ctrl-O) cycles through search modes.
+/// With a `ranking` other than `Recency` (if supported by `history`), matches
+/// are visited best first.
+fn reverse_incremental_search<H: Helper, P: Prompt + ?Sized, R: Renderer>(
+    rdr: &mut R::Reader,
+    s: &mut State<'_, '_, H, P, R>,
     input_state: &mut InputState,
-    history: &I,
+    ranking: HistoryRanking,
 ) -> Result<Option<Cmd>> {
-    if history.is_empty() {
+    s.ctx.sync()?;
+    if s.ctx.history().is_empty() {
         return Ok(None);
     }
     let mark = s.changes.begin();
@@ -381,32 +410,59 @@ fn reverse_incremental_search<H: Helper, I: History, P: Prompt + ?Sized>(
     let backup_pos = s.line.pos();
 
     let mut search_buf = String::new();
-    let mut history_idx = history.len() - 1;
+    let mut history_idx = s.ctx.history().len() - 1;
     let mut direction = SearchDirection::Reverse;
+    let mut mode = if cfg!(feature = "case_insensitive_history_search") {
+        SearchMode::CaseInsensitive
+    } else {
+        SearchMode::Substring
+    };
     let mut success = true;
+    // ranked matches of `search_buf` and the current one
+    let mut ranked: Option<(Vec<SearchResult<'static>>, usize)> = None;
 
     let mut cmd;
     // Display the reverse-i-search prompt and process chars
     loop {
+        let label = match mode {
+            SearchMode::Substring => "",
+            SearchMode::Prefix => "prefix ",
+            SearchMode::CaseInsensitive => "i-case ",
+            SearchMode::Regex => "regex ",
+            SearchMode::Fts => "fts ",
+        };
         let prompt = if success {
-            format!("(reverse-i-search)`{search_buf}': ")
+            format!("({label}reverse-i-search)`{search_buf}': ")
         } else {
-            format!("(failed reverse-i-search)`{search_buf}': ")
+            format!("(failed {label}reverse-i-search)`{search_buf}': ")
         };
         s.refresh_prompt_and_line(&prompt)?;
 
         cmd = s.next_cmd(input_state, rdr, true, true)?;
         if let Cmd::SelfInsert(_, c) = cmd {
             search_buf.push(c);
+            ranked = None;
         } else {
             match cmd {
                 Cmd::Kill(Movement::BackwardChar(_)) => {
                     search_buf.pop();
+                    ranked = None;
                     continue;
                 }
+                Cmd::CycleSearchMode => {
+                    mode = mode.next();
+                    ranked = None;
+                }
                 Cmd::ReverseSearchHistory => {
                     direction = SearchDirection::Reverse;
-                    if history_idx > 0 {
+                    if let Some((ref matches, ref mut rank)) = ranked {
+                        if *rank + 1 < matches.len() {
+                            *rank += 1;
+                        } else {
+                            success = false;
+                            continue;
+                        }
+                    } else if history_idx > 0 {
                         history_idx -= 1;
                     } else {
                         success = false;
@@ -415,7 +471,14 @@ fn reverse_incremental_search<H: Helper, I: History, P: Prompt + ?Sized>(
                 }
                 Cmd::ForwardSearchHistory => {
                     direction = SearchDirection::Forward;
-                    if history_idx < history.len() - 1 {
+                    if let Some((_, ref mut rank)) = ranked {
+                        if *rank > 0 {
+                            *rank -= 1;
+                        } else {
+                            success = false;
+                            continue;
+                        }
+                    } else if history_idx < s.ctx.history().len() - 1 {
                         history_idx += 1;
                     } else {
                         success = false;
@@ -436,23 +499,52 @@ fn reverse_incremental_search<H: Helper, I: History, P: Prompt + ?Sized>(
                 _ => break,
             }
         }
-        success = match history.search(&search_buf, history_idx, direction)? {
-            Some(sr) => {
+        if ranking != HistoryRanking::Recency && ranked.is_none() {
+            let matches = s
+                .ctx
+                .history()
+                .ranked(&search_buf, mode, ranking, MAX_RANKED_MATCHES);
+            ranked = match matches {
+                Ok(matches) => {
+                    let matches = matches
+                        .into_iter()
+                        .map(|sr| SearchResult {
+                            entry: Cow::Owned(sr.entry.into_owned()),
+                            ..sr
+                        })
+                        .collect();
+                    Some((matches, 0))
+                }
+                Err(ReadlineError::Io(err)) if err.kind() == io::ErrorKind::Unsupported => None,
+                Err(err) => return Err(err),
+            };
+        }
+        let found = if let Some((ref matches, rank)) = ranked {
+            Ok(matches.get(rank).cloned())
+        } else {
+            s.ctx
+                .history()
+                .search_with_mode(&search_buf, history_idx, direction, mode)
+        };
+        success = match found {
+            Ok(Some(sr)) => {
                 history_idx = sr.idx;
                 s.line.update(&sr.entry, sr.pos, &mut s.changes);
                 true
             }
-            _ => false,
+            Ok(None) => false,
+            Err(ReadlineError::Io(err)) if err.kind() == io::ErrorKind::Unsupported => false,
+            Err(err) => return Err(err),
         };
     }
     s.changes.end();
     Ok(Some(cmd))
 }
 
-struct Guard<'m>(&'m tty::Mode);
+struct Guard<'m, M: RawMode>(&'m M);
 
 #[expect(unused_must_use)]
-impl Drop for Guard<'_> {
+impl<M: RawMode> Drop for Guard<'_, M> {
     fn drop(&mut self) {
         let Guard(mode) = *self;
         mode.disable_raw_mode();
@@ -486,6 +578,30 @@ fn apply_backspace_direct(input: &str) -> String {
     out
 }
 
+/// Append the next line of `reader` to `input` without its trailing
+/// newline, returns whether `\r` and `\n` were removed.
+fn read_line_direct(reader: &mut impl BufRead, input: &mut String) -> Result<(bool, bool)> {
+    if reader.read_line(input)? == 0 {
+        return Err(ReadlineError::Eof);
+    }
+    // Remove trailing newline
+    let trailing_n = input.ends_with('\n');
+    let trailing_r;
+
+    if trailing_n {
+        input.pop();
+        trailing_r = input.ends_with('\r');
+        if trailing_r {
+            input.pop();
+        }
+    } else {
+        trailing_r = false;
+    }
+
+    *input = apply_backspace_direct(input);
+    Ok((trailing_r, trailing_n))
+}
+
 fn readline_direct(
     mut reader: impl BufRead,
     mut writer: impl Write,
@@ -494,24 +610,7 @@ fn readline_direct(
     let mut input = String::new();
 
     loop {
-        if reader.read_line(&mut input)? == 0 {
-            return Err(ReadlineError::Eof);
-        }
-        // Remove trailing newline
-        let trailing_n = input.ends_with('\n');
-        let trailing_r;
-
-        if trailing_n {
-            input.pop();
-            trailing_r = input.ends_with('\r');
-            if trailing_r {
-                input.pop();
-            }
-        } else {
-            trailing_r = false;
-        }
-
-        input = apply_backspace_direct(&input);
+        let (trailing_r, trailing_n) = read_line_direct(&mut reader, &mut input)?;
 
         match validator.as_ref() {
             None => return Ok(input),
@@ -545,6 +644,44 @@ fn readline_direct(
     }
 }
 
+/// Read lines from a script (see [`Editor::set_script`]) until the input is
+/// complete: unlike [`readline_direct`], nothing is printed and an invalid
+/// input, or an input still incomplete at the end of the script, is an error.
+fn readline_script(mut reader: impl BufRead, validator: Option<&impl Validator>) -> Result<String> {
+    let mut input = String::new();
+
+    loop {
+        let (trailing_r, trailing_n) = match read_line_direct(&mut reader, &mut input) {
+            Err(ReadlineError::Eof) if !input.is_empty() => {
+                return Err(ReadlineError::Validation(format!(
+                    "incomplete input at end of script: {input:?}"
+                )));
+            }
+            r => r?,
+        };
+        let Some(v) = validator else {
+            return Ok(input);
+        };
+        let mut ctx = input.as_str();
+        let mut ctx = validate::ValidationContext::new(&mut ctx);
+        match v.validate(&mut ctx)? {
+            validate::ValidationResult::Valid(_) => return Ok(input),
+            validate::ValidationResult::Invalid(msg) => {
+                return Err(ReadlineError::Validation(msg.unwrap_or_default()));
+            }
+            validate::ValidationResult::Incomplete => {
+                // Add newline and keep on taking input
+                if trailing_r {
+                    input.push('\r');
+                }
+                if trailing_n {
+                    input.push('\n');
+                }
+            }
+        }
+    }
+}
+
 /// Syntax specific helper.
 ///
 /// TODO Tokenizer/parser used for both completion, suggestion, highlighting.
@@ -559,24 +696,63 @@ impl Helper for () {}
 
 /// Completion/suggestion context
 pub struct Context<'h> {
-    history: &'h dyn History,
+    history: HistoryRef<'h>,
     history_index: usize,
 }
 
+/// History seen by helpers
+enum HistoryRef<'h> {
+    Shared(&'h dyn History),
+    /// Entries added by other sessions are picked up before history
+    /// navigation / search (see [`Config::history_sharing`])
+    Synced(&'h mut dyn History),
+}
+
+impl<'h> std::ops::Deref for HistoryRef<'h> {
+    type Target = dyn History + 'h;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Shared(history) => *history,
+            Self::Synced(history) => &**history,
+        }
+    }
+}
+
 impl<'h> Context<'h> {
     /// Constructor. Visible for testing.
     #[must_use]
     pub fn new(history: &'h dyn History) -> Self {
         Self {
-            history,
+            history: HistoryRef::Shared(history),
             history_index: history.len(),
         }
     }
 
+    /// Constructor with history sharing (see [`Context::sync`])
+    pub(crate) fn synced(history: &'h mut dyn History) -> Self {
+        Self {
+            history_index: history.len(),
+            history: HistoryRef::Synced(history),
+        }
+    }
+
     /// Return an immutable reference to the history object.
     #[must_use]
     pub fn history(&self) -> &dyn History {
-        self.history
+        &*self.history
+    }
+
+    /// Pick up entries added by other sessions (see [`History::sync`]) unless
+    /// history is being browsed
+    pub(crate) fn sync(&mut self) -> Result<()> {
+        if let HistoryRef::Synced(ref mut history) = self.history
+            && self.history_index == history.len()
+            && history.sync()?
+        {
+            self.history_index = history.len();
+        }
+        Ok(())
     }
 
     /// The history index we are currently editing
@@ -588,14 +764,16 @@ impl<'h> Context<'h> {
 
 /// Line editor
 #[must_use]
-pub struct Editor<H: Helper, I: History> {
-    term: Terminal,
-    buffer: Option<Buffer>,
+pub struct Editor<H: Helper, I: History, T: Term = Terminal> {
+    term: T,
+    buffer: Option<T::Buffer>,
     history: I,
     helper: Option<H>,
     kill_ring: KillRing,
     config: Config,
     custom_bindings: Bindings,
+    /// Lines read instead of the terminal (see [`Editor::set_script`])
+    script: Option<Box<dyn BufRead + Send + Sync>>,
 }
 
 /// Default editor with default helper and `DefaultHistory`
@@ -643,7 +821,15 @@ impl<H: Helper, I: History> Editor<H, I> {
     /// Create an editor with a custom history impl.
     pub fn with_history(config: Config, history: I) -> Result<Self> {
         let term = Terminal::new(&config)?;
-        Ok(Self {
+        Ok(Self::with_term(config, history, term))
+    }
+}
+
+impl<H: Helper, I: History, T: Term> Editor<H, I, T> {
+    /// Create an editor over an already opened terminal (see
+    /// [`term`]).
+    pub fn with_term(config: Config, history: I, term: T) -> Self {
+        Self {
             term,
             buffer: None,
             history,
@@ -651,7 +837,8 @@ impl<H: Helper, I: History> Editor<H, I> {
             kill_ring: KillRing::new(60),
             config,
             custom_bindings: Bindings::new(),
-        })
+            script: None,
+        }
     }
 
     /// This method will read a line from STDIN and will display a `prompt`.
@@ -682,12 +869,32 @@ impl<H: Helper, I: History> Editor<H, I> {
         self.readline_with(prompt, Some(initial))
     }
 
+    /// Read lines from `script` (like a file) instead of the terminal: lines
+    /// are validated (and accumulated while incomplete) and added to the
+    /// history like interactive input.
+    ///
+    /// Nothing is printed: an input rejected by the [`Validator`], or still
+    /// incomplete at the end of the script, is reported as
+    /// [`ReadlineError::Validation`] (and skipped).
+    pub fn set_script<R: BufRead + Send + Sync + 'static>(&mut self, script: R) {
+        self.script = Some(Box::new(script));
+    }
+
     fn readline_with<P: Prompt + ?Sized>(
         &mut self,
         prompt: &P,
         initial: Option<(&str, &str)>,
     ) -> Result<String> {
-        if self.term.is_unsupported() {
+        if let Some(ref mut script) = self.script {
+            debug!(target: "rustyline", "read from script");
+            let user_input = readline_script(script, self.helper.as_ref());
+            if self.config.auto_add_history()
+                && let Ok(ref line) = user_input
+            {
+                self.add_history_entry(line.as_str())?;
+            }
+            user_input
+        } else if self.term.is_unsupported() {
             debug!(target: "rustyline", "unsupported terminal");
             // Write prompt and flush it to stdout
             let mut stdout = io::stdout();
@@ -721,13 +928,18 @@ impl<H: Helper, I: History> Editor<H, I> {
         &mut self,
         prompt: &P,
         initial: Option<(&str, &str)>,
-        original_mode: &tty::Mode,
-        term_key_map: tty::KeyMap,
+        original_mode: &T::Mode,
+        term_key_map: T::KeyMap,
     ) -> Result<String> {
         let mut stdout = self.term.create_writer(&self.config);
 
         self.kill_ring.reset(); // TODO recreate a new kill ring vs reset
-        let ctx = Context::new(&self.history);
+        let ctx = if self.config.history_sharing() {
+            self.history.sync()?;
+            Context::synced(&mut self.history)
+        } else {
+            Context::new(&self.history)
+        };
         let mut s = State::new(&mut stdout, prompt, self.helper.as_ref(), ctx);
 
         let mut input_state = InputState::new(&self.config, &self.custom_bindings);
@@ -753,6 +965,15 @@ impl<H: Helper, I: History> Editor<H, I> {
                 return Err(e);
             }
         }
+        if self.term.is_output_tty() && self.config.enable_mouse() {
+            // before the prompt is drawn, so that clicks are mapped to the
+            // line without asking the terminal again
+            match s.out.cursor_row(&mut rdr) {
+                Ok(row) => s.prompt_row = row,
+                Err(ReadlineError::Signal(error::Signal::Resize)) => s.out.update_size(),
+                Err(e) => return Err(e),
+            }
+        }
         s.refresh_line()?;
 
         loop {
@@ -775,8 +996,12 @@ impl<H: Helper, I: History> Editor<H, I> {
 
             if cmd == Cmd::ReverseSearchHistory {
                 // Search history backward
-                let next =
-                    reverse_incremental_search(&mut rdr, &mut s, &mut input_state, &self.history)?;
+                let next = reverse_incremental_search(
+                    &mut rdr,
+                    &mut s,
+                    &mut input_state,
+                    self.config.history_ranking(),
+                )?;
                 if let Some(next) = next {
                     cmd = next;
                 } else {
@@ -784,11 +1009,29 @@ impl<H: Helper, I: History> Editor<H, I> {
                 }
             }
 
+            if let Cmd::MoveCursorTo { row, col } = cmd {
+                if s.prompt_row.is_none() {
+                    // moved by an external print or a resize: ask the terminal
+                    s.prompt_row = s
+                        .out
+                        .cursor_row(&mut rdr)?
+                        .and_then(|row| row.checked_sub(s.layout.screen_cursor_row()));
+                }
+                s.edit_move_to_screen(row, col)?;
+                continue;
+            }
+
+            if cmd == Cmd::DescribeKey {
+                let msg = input_state.describe_key(&mut rdr, &mut s)?;
+                s.refresh_line_with_msg(Some(&format!("\n{msg}")), CmdKind::Other)?;
+                continue;
+            }
+
             #[cfg(unix)]
             if cmd == Cmd::Suspend {
                 debug!(target: "rustyline", "SIGTSTP");
                 original_mode.disable_raw_mode()?;
-                tty::suspend()?;
+                self.term.suspend()?;
                 let _ = self.term.enable_raw_mode(&self.config)?; // TODO original_mode may have changed
                 s.out.update_size(); // window may have been resized
                 s.refresh_line()?;
@@ -815,7 +1058,7 @@ impl<H: Helper, I: History> Editor<H, I> {
                 cmd,
                 Cmd::AcceptLine | Cmd::Newline | Cmd::AcceptOrInsertLine { .. }
             ) {
-                self.term.cursor = s.layout.cursor.col as usize;
+                self.term.set_cursor(s.layout.cursor.col as usize);
             }
 
             // Execute things can be done solely on a state object
@@ -828,6 +1071,8 @@ impl<H: Helper, I: History> Editor<H, I> {
         // Move to end, in case cursor was in the middle of the line, so that
         // next thing application prints goes after the input
         s.edit_move_buffer_end(CmdKind::ForcedRefresh)?;
+        // Replace the prompt by its transient version (if any) in the scrollback
+        s.refresh_transient_prompt()?;
 
         if cfg!(windows) {
             let _ = original_mode; // silent warning
@@ -905,6 +1150,115 @@ impl<H: Helper, I: History> Editor<H, I> {
             .remove(&Event::normalize(key_seq.into()))
     }
 
+    /// Effective key bindings of the current edit mode (and vi `input_mode`,
+    /// ignored in Emacs mode): built-in bindings replaced by custom ones.
+    ///
+    /// Built-in commands are listed with their default repeat count (on an
+    /// empty line without hint), commands which need a character argument (like
+    /// vi `f`) are not listed and commands which need a motion are only
+    /// listed for the whole line (like vi `dd`).
+    /// Custom conditional handlers are not listed (but the built-in bindings
+    /// they may fall back to are).
+    #[cfg(feature = "custom-bindings")]
+    #[must_use]
+    pub fn key_bindings(&self, input_mode: InputMode) -> Vec<(Event, Cmd)> {
+        let (mode, input_mode) = self.input_modes(input_mode);
+        let mut bindings = Vec::new();
+        for (keys, cmd) in keymap::builtin_bindings(mode, input_mode) {
+            let evt = Event::KeySeq(keys.to_vec());
+            let cmd = match self.custom_binding(keys[0]) {
+                Some(EventHandler::Simple(cmd)) if keys.len() == 1 => cmd,
+                // shadowed by a custom binding of its first key
+                Some(EventHandler::Simple(_)) => continue,
+                _ => match self.custom_bindings.get(&evt) {
+                    Some(EventHandler::Simple(cmd))
+                        if !keymap::is_builtin_prefix(mode, input_mode, keys[0]) =>
+                    {
+                        cmd
+                    }
+                    _ => cmd,
+                },
+            };
+            bindings.push((evt, cmd.clone()));
+        }
+        for (evt, handler) in self.custom_bindings.iter() {
+            if let EventHandler::Simple(cmd) = handler
+                && !bindings.iter().any(|(e, _)| e == evt)
+                && self.unreachable(mode, input_mode, evt).is_none()
+            {
+                bindings.push((evt.clone(), cmd.clone()));
+            }
+        }
+        bindings
+    }
+
+    /// Custom bindings which replace built-in ones or which cannot be reached
+    /// in the current edit mode (and vi `input_mode`, ignored in Emacs mode).
+    ///
+    /// [`Event::Any`] is not checked.
+    #[cfg(feature = "custom-bindings")]
+    #[must_use]
+    pub fn binding_conflicts(&self, input_mode: InputMode) -> Vec<BindingConflict> {
+        let (mode, input_mode) = self.input_modes(input_mode);
+        let mut conflicts = Vec::new();
+        for (keys, cmd) in keymap::builtin_bindings(mode, input_mode) {
+            let evt = Event::KeySeq(keys.to_vec());
+            if self.custom_bindings.get(&evt).is_some()
+                && (keys.len() == 1 || !keymap::is_builtin_prefix(mode, input_mode, keys[0]))
+            {
+                conflicts.push(BindingConflict::Override(evt, cmd.clone()));
+            } else if keys.len() > 1 && self.custom_bindings.get(&keys[0].into()).is_some() {
+                conflicts.push(BindingConflict::Unreachable(evt, keys[0].into()));
+            }
+        }
+        for (evt, _) in self.custom_bindings.iter() {
+            if let Some(prefix) = self.unreachable(mode, input_mode, evt) {
+                conflicts.push(BindingConflict::Unreachable(evt.clone(), prefix));
+            }
+        }
+        conflicts
+    }
+
+    #[cfg(feature = "custom-bindings")]
+    fn input_modes(&self, input_mode: InputMode) -> (EditMode, InputMode) {
+        match self.config.edit_mode() {
+            EditMode::Emacs => (EditMode::Emacs, InputMode::Insert),
+            mode => (mode, input_mode),
+        }
+    }
+
+    /// Custom handler of a single key
+    #[cfg(feature = "custom-bindings")]
+    fn custom_binding(&self, key: KeyEvent) -> Option<&EventHandler> {
+        self.custom_bindings
+            .get(&Event::normalize(key.into()))
+            .or_else(|| {
+                matches!(key, KeyEvent(KeyCode::Mouse(_), _))
+                    .then(|| self.custom_bindings.get(&Event::Mouse()))
+                    .flatten()
+            })
+            .or_else(|| self.custom_bindings.get(&Event::Any))
+    }
+
+    /// Returns the bound first key which makes the custom sequence `evt`
+    /// unreachable
+    #[cfg(feature = "custom-bindings")]
+    fn unreachable(&self, mode: EditMode, input_mode: InputMode, evt: &Event) -> Option<Event> {
+        match evt {
+            Event::KeySeq(keys) if keys.len() > 1 => {
+                let first = Event::from(keys[0]);
+                if self.custom_bindings.get(&first).is_some()
+                    || keymap::is_builtin_prefix(mode, input_mode, keys[0])
+                {
+                    Some(first)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
     /// Returns an iterator over edited lines.
     /// Iterator ends at [EOF](ReadlineError::Eof).
     /// ```
@@ -951,20 +1305,17 @@ impl<H: Helper, I: History> Editor<H, I> {
     }
 
     /// Create an external printer
-    pub fn create_external_printer(&mut self) -> Result<<Terminal as Term>::ExternalPrinter> {
+    pub fn create_external_printer(&mut self) -> Result<T::ExternalPrinter> {
         self.term.create_external_printer()
     }
 
     /// Change cursor visibility
-    pub fn set_cursor_visibility(
-        &mut self,
-        visible: bool,
-    ) -> Result<Option<<Terminal as Term>::CursorGuard>> {
+    pub fn set_cursor_visibility(&mut self, visible: bool) -> Result<Option<T::CursorGuard>> {
         self.term.set_cursor_visibility(visible)
     }
 }
 
-impl<H: Helper, I: History> config::Configurer for Editor<H, I> {
+impl<H: Helper, I: History, T: Term> config::Configurer for Editor<H, I, T> {
     fn config_mut(&mut self) -> &mut Config {
         &mut self.config
     }
@@ -979,13 +1330,18 @@ impl<H: Helper, I: History> config::Configurer for Editor<H, I> {
         self.history.ignore_dups(yes)
     }
 
+    fn set_history_duplicates(&mut self, duplicates: HistoryDuplicates) -> Result<()> {
+        self.config_mut().set_history_duplicates(duplicates);
+        self.history.set_duplicates(duplicates)
+    }
+
     fn set_history_ignore_space(&mut self, yes: bool) {
         self.config_mut().set_history_ignore_space(yes);
         self.history.ignore_space(yes);
     }
 }
 
-impl<H: Helper, I: History> fmt::Debug for Editor<H, I> {
+impl<H: Helper, I: History, T: Term + fmt::Debug> fmt::Debug for Editor<H, I, T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Editor")
             .field("term", &self.term)
@@ -994,12 +1350,12 @@ impl<H: Helper, I: History> fmt::Debug for Editor<H, I> {
     }
 }
 
-struct Iter<'a, H: Helper, I: History> {
-    editor: &'a mut Editor<H, I>,
+struct Iter<'a, H: Helper, I: History, T: Term> {
+    editor: &'a mut Editor<H, I, T>,
     prompt: &'a str,
 }
 
-impl<H: Helper, I: History> Iterator for Iter<'_, H, I> {
+impl<H: Helper, I: History, T: Term> Iterator for Iter<'_, H, I, T> {
     type Item = Result<String>;
 
     fn next(&mut self) -> Option<Result<String>> {
@@ -1,3 +1,16 @@
+use std::borrow::Cow;
+
+/// Row where the right prompt (see [`Prompt::right_raw`]) is displayed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RightPromptPosition {
+    /// First input row (next to the prompt)
+    #[default]
+    FirstRow,
+    /// Last input row (or hint row), moving down as the input grows
+    LastRow,
+}
+
 /// Provide two versions of the prompt:
 /// - the `raw` version used when `stdout` is not a tty, or when the terminal is
 ///   not supported or in `NO_COLOR` mode
@@ -14,6 +27,71 @@ pub trait Prompt {
     fn styled(&self) -> &str {
         self.raw()
     }
+
+    /// Optional text displayed at the right edge of the first (or last, see
+    /// [`Prompt::right_position`]) input row (like zsh `RPROMPT`), without
+    /// style.
+    ///
+    /// It is hidden as soon as the input (or hint) overlaps it.
+    ///
+    /// By default, there is no right prompt.
+    fn right_raw(&self) -> Option<&str> {
+        None
+    }
+    /// Right prompt with style(s), ANSI escape sequences
+    ///
+    /// Like [`Prompt::styled`], the styled version *must* have the same
+    /// display width as the raw version.
+    ///
+    /// By default, returns the raw right prompt.
+    fn right_styled(&self) -> Option<&str> {
+        self.right_raw()
+    }
+    /// Row where the right prompt is displayed
+    ///
+    /// By default, on the first input row.
+    fn right_position(&self) -> RightPromptPosition {
+        RightPromptPosition::FirstRow
+    }
+
+    /// Optional (shorter) prompt, without style, used to re-render the line
+    /// once it has been accepted, so that the scrollback is not cluttered
+    /// (like powerlevel10k transient prompt).
+    ///
+    /// By default, the prompt is left as is.
+    fn transient_raw(&self) -> Option<&str> {
+        None
+    }
+    /// Transient prompt with style(s), ANSI escape sequences
+    ///
+    /// Like [`Prompt::styled`], the styled version *must* have the same
+    /// display width as the raw version.
+    ///
+    /// By default, returns the raw transient prompt.
+    fn transient_styled(&self) -> Option<&str> {
+        self.transient_raw()
+    }
+
+    /// Optional prompt, without style, displayed at the start of each
+    /// subsequent line of a multiline input (like bash `PS2`).
+    ///
+    /// `line_number` is the (1-based) number of the input line: `2` for the
+    /// first continuation line.
+    ///
+    /// By default, there is no continuation prompt.
+    fn continuation_raw(&self, line_number: usize) -> Option<Cow<'_, str>> {
+        let _ = line_number;
+        None
+    }
+    /// Continuation prompt with style(s), ANSI escape sequences
+    ///
+    /// Like [`Prompt::styled`], the styled version *must* have the same
+    /// display width as the raw version.
+    ///
+    /// By default, returns the raw continuation prompt.
+    fn continuation_styled(&self, line_number: usize) -> Option<Cow<'_, str>> {
+        self.continuation_raw(line_number)
+    }
 }
 
 impl Prompt for str {
@@ -0,0 +1,192 @@
+//! Serve an [`Editor`] on any terminal file descriptors (like the slave side
+//! of a PTY) instead of the process stdin / stdout.
+//!
+//! Each editor has its own raw mode and resize notification, no signal
+//! handler is installed, so several sessions can run concurrently in one
+//! process:
+//! ```no_run
+//! use rustyline::Config;
+//! use rustyline::pty::PtyEditor;
+//!
+//! # fn session(slave: std::os::fd::OwnedFd) -> rustyline::Result<()> {
+//! let output = slave.try_clone()?;
+//! let mut rl = PtyEditor::<()>::with_fds(Config::default(), slave, output)?;
+//! let resize = rl.resize_notifier();
+//! // on window change: `TIOCSWINSZ` on the master side then `resize.notify()`
+//! let line = rl.readline("> ")?;
+//! # Ok(())
+//! # }
+//! ```
+use std::os::fd::OwnedFd;
+
+use crate::config::Config;
+use crate::history::{DefaultHistory, History};
+pub use crate::tty::unix::{PtyTerminal, ResizeNotifier};
+use crate::{Editor, Helper, Result};
+
+/// Editor bound to file descriptors
+pub type PtyEditor<H, I = DefaultHistory> = Editor<H, I, PtyTerminal>;
+
+impl<H: Helper> Editor<H, DefaultHistory, PtyTerminal> {
+    /// Create an editor reading keys from `input` and writing to `output`.
+    pub fn with_fds(config: Config, input: OwnedFd, output: OwnedFd) -> Result<Self> {
+        let history = DefaultHistory::with_config(&config);
+        Self::with_fds_and_history(config, history, input, output)
+    }
+}
+
+impl<H: Helper, I: History> Editor<H, I, PtyTerminal> {
+    /// Create an editor bound to file descriptors with a custom history impl.
+    pub fn with_fds_and_history(
+        config: Config,
+        history: I,
+        input: OwnedFd,
+        output: OwnedFd,
+    ) -> Result<Self> {
+        Ok(Self::with_term(
+            config,
+            history,
+            PtyTerminal::new(input, output)?,
+        ))
+    }
+
+    /// Notify this editor that its window size has changed.
+    #[must_use]
+    pub fn resize_notifier(&self) -> ResizeNotifier {
+        self.term.resize_notifier()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+    use std::io::{Read as _, Write as _};
+    use std::os::fd::{AsRawFd as _, OwnedFd};
+    use std::sync::{Arc, Mutex};
+    use std::thread::{self, JoinHandle};
+    use std::time::{Duration, Instant};
+
+    use nix::pty::{OpenptyResult, Winsize, openpty};
+    use nix::sys::termios::{self, SetArg};
+
+    use super::PtyEditor;
+    use crate::config::Config;
+    use crate::testing::Screen;
+
+    /// Master output drained in background
+    struct Output {
+        text: Arc<Mutex<Vec<u8>>>,
+        reader: JoinHandle<()>,
+    }
+
+    impl Output {
+        /// Wait until `s` has been written by the editor after the first
+        /// `from` bytes, returns the number of bytes written so far
+        fn wait_for(&self, s: &str, from: usize) -> usize {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            loop {
+                let text = self.text.lock().unwrap();
+                if String::from_utf8_lossy(&text[from..]).contains(s) {
+                    return text.len();
+                }
+                drop(text);
+                assert!(Instant::now() < deadline, "timeout waiting for {s:?}");
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        /// Whole output, once the slave is closed
+        fn join(self) -> String {
+            self.reader.join().unwrap();
+            String::from_utf8_lossy(&self.text.lock().unwrap()).into_owned()
+        }
+    }
+
+    nix::ioctl_write_ptr_bad!(set_win_size, libc::TIOCSWINSZ, Winsize);
+
+    fn win_size(cols: u16) -> Winsize {
+        Winsize {
+            ws_row: 24,
+            ws_col: cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        }
+    }
+
+    /// Returns master input, master output and slave
+    fn pty(cols: u16) -> (File, Output, OwnedFd) {
+        let OpenptyResult { master, slave } = openpty(Some(&win_size(cols)), None).unwrap();
+        // so that keys written before the editor is ready are not altered
+        let mut termios = termios::tcgetattr(&slave).unwrap();
+        termios::cfmakeraw(&mut termios);
+        termios::tcsetattr(&slave, SetArg::TCSANOW, &termios).unwrap();
+        let master = File::from(master);
+        let mut input = master.try_clone().unwrap();
+        let text = Arc::new(Mutex::new(Vec::new()));
+        let reader = {
+            let text = text.clone();
+            thread::spawn(move || {
+                let mut buf = [0; 1024];
+                // EIO once the slave is closed
+                while let Ok(n @ 1..) = input.read(&mut buf) {
+                    text.lock().unwrap().extend_from_slice(&buf[..n]);
+                }
+            })
+        };
+        (master, Output { text, reader }, slave)
+    }
+
+    fn screen(output: &str) -> String {
+        let mut screen = Screen::new(80, 24);
+        screen.print(output);
+        screen.to_string()
+    }
+
+    fn session(slave: OwnedFd) -> PtyEditor<()> {
+        let output = slave.try_clone().unwrap();
+        PtyEditor::with_fds(Config::default(), slave, output).unwrap()
+    }
+
+    #[test]
+    fn concurrent_sessions() {
+        let sessions: Vec<_> = ["first", "second"]
+            .into_iter()
+            .map(|text| {
+                let (mut master, output, slave) = pty(80);
+                let rl = thread::spawn(move || session(slave).readline("> "));
+                master.write_all(text.as_bytes()).unwrap();
+                master.write_all(b"\r").unwrap();
+                (text, master, output, rl)
+            })
+            .collect();
+        for (text, master, output, rl) in sessions {
+            assert_eq!(text, rl.join().unwrap().unwrap());
+            drop(master);
+            assert_eq!(format!("> {text}"), screen(&output.join()));
+        }
+    }
+
+    #[test]
+    fn resize() {
+        let (mut master, output, slave) = pty(80);
+        let mut rl = session(slave);
+        let notifier = rl.resize_notifier();
+        let rl = thread::spawn(move || rl.readline("> "));
+        master.write_all(b"0123456789").unwrap();
+        let before = output.wait_for("0123456789", 0);
+        // resize while editing
+        unsafe { set_win_size(master.as_raw_fd(), &win_size(8)) }.unwrap();
+        notifier.notify().unwrap();
+        // the line is redrawn at the new width
+        output.wait_for("0123456789", before);
+        master.write_all(b"\r").unwrap();
+        assert_eq!("0123456789", rl.join().unwrap().unwrap());
+        drop(master);
+        let text = output.join();
+        let mut screen = Screen::new(80, 24);
+        screen.print(&text[..before]);
+        screen.resize(8, 24);
+        screen.print(&text[before..]);
+        assert_eq!("> 012345\n6789", screen.to_string());
+    }
+}
@@ -0,0 +1,382 @@
+//! Import / export history from / to shell history files, with any
+//! [`History`] backend.
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io::BufReader;
+//!
+//! use rustyline::history::{DefaultHistory, History};
+//! use rustyline::shell_history::{self, Format};
+//!
+//! let mut history = DefaultHistory::new();
+//! let bash_history = File::open("/home/user/.bash_history")?;
+//! shell_history::import(&mut history, Format::Bash, BufReader::new(bash_history))?;
+//! let fish_history = File::create("fish_history")?;
+//! shell_history::export(&history, Format::Fish, fish_history)?;
+//! # Ok::<(), rustyline::error::ReadlineError>(())
+//! ```
+use std::io::{BufRead, BufWriter, Write};
+use std::time::{Duration, SystemTime};
+
+use crate::Result;
+use crate::history::{History, SearchDirection, SearchResult};
+
+/// Shell history file format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Format {
+    /// bash `~/.bash_history`: one entry per line, preceded by a
+    /// `#<timestamp>` line when `HISTTIMEFORMAT` is set (then an entry spans
+    /// all lines up to the next timestamp).
+    Bash,
+    /// zsh `~/.zsh_history`: one entry per line, prefixed by
+    /// `: <timestamp>:<duration>;` with `EXTENDED_HISTORY`. Line feeds are
+    /// escaped by a backslash.
+    Zsh,
+    /// fish `fish_history`: `- cmd: <entry>` followed by `  when: <timestamp>`
+    Fish,
+}
+
+/// Add the entries read from `rdr` (in `format`) to `history`.
+///
+/// Return the number of entries added (blank lines / duplicates / ... may be
+/// ignored by `history`).
+pub fn import<H: History + ?Sized, R: BufRead>(
+    history: &mut H,
+    format: Format,
+    mut rdr: R,
+) -> Result<usize> {
+    let mut importer = Importer {
+        history,
+        count: 0,
+        entry: None,
+        timestamp: None,
+    };
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if rdr.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        if buf.last() == Some(&b'\n') {
+            buf.pop();
+        }
+        match format {
+            Format::Bash => importer.bash(String::from_utf8_lossy(&buf).into_owned())?,
+            Format::Zsh => importer.zsh(String::from_utf8_lossy(&unmetafy(&buf)).into_owned())?,
+            Format::Fish => importer.fish(&String::from_utf8_lossy(&buf))?,
+        }
+    }
+    importer.flush()?;
+    Ok(importer.count)
+}
+
+/// Write all the entries of `history` to `wtr` in `format`.
+///
+/// Timestamps are written only when known by `history` (see
+/// [`History::timestamp`]), except for multi-line entries in `Bash` format
+/// which are preceded by `#0` so that they are imported as a single entry.
+pub fn export<H: History + ?Sized, W: Write>(history: &H, format: Format, wtr: W) -> Result<()> {
+    let mut wtr = BufWriter::new(wtr);
+    let mut index = 0;
+    while let Some(SearchResult { entry, idx, .. }) =
+        history.get(index, SearchDirection::Forward)?
+    {
+        let timestamp = history.timestamp(idx)?.and_then(secs);
+        match format {
+            Format::Bash => {
+                if let Some(ts) = timestamp {
+                    writeln!(wtr, "#{ts}")?;
+                } else if entry.contains('\n') {
+                    writeln!(wtr, "#0")?;
+                }
+                writeln!(wtr, "{entry}")?;
+            }
+            Format::Zsh => {
+                if let Some(ts) = timestamp {
+                    write!(wtr, ": {ts}:0;")?;
+                }
+                wtr.write_all(&metafy(&entry.replace('\n', "\\\n")))?;
+                wtr.write_all(b"\n")?;
+            }
+            Format::Fish => {
+                let entry = entry.replace('\\', "\\\\").replace('\n', "\\n");
+                writeln!(wtr, "- cmd: {entry}")?;
+                if let Some(ts) = timestamp {
+                    writeln!(wtr, "  when: {ts}")?;
+                }
+            }
+        }
+        index = idx + 1;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Entries being imported
+struct Importer<'h, H: ?Sized> {
+    history: &'h mut H,
+    count: usize,
+    /// Current entry (may span multiple lines)
+    entry: Option<String>,
+    timestamp: Option<SystemTime>,
+}
+
+impl<H: History + ?Sized> Importer<'_, H> {
+    /// Add the current entry to the history
+    fn flush(&mut self) -> Result<()> {
+        let Some(entry) = self.entry.take() else {
+            return Ok(());
+        };
+        let added = match self.timestamp {
+            // `#0` stands for an unknown timestamp (see `export`)
+            Some(timestamp) if timestamp != SystemTime::UNIX_EPOCH => {
+                self.history.add_with_timestamp(entry, timestamp)?
+            }
+            _ => self.history.add_owned(entry)?,
+        };
+        if added {
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    fn bash(&mut self, line: String) -> Result<()> {
+        if let Some(ts) = line.strip_prefix('#').and_then(parse_secs) {
+            self.flush()?;
+            self.timestamp = Some(ts);
+        } else if let Some(entry) = self.entry.as_mut().filter(|_| self.timestamp.is_some()) {
+            // multi-line entry
+            entry.push('\n');
+            entry.push_str(&line);
+        } else {
+            self.flush()?;
+            self.entry = Some(line);
+        }
+        Ok(())
+    }
+
+    fn zsh(&mut self, line: String) -> Result<()> {
+        let mut line = if let Some(entry) = self.entry.as_mut() {
+            // continuation of an entry ending with a backslash
+            entry.push('\n');
+            entry.push_str(&line);
+            self.entry.take().unwrap()
+        } else {
+            self.timestamp = None;
+            match zsh_extended(&line) {
+                Some((ts, entry)) => {
+                    self.timestamp = Some(ts);
+                    entry.to_owned()
+                }
+                None => line,
+            }
+        };
+        if line.ends_with('\\') {
+            line.pop();
+            self.entry = Some(line);
+            Ok(())
+        } else {
+            self.entry = Some(line);
+            self.flush()
+        }
+    }
+
+    fn fish(&mut self, line: &str) -> Result<()> {
+        if let Some(cmd) = line.strip_prefix("- cmd: ") {
+            self.flush()?;
+            self.timestamp = None;
+            self.entry = Some(unescape_fish(cmd));
+        } else if let Some(ts) = line.strip_prefix("  when: ") {
+            self.timestamp = parse_secs(ts);
+        } // else paths
+        Ok(())
+    }
+}
+
+/// `: <timestamp>:<duration>;<entry>`
+fn zsh_extended(line: &str) -> Option<(SystemTime, &str)> {
+    let header = line.strip_prefix(": ")?;
+    let (ts, tail) = header.split_once(':')?;
+    let (duration, entry) = tail.split_once(';')?;
+    if duration.is_empty() || !duration.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((parse_secs(ts)?, entry))
+}
+
+/// Seconds since the Unix epoch
+fn parse_secs(s: &str) -> Option<SystemTime> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(s.parse().ok()?))
+}
+
+/// Seconds (rounded) since the Unix epoch
+fn secs(timestamp: SystemTime) -> Option<u64> {
+    let d = timestamp.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+    Some((d + Duration::from_millis(500)).as_secs())
+}
+
+fn unescape_fish(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some('\\') => unescaped.push('\\'),
+                Some(c) => {
+                    unescaped.push('\\');
+                    unescaped.push(c);
+                }
+                None => unescaped.push('\\'),
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
+/// zsh escapes some bytes (its internal tokens) in history files
+const META: u8 = 0x83;
+
+fn is_meta(b: u8) -> bool {
+    b == 0 || (META..=0xa2).contains(&b)
+}
+
+fn metafy(s: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(s.len());
+    for &b in s.as_bytes() {
+        if is_meta(b) {
+            bytes.push(META);
+            bytes.push(b ^ 32);
+        } else {
+            bytes.push(b);
+        }
+    }
+    bytes
+}
+
+fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+    let mut unmetafied = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter();
+    while let Some(&b) = iter.next() {
+        if b == META {
+            if let Some(&b) = iter.next() {
+                unmetafied.push(b ^ 32);
+            }
+        } else {
+            unmetafied.push(b);
+        }
+    }
+    unmetafied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, export, import};
+    use crate::Result;
+    use crate::history::MemHistory;
+
+    fn entries(history: &MemHistory) -> Vec<&str> {
+        history.into_iter().map(String::as_str).collect()
+    }
+
+    fn round_trip(format: Format, text: &str) -> Result<()> {
+        let mut history = MemHistory::new();
+        assert_eq!(3, import(&mut history, format, text.as_bytes())?);
+        assert_eq!(
+            vec!["ls -l", "for i in 1 2\ndo echo $i\ndone", "échö"],
+            entries(&history)
+        );
+        let mut exported = Vec::new();
+        export(&history, format, &mut exported)?;
+        let mut copy = MemHistory::new();
+        import(&mut copy, format, exported.as_slice())?;
+        assert_eq!(entries(&history), entries(&copy));
+        Ok(())
+    }
+
+    #[test]
+    fn bash() -> Result<()> {
+        round_trip(
+            Format::Bash,
+            "#1700000000\nls -l\n#1700000001\nfor i in 1 2\ndo echo $i\ndone\n#1700000002\néchö\n",
+        )?;
+        let mut history = MemHistory::new();
+        assert_eq!(
+            2,
+            import(&mut history, Format::Bash, "ls\n#comment\n".as_bytes())?
+        );
+        assert_eq!(vec!["ls", "#comment"], entries(&history));
+        Ok(())
+    }
+
+    #[test]
+    fn zsh() -> Result<()> {
+        round_trip(
+            Format::Zsh,
+            ": 1700000000:0;ls -l\n: 1700000001:5;for i in 1 2\\\ndo echo \
+             $i\\\ndone\n\u{e9}ch\u{f6}\n",
+        )?;
+        // metafied
+        let mut history = MemHistory::new();
+        import(&mut history, Format::Zsh, b"\xc3\x83\xa3".as_slice())?;
+        assert_eq!(vec!["\u{c3}"], entries(&history));
+        Ok(())
+    }
+
+    #[test]
+    fn fish() -> Result<()> {
+        round_trip(
+            Format::Fish,
+            "- cmd: ls -l\n  when: 1700000000\n  paths:\n    - -l\n- cmd: for i in 1 2\\ndo echo \
+             $i\\ndone\n  when: 1700000001\n- cmd: échö\n  when: 1700000002\n",
+        )
+    }
+
+    #[cfg(feature = "with-file-history")]
+    #[test]
+    fn bash_without_timestamps() -> Result<()> {
+        use std::io::Write as _;
+
+        use crate::history::{FileHistory, History as _};
+
+        let tf = tempfile::NamedTempFile::new()?;
+        std::fs::File::create(tf.path())?.write_all(b"#V2\nls -l\nfor i in 1 2\\ndone\n")?;
+        let mut history = FileHistory::new();
+        history.load(tf.path())?;
+        let mut exported = Vec::new();
+        export(&history, Format::Bash, &mut exported)?;
+        assert_eq!(b"ls -l\n#0\nfor i in 1 2\ndone\n", exported.as_slice());
+        let mut copy = MemHistory::new();
+        assert_eq!(2, import(&mut copy, Format::Bash, exported.as_slice())?);
+        assert_eq!(vec!["ls -l", "for i in 1 2\ndone"], entries(&copy));
+        assert_ne!(Some(std::time::SystemTime::UNIX_EPOCH), copy.timestamp(1)?);
+        Ok(())
+    }
+
+    #[cfg(feature = "with-sqlite-history")]
+    #[test]
+    fn timestamps() -> Result<()> {
+        use crate::history::History as _;
+        use crate::sqlite_history::SQLiteHistory;
+
+        let mut history = SQLiteHistory::with_config(&crate::Config::default())?;
+        let text = ": 1700000000:0;ls -l\ncd\n";
+        assert_eq!(2, import(&mut history, Format::Zsh, text.as_bytes())?);
+        assert_eq!(
+            Some(1_700_000_000),
+            history.timestamp(0)?.and_then(super::secs)
+        );
+        let mut exported = Vec::new();
+        export(&history, Format::Bash, &mut exported)?;
+        let exported = String::from_utf8(exported).unwrap();
+        assert!(exported.starts_with("#1700000000\nls -l\n#"), "{exported}");
+        assert!(exported.ends_with("\ncd\n"), "{exported}");
+        Ok(())
+    }
+}
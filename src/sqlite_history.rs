@@ -2,22 +2,41 @@
 use std::borrow::Cow;
 use std::cell::Cell;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 use rusqlite::{Connection, OptionalExtension as _};
 
-use crate::history::SearchResult;
-use crate::{Config, History, HistoryDuplicates, ReadlineError, Result, SearchDirection};
+use crate::history::{
+    FilterResult, HistoryFilter, Matcher, SearchMode, SearchResult, find_ignore_case, matcher,
+};
+use crate::{
+    Config, History, HistoryDuplicates, HistoryRanking, ReadlineError, Result, SearchDirection,
+};
 
 /// History stored in an SQLite database.
+///
+/// Session-only entries (see [`FilterResult::SessionOnly`]) are kept in memory
+/// so that they never reach the database, where other sessions could read
+/// them: they can be navigated and searched but are not ranked.
 pub struct SQLiteHistory {
     max_len: usize,
+    max_entry_len: Option<usize>,
+    max_bytes: Option<usize>,
     ignore_space: bool,
-    ignore_dups: bool,
+    duplicates: HistoryDuplicates,
     path: Option<PathBuf>, // None => memory
     conn: Connection,      /* we need to keep a connection opened at least for in memory
                             * database and also for cached statement(s) */
     session_id: usize,   // 0 means no new entry added
     row_id: Cell<usize>, // max entry id
+    filter: Option<Box<dyn HistoryFilter>>,
+    /// Context of new entries
+    context: Option<String>,
+    /// Context of returned entries
+    context_filter: Option<String>,
+    /// Session-only entries (rowid, entry, context), never written to the
+    /// database
+    session_only: Vec<(usize, String, Option<String>)>,
 }
 
 /*
@@ -45,17 +64,65 @@ impl SQLiteHistory {
         Self::new(config, normalize(path.as_ref()))
     }
 
+    /// Most frequently used entries (with their number of uses), at most
+    /// `limit`
+    pub fn most_used(&self, limit: usize) -> Result<Vec<(String, usize)>> {
+        self.statistics(
+            "SELECT entry, count(1) FROM history WHERE ?1 IS NULL OR context = ?1 GROUP BY entry \
+             ORDER BY count(1) DESC, max(rowid) DESC LIMIT ?2;",
+            limit,
+        )
+    }
+
+    /// Most recently used distinct entries, at most `limit`
+    pub fn most_recent_unique(&self, limit: usize) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT entry FROM history WHERE ?1 IS NULL OR context = ?1 GROUP BY entry ORDER BY \
+             max(rowid) DESC LIMIT ?2;",
+        )?;
+        let rows = stmt.query_map((self.context_filter.as_deref(), limit), |r| r.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Entries with the best frecency score (with their score), at most
+    /// `limit`: each use scores from 100 (less than 4 days ago) down to 10
+    /// (more than 90 days ago).
+    pub fn frecency(&self, limit: usize) -> Result<Vec<(String, usize)>> {
+        self.statistics(
+            &format!(
+                "SELECT entry, {FRECENCY} FROM history WHERE ?1 IS NULL OR context = ?1 GROUP BY \
+                 entry ORDER BY {FRECENCY} DESC, max(rowid) DESC LIMIT ?2;"
+            ),
+            limit,
+        )
+    }
+
+    /// Entries with their score given by `query`
+    fn statistics(&self, query: &str, limit: usize) -> Result<Vec<(String, usize)>> {
+        let mut stmt = self.conn.prepare_cached(query)?;
+        let rows = stmt.query_map((self.context_filter.as_deref(), limit), |r| {
+            Ok((r.get(0)?, r.get(1)?))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
     fn new(config: &Config, path: Option<PathBuf>) -> Result<Self> {
         let conn = conn(path.as_ref())?;
         let mut sh = Self {
             max_len: config.max_history_size(),
+            max_entry_len: config.max_history_entry_len(),
+            max_bytes: config.max_history_bytes(),
             ignore_space: config.history_ignore_space(),
             // not strictly consecutive...
-            ignore_dups: config.history_duplicates() == HistoryDuplicates::IgnoreConsecutive,
+            duplicates: config.history_duplicates(),
             path,
             conn,
             session_id: 0,
             row_id: Cell::new(0),
+            filter: None,
+            context: None,
+            context_filter: None,
+            session_only: Vec::new(),
         };
         sh.check_schema()?;
         Ok(sh)
@@ -72,18 +139,24 @@ impl SQLiteHistory {
         self.path = normalize(path);
         self.session_id = 0;
         self.row_id.set(0);
+        self.session_only.clear();
         Ok(std::mem::replace(&mut self.conn, conn(self.path.as_ref())?))
     }
 
     fn update_row_id(&mut self) -> Result<()> {
         self.row_id.set(self.conn.query_row(
-            "SELECT ifnull(max(rowid), 0) FROM history;",
-            [],
+            "SELECT max(ifnull(max(rowid), 0), ?1) FROM history;",
+            [self.last_session_only()],
             |r| r.get(0),
         )?);
         Ok(())
     }
 
+    /// Rowid of the last session-only entry (0 if none)
+    fn last_session_only(&self) -> usize {
+        self.session_only.last().map_or(0, |(rowid, ..)| *rowid)
+    }
+
     fn check_schema(&mut self) -> Result<()> {
         let user_version: i32 = self
             .conn
@@ -118,12 +191,23 @@ CREATE TRIGGER history_ai AFTER INSERT ON history BEGIN
     INSERT INTO fts (docid, entry) VALUES(new.rowid, new.entry);
 END;
 PRAGMA user_version = 1;
+COMMIT;
+                 ",
+            )?;
+        }
+        if user_version < 2 {
+            self.conn.execute_batch(
+                "
+BEGIN EXCLUSIVE;
+ALTER TABLE history ADD COLUMN context TEXT;
+CREATE INDEX context ON history(context);
+PRAGMA user_version = 2;
 COMMIT;
                  ",
             )?;
         }
         self.conn.pragma_update(None, "foreign_keys", 1)?;
-        if self.ignore_dups || user_version > 0 {
+        if self.duplicates != HistoryDuplicates::AlwaysAdd || user_version > 0 {
             self.set_ignore_dups()?;
         }
         if self.row_id.get() == 0 && user_version > 0 {
@@ -133,14 +217,19 @@ COMMIT;
     }
 
     fn set_ignore_dups(&mut self) -> Result<()> {
-        if self.ignore_dups {
-            // TODO Validate: ignore dups only in the same session_id ?
-            self.conn.execute_batch(
-                "CREATE UNIQUE INDEX IF NOT EXISTS ignore_dups ON history(entry, session_id);",
-            )?;
-        } else {
-            self.conn
-                .execute_batch("DROP INDEX IF EXISTS ignore_dups;")?;
+        match self.duplicates {
+            HistoryDuplicates::AlwaysAdd => {
+                self.conn
+                    .execute_batch("DROP INDEX IF EXISTS ignore_dups;")?;
+            }
+            HistoryDuplicates::IgnoreConsecutive => {
+                // TODO Validate: ignore dups only in the same session_id ?
+                self.conn.execute_batch(
+                    "CREATE UNIQUE INDEX IF NOT EXISTS ignore_dups ON history(entry, session_id);",
+                )?;
+            }
+            // see `add_entry`: older entries are deleted on insert
+            HistoryDuplicates::EraseDups => {}
         }
         Ok(())
     }
@@ -166,26 +255,168 @@ COMMIT;
         {
             return true;
         }
-        // ignore_dups => SQLITE_CONSTRAINT_UNIQUE
+        if self.max_entry_len.is_some_and(|max| line.len() > max)
+            || self.max_bytes.is_some_and(|max| line.len() > max)
+        {
+            return true;
+        }
+        // ignore_dups => SQLITE_CONSTRAINT_UNIQUE, erase_dups => see `add_entry`
         false
     }
 
-    fn add_entry(&mut self, line: &str) -> Result<bool> {
+    fn add_entry(&mut self, line: &str, timestamp: Option<f64>) -> Result<bool> {
+        if self.duplicates == HistoryDuplicates::EraseDups {
+            // like bash `erasedups`, including entries of other sessions
+            self.conn
+                .prepare_cached("DELETE FROM history WHERE entry = ?1;")?
+                .execute([line])?;
+            self.session_only.retain(|(_, entry, _)| entry != line);
+        }
         // ignore SQLITE_CONSTRAINT_UNIQUE
+        // rowids of session-only entries are skipped
         let mut stmt = self.conn.prepare_cached(
-            "INSERT OR REPLACE INTO history (session_id, entry) VALUES (?1, ?2) RETURNING rowid;",
+            "INSERT OR REPLACE INTO history (rowid, session_id, entry, timestamp, context) VALUES \
+             ((SELECT max(ifnull(max(rowid), 0), ?5) + 1 FROM history), ?1, ?2, ifnull(?3, \
+             julianday('now')), ?4) RETURNING rowid;",
         )?;
         if let Some(row_id) = stmt
-            .query_row((self.session_id, line), |r| r.get(0))
+            .query_row(
+                (
+                    self.session_id,
+                    line,
+                    timestamp,
+                    self.context.as_deref(),
+                    self.last_session_only(),
+                ),
+                |r| r.get(0),
+            )
             .optional()?
         {
             self.row_id.set(row_id);
+            if let Some(max) = self.max_bytes {
+                // remove oldest entries to make room for the new one
+                self.conn
+                    .prepare_cached(
+                        "DELETE FROM history WHERE rowid IN (SELECT rowid FROM (SELECT rowid, \
+                         sum(length(CAST(entry AS BLOB))) OVER (ORDER BY rowid DESC) AS bytes \
+                         FROM history) WHERE bytes > ?1);",
+                    )?
+                    .execute([max])?;
+            }
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    /// Add a new (filtered) entry
+    fn add_new(&mut self, line: &str, timestamp: Option<f64>) -> Result<bool> {
+        let filtered = self.filter.as_ref().map(|filter| filter.filter(line));
+        let line = match filtered {
+            None | Some(FilterResult::Keep) => Cow::Borrowed(line),
+            Some(FilterResult::Reject) => return Ok(false),
+            Some(FilterResult::SessionOnly) => return self.add_session_only(line),
+            Some(FilterResult::Rewrite(line)) => Cow::Owned(line),
+        };
+        if self.ignore(&line) {
+            return Ok(false);
+        }
+        // Do not create a session until the first entry is added.
+        self.create_session()?;
+        self.add_entry(&line, timestamp)
+    }
+
+    /// Add an entry visible only to this session
+    fn add_session_only(&mut self, line: &str) -> Result<bool> {
+        if self.ignore(line) {
+            return Ok(false);
+        }
+        if self.duplicates != HistoryDuplicates::AlwaysAdd
+            && self.len() > 0
+            && self
+                .get(self.len() - 1, SearchDirection::Reverse)?
+                .is_some_and(|r| r.entry == line)
+        {
+            return Ok(false);
+        }
+        // other sessions may only see the database
+        self.update_row_id()?;
+        let rowid = self.row_id.get() + 1;
+        self.session_only
+            .push((rowid, line.to_owned(), self.context.clone()));
+        self.row_id.set(rowid);
+        Ok(true)
+    }
+
+    /// Session-only entry nearest to `start` in `dir` direction matching
+    /// `test`
+    fn search_session_only<'s, F>(
+        &'s self,
+        start: usize,
+        dir: SearchDirection,
+        test: F,
+    ) -> Option<SearchResult<'s>>
+    where
+        F: Fn(&str) -> Option<usize>,
+    {
+        let rowid = start + 1; // first rowid is 1
+        let matches = |(id, entry, context): &'s (usize, String, Option<String>)| {
+            if self
+                .context_filter
+                .as_ref()
+                .is_some_and(|filter| context.as_ref() != Some(filter))
+            {
+                return None;
+            }
+            test(entry).map(|pos| SearchResult {
+                entry: Cow::Borrowed(entry.as_str()),
+                idx: id - 1,
+                pos,
+            })
+        };
+        match dir {
+            SearchDirection::Forward => self
+                .session_only
+                .iter()
+                .filter(|(id, ..)| *id >= rowid)
+                .find_map(matches),
+            SearchDirection::Reverse => self
+                .session_only
+                .iter()
+                .rev()
+                .filter(|(id, ..)| *id <= rowid)
+                .find_map(matches),
+        }
+    }
+
+    /// Merge `found` in the database with the session-only entries
+    fn with_session_only<'s, F>(
+        &'s self,
+        found: Option<SearchResult<'s>>,
+        start: usize,
+        dir: SearchDirection,
+        test: F,
+    ) -> Option<SearchResult<'s>>
+    where
+        F: Fn(&str) -> Option<usize>,
+    {
+        let Some(other) = self.search_session_only(start, dir, test) else {
+            return found;
+        };
+        match found {
+            // another session may have reused the rowid of a session-only entry
+            Some(found)
+                if match dir {
+                    SearchDirection::Forward => found.idx <= other.idx,
+                    SearchDirection::Reverse => found.idx >= other.idx,
+                } =>
+            {
+                Some(found)
+            }
+            _ => Some(other),
+        }
+    }
+
     fn search_match(
         &self,
         term: &str,
@@ -200,23 +431,27 @@ COMMIT;
         let query = match (dir, start_with) {
             (SearchDirection::Forward, true) => {
                 "SELECT docid, entry FROM fts WHERE entry MATCH '^' || ?1 || '*'  AND docid >= ?2 \
-                 ORDER BY docid ASC LIMIT 1;"
+                 AND (?3 IS NULL OR docid IN (SELECT rowid FROM history WHERE context = ?3)) ORDER \
+                 BY docid ASC LIMIT 1;"
             }
             (SearchDirection::Forward, false) => {
                 "SELECT docid, entry, offsets(fts) FROM fts WHERE entry MATCH ?1 || '*'  AND docid \
-                 >= ?2 ORDER BY docid ASC LIMIT 1;"
+                 >= ?2 AND (?3 IS NULL OR docid IN (SELECT rowid FROM history WHERE context = ?3)) \
+                 ORDER BY docid ASC LIMIT 1;"
             }
             (SearchDirection::Reverse, true) => {
                 "SELECT docid, entry FROM fts WHERE entry MATCH '^' || ?1 || '*'  AND docid <= ?2 \
-                 ORDER BY docid DESC LIMIT 1;"
+                 AND (?3 IS NULL OR docid IN (SELECT rowid FROM history WHERE context = ?3)) ORDER \
+                 BY docid DESC LIMIT 1;"
             }
             (SearchDirection::Reverse, false) => {
                 "SELECT docid, entry, offsets(fts) FROM fts WHERE entry MATCH ?1 || '*'  AND docid \
-                 <= ?2 ORDER BY docid DESC LIMIT 1;"
+                 <= ?2 AND (?3 IS NULL OR docid IN (SELECT rowid FROM history WHERE context = ?3)) \
+                 ORDER BY docid DESC LIMIT 1;"
             }
         };
         let mut stmt = self.conn.prepare_cached(query)?;
-        stmt.query_row((term, start), |r| {
+        stmt.query_row((term, start, self.context_filter.as_deref()), |r| {
             let rowid = r.get::<_, usize>(0)?;
             if rowid > self.row_id.get() {
                 self.row_id.set(rowid);
@@ -232,7 +467,59 @@ COMMIT;
             })
         })
         .optional()
-        .map_err(ReadlineError::from)
+        .or_else(|err| {
+            if is_malformed_match(&err) {
+                Ok(None)
+            } else {
+                Err(ReadlineError::from(err))
+            }
+        })
+    }
+
+    /// Scan entries matching the SQL `condition` (with the search term as
+    /// `?3`) until `test` gives the match position.
+    fn search_scan<F>(
+        &self,
+        term: &str,
+        start: usize,
+        dir: SearchDirection,
+        condition: &str,
+        test: F,
+    ) -> Result<Option<SearchResult<'_>>>
+    where
+        F: Fn(&str) -> Option<usize>,
+    {
+        if term.is_empty() || start >= self.len() {
+            return Ok(None);
+        }
+        let start = start + 1; // first rowid is 1
+        let query = match dir {
+            SearchDirection::Forward => format!(
+                "SELECT rowid, entry FROM history WHERE rowid >= ?1 AND (?2 IS NULL OR context = \
+                 ?2) AND {condition} ORDER BY rowid ASC;"
+            ),
+            SearchDirection::Reverse => format!(
+                "SELECT rowid, entry FROM history WHERE rowid <= ?1 AND (?2 IS NULL OR context = \
+                 ?2) AND {condition} ORDER BY rowid DESC;"
+            ),
+        };
+        let mut stmt = self.conn.prepare_cached(&query)?;
+        let mut rows = stmt.query((start, self.context_filter.as_deref(), term))?;
+        while let Some(row) = rows.next()? {
+            let entry: String = row.get(1)?;
+            if let Some(pos) = test(&entry) {
+                let rowid = row.get::<_, usize>(0)?;
+                if rowid > self.row_id.get() {
+                    self.row_id.set(rowid);
+                }
+                return Ok(Some(SearchResult {
+                    entry: Cow::Owned(entry),
+                    idx: rowid - 1,
+                    pos,
+                }));
+            }
+        }
+        Ok(None)
     }
 }
 
@@ -246,41 +533,67 @@ impl History for SQLiteHistory {
         // rowid may not be sequential
         let query = match dir {
             SearchDirection::Forward => {
-                "SELECT rowid, entry FROM history WHERE rowid >= ?1 ORDER BY rowid ASC LIMIT 1;"
+                "SELECT rowid, entry FROM history WHERE rowid >= ?1 AND (?2 IS NULL OR context = \
+                 ?2) ORDER BY rowid ASC LIMIT 1;"
             }
             SearchDirection::Reverse => {
-                "SELECT rowid, entry FROM history WHERE rowid <= ?1 ORDER BY rowid DESC LIMIT 1;"
+                "SELECT rowid, entry FROM history WHERE rowid <= ?1 AND (?2 IS NULL OR context = \
+                 ?2) ORDER BY rowid DESC LIMIT 1;"
             }
         };
         let mut stmt = self.conn.prepare_cached(query)?;
-        stmt.query_row([rowid], |r| {
-            let rowid = r.get::<_, usize>(0)?;
-            if rowid > self.row_id.get() {
-                self.row_id.set(rowid);
-            }
-            Ok(SearchResult {
-                entry: Cow::Owned(r.get(1)?),
-                idx: rowid - 1,
-                pos: 0,
+        let found = stmt
+            .query_row((rowid, self.context_filter.as_deref()), |r| {
+                let rowid = r.get::<_, usize>(0)?;
+                if rowid > self.row_id.get() {
+                    self.row_id.set(rowid);
+                }
+                Ok(SearchResult {
+                    entry: Cow::Owned(r.get(1)?),
+                    idx: rowid - 1,
+                    pos: 0,
+                })
             })
-        })
-        .optional()
-        .map_err(ReadlineError::from)
+            .optional()?;
+        Ok(self.with_session_only(found, index, dir, |_| Some(0)))
     }
 
     fn add(&mut self, line: &str) -> Result<bool> {
-        if self.ignore(line) {
-            return Ok(false);
-        }
-        // Do not create a session until the first entry is added.
-        self.create_session()?;
-        self.add_entry(line)
+        self.add_new(line, None)
     }
 
     fn add_owned(&mut self, line: String) -> Result<bool> {
         self.add(line.as_str())
     }
 
+    fn add_with_timestamp(&mut self, line: String, timestamp: SystemTime) -> Result<bool> {
+        let secs = match timestamp.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(d) => d.as_secs_f64(),
+            Err(e) => -e.duration().as_secs_f64(),
+        };
+        self.add_new(&line, Some(secs / SECS_PER_DAY + UNIX_EPOCH_JULIAN_DAY))
+    }
+
+    fn timestamp(&self, index: usize) -> Result<Option<SystemTime>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT timestamp FROM history WHERE rowid = ?1;")?;
+        let Some(julian_day) = stmt
+            .query_row([index + 1], |r| r.get::<_, f64>(0))
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        let secs = (julian_day - UNIX_EPOCH_JULIAN_DAY) * SECS_PER_DAY;
+        Ok(Duration::try_from_secs_f64(secs.abs()).ok().and_then(|d| {
+            if secs >= 0. {
+                SystemTime::UNIX_EPOCH.checked_add(d)
+            } else {
+                SystemTime::UNIX_EPOCH.checked_sub(d)
+            }
+        }))
+    }
+
     /// This is not really the length
     fn len(&self) -> usize {
         self.row_id.get()
@@ -292,24 +605,22 @@ impl History for SQLiteHistory {
 
     fn set_max_len(&mut self, len: usize) -> Result<()> {
         // TODO call this method on save ? before append ?
-        // FIXME rowid may not be sequential
-        let count: usize = self
-            .conn
-            .query_row("SELECT count(1) FROM history;", [], |r| r.get(0))?;
-        if count > len {
-            self.conn.execute(
-                "DELETE FROM history WHERE rowid IN (SELECT rowid FROM history ORDER BY rowid ASC \
-                 LIMIT ?1);",
-                [count - len],
-            )?;
-        }
+        truncate(&self.conn, len)?;
         self.max_len = len;
         Ok(())
     }
 
     fn ignore_dups(&mut self, yes: bool) -> Result<()> {
-        if self.ignore_dups != yes {
-            self.ignore_dups = yes;
+        self.set_duplicates(if yes {
+            HistoryDuplicates::IgnoreConsecutive
+        } else {
+            HistoryDuplicates::AlwaysAdd
+        })
+    }
+
+    fn set_duplicates(&mut self, duplicates: HistoryDuplicates) -> Result<()> {
+        if self.duplicates != duplicates {
+            self.duplicates = duplicates;
             self.set_ignore_dups()?;
         }
         Ok(())
@@ -354,8 +665,8 @@ PRAGMA incremental_vacuum;
             self.create_session()?; // TODO preserve session.timestamp
             old.execute("ATTACH DATABASE ?1 AS new;", [path.to_string_lossy()])?; // TODO empty path / temporary database
             old.execute(
-                "INSERT OR IGNORE INTO new.history (session_id, entry) SELECT ?1, entry FROM \
-                 history WHERE session_id = ?2;",
+                "INSERT OR IGNORE INTO new.history (session_id, entry, context) SELECT ?1, entry, \
+                 context FROM history WHERE session_id = ?2;",
                 [self.session_id, old_id],
             )?; // TODO Validate: only current session entries
             old.execute("DETACH DATABASE new;", [])?;
@@ -380,7 +691,19 @@ PRAGMA incremental_vacuum;
         Ok(())
     }
 
+    fn truncate_file(&mut self, path: &Path, len: usize) -> Result<()> {
+        if is_same(self.path.as_ref(), path) {
+            truncate(&self.conn, len)
+        } else {
+            truncate(&Connection::open(path)?, len)
+        }
+    }
+
     fn clear(&mut self) -> Result<()> {
+        if !self.session_only.is_empty() {
+            self.session_only.clear();
+            self.update_row_id()?;
+        }
         if self.session_id == 0 {
             return Ok(());
         } else if self.is_mem_or_temp() {
@@ -393,13 +716,58 @@ PRAGMA incremental_vacuum;
         Ok(())
     }
 
+    fn set_context(&mut self, context: Option<String>) -> Result<()> {
+        self.context = context;
+        Ok(())
+    }
+
+    fn set_context_filter(&mut self, context: Option<String>) -> Result<()> {
+        self.context_filter = context;
+        Ok(())
+    }
+
+    fn set_filter(&mut self, filter: Option<Box<dyn HistoryFilter>>) -> Result<()> {
+        self.filter = filter;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<bool> {
+        let row_id = self.row_id.get();
+        self.update_row_id()?;
+        Ok(self.row_id.get() != row_id)
+    }
+
     fn search(
         &self,
         term: &str,
         start: usize,
         dir: SearchDirection,
     ) -> Result<Option<SearchResult<'_>>> {
-        self.search_match(term, start, dir, false)
+        self.search_with_mode(term, start, dir, SearchMode::Fts)
+    }
+
+    fn search_with_mode(
+        &self,
+        term: &str,
+        start: usize,
+        dir: SearchDirection,
+        mode: SearchMode,
+    ) -> Result<Option<SearchResult<'_>>> {
+        if term.is_empty() || start >= self.len() {
+            return Ok(None);
+        }
+        let found = if mode == SearchMode::Fts {
+            self.search_match(term, start, dir, false)?
+        } else {
+            let Some((condition, test)) = sql_matcher(term, mode)? else {
+                return Ok(None);
+            };
+            self.search_scan(term, start, dir, condition, test)?
+        };
+        let Some(test) = matcher(term, mode)? else {
+            return Ok(found);
+        };
+        Ok(self.with_session_only(found, start, dir, test))
     }
 
     fn starts_with(
@@ -408,19 +776,153 @@ PRAGMA incremental_vacuum;
         start: usize,
         dir: SearchDirection,
     ) -> Result<Option<SearchResult<'_>>> {
-        self.search_match(term, start, dir, true)
+        let found = self.search_match(term, start, dir, true)?;
+        if term.is_empty() {
+            return Ok(found);
+        }
+        Ok(self.with_session_only(found, start, dir, |entry| {
+            entry.starts_with(term).then_some(term.len())
+        }))
     }
+
+    fn ranked(
+        &self,
+        term: &str,
+        mode: SearchMode,
+        ranking: HistoryRanking,
+        limit: usize,
+    ) -> Result<Vec<SearchResult<'_>>> {
+        if term.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+        let Some((condition, test)) = sql_matcher(term, mode)? else {
+            return Ok(Vec::new());
+        };
+        let order = match ranking {
+            HistoryRanking::Recency => "max(rowid) DESC".to_owned(),
+            HistoryRanking::Frequency => "count(1) DESC, max(rowid) DESC".to_owned(),
+            HistoryRanking::Frecency => format!("{FRECENCY} DESC, max(rowid) DESC"),
+        };
+        let query = format!(
+            "SELECT max(rowid), entry FROM history WHERE rowid <= ?1 AND (?2 IS NULL OR context = \
+             ?2) AND {condition} GROUP BY entry ORDER BY {order};"
+        );
+        let mut stmt = self.conn.prepare_cached(&query)?;
+        let mut matches = Vec::new();
+        let mut rows = stmt.query((self.row_id.get(), self.context_filter.as_deref(), term))?;
+        while matches.len() < limit {
+            let row = match rows.next() {
+                Ok(Some(row)) => row,
+                Ok(None) => break,
+                Err(err) if is_malformed_match(&err) => break,
+                Err(err) => return Err(err.into()),
+            };
+            let entry: String = row.get(1)?;
+            if let Some(pos) = test(&entry) {
+                matches.push(SearchResult {
+                    idx: row.get::<_, usize>(0)? - 1,
+                    entry: Cow::Owned(entry),
+                    pos,
+                });
+            }
+        }
+        Ok(matches)
+    }
+}
+
+/// Frecency score of an entry (in a `GROUP BY entry` query), see
+/// [`frecency`](crate::history::frecency)
+const FRECENCY: &str = "sum(CASE WHEN julianday('now') - timestamp < 4 THEN 100 WHEN \
+                        julianday('now') - timestamp < 14 THEN 70 WHEN julianday('now') - \
+                        timestamp < 31 THEN 50 WHEN julianday('now') - timestamp < 90 THEN 30 \
+                        ELSE 10 END)";
+
+/// SQL condition on `entry` (with the search term as `?3`) and matcher of
+/// `mode` (`None` for an incomplete regex)
+fn sql_matcher(term: &str, mode: SearchMode) -> Result<Option<(&'static str, Matcher<'_>)>> {
+    let condition = match mode {
+        SearchMode::Substring => "instr(entry, ?3) > 0",
+        SearchMode::Prefix => "substr(entry, 1, length(?3)) = ?3",
+        // LIKE is only case-insensitive for ASCII characters
+        SearchMode::CaseInsensitive => "length(entry) >= length(?3)",
+        SearchMode::Regex => "entry REGEXP ?3",
+        SearchMode::Fts => {
+            // FTS query syntax differs from `matcher`: position of the first word
+            let test: Matcher<'_> = Box::new(move |entry: &str| {
+                Some(
+                    term.split_whitespace()
+                        .next()
+                        .and_then(|word| find_ignore_case(entry, word))
+                        .unwrap_or(0),
+                )
+            });
+            return Ok(Some((
+                "rowid IN (SELECT docid FROM fts WHERE entry MATCH ?3 || '*')",
+                test,
+            )));
+        }
+    };
+    Ok(matcher(term, mode)?.map(|test| (condition, test)))
+}
+
+/// Malformed MATCH expression (like an unbalanced quote while typing)
+fn is_malformed_match(err: &rusqlite::Error) -> bool {
+    matches!(err, rusqlite::Error::SqliteFailure(e, Some(msg))
+        if e.code == rusqlite::ErrorCode::Unknown && msg.starts_with("malformed MATCH expression"))
 }
 
 fn conn(path: Option<&PathBuf>) -> rusqlite::Result<Connection> {
-    if let Some(ref path) = path {
-        Connection::open(path)
+    let conn = if let Some(ref path) = path {
+        Connection::open(path)?
     } else {
-        Connection::open_in_memory()
+        Connection::open_in_memory()?
+    };
+    #[cfg(feature = "regex")]
+    regexp(&conn)?;
+    Ok(conn)
+}
+
+/// Register the `REGEXP` function (`entry REGEXP pattern`)
+#[cfg(feature = "regex")]
+fn regexp(conn: &Connection) -> rusqlite::Result<()> {
+    use rusqlite::functions::FunctionFlags;
+
+    type BoxError = Box<dyn std::error::Error + Send + Sync>;
+    conn.create_scalar_function(
+        "regexp",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            // compiled once per statement
+            let re = ctx.get_or_create_aux(0, |pattern| -> std::result::Result<_, BoxError> {
+                Ok(regex::Regex::new(pattern.as_str()?)?)
+            })?;
+            let entry = ctx
+                .get_raw(1)
+                .as_str()
+                .map_err(|e| rusqlite::Error::UserFunctionError(e.into()))?;
+            Ok(re.is_match(entry))
+        },
+    )
+}
+
+/// Keep only the last `len` entries
+fn truncate(conn: &Connection, len: usize) -> Result<()> {
+    // FIXME rowid may not be sequential
+    let count: usize = conn.query_row("SELECT count(1) FROM history;", [], |r| r.get(0))?;
+    if count > len {
+        conn.execute(
+            "DELETE FROM history WHERE rowid IN (SELECT rowid FROM history ORDER BY rowid ASC \
+             LIMIT ?1);",
+            [count - len],
+        )?;
     }
+    Ok(())
 }
 
 const MEMORY: &str = ":memory:";
+const SECS_PER_DAY: f64 = 86_400.;
+const UNIX_EPOCH_JULIAN_DAY: f64 = 2_440_587.5;
 
 fn normalize(path: &Path) -> Option<PathBuf> {
     if path.as_os_str() == MEMORY {
@@ -455,7 +957,7 @@ mod tests {
     use super::SQLiteHistory;
     use crate::Result;
     use crate::config::Config;
-    use crate::history::{History as _, SearchDirection, SearchResult};
+    use crate::history::{History as _, SearchDirection, SearchMode, SearchResult};
 
     fn init() -> Result<SQLiteHistory> {
         let mut h = SQLiteHistory::with_config(&Config::default())?;
@@ -534,7 +1036,7 @@ mod tests {
         h.save(Path::new(db2))?;
         h.add("line")?;
         h.save(Path::new(db1))?;
-        assert_eq!(db1, h.path.unwrap().as_os_str());
+        assert_eq!(db1, h.path.as_ref().unwrap().as_os_str());
         assert_eq!(1, h.session_id);
         assert_eq!(1, h.row_id.get());
         Ok(())
@@ -553,13 +1055,166 @@ mod tests {
         //h.append(Path::new(db2))?;
         h.add("line")?;
         h.append(Path::new(db2))?;
-        assert_eq!(db2, h.path.unwrap().as_os_str());
+        assert_eq!(db2, h.path.as_ref().unwrap().as_os_str());
         assert_eq!(1, h.session_id);
         assert_eq!(1, h.row_id.get());
         tf.close()?;
         Ok(())
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn sync() -> Result<()> {
+        let tf = tempfile::NamedTempFile::new()?;
+        let mut h1 = SQLiteHistory::open(&Config::default(), tf.path())?;
+        h1.add("line1")?;
+        let mut h2 = SQLiteHistory::open(&Config::default(), tf.path())?;
+        assert_eq!(1, h2.len());
+        h1.add("line2")?;
+        assert_eq!(1, h2.len());
+        assert!(h2.sync()?);
+        assert!(!h2.sync()?);
+        assert_eq!(
+            Some(Cow::Borrowed("line2")),
+            h2.get(1, SearchDirection::Reverse)?.map(|r| r.entry)
+        );
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn truncate_file() -> Result<()> {
+        let config = Config::builder()
+            .max_history_entry_len(Some(5))
+            .max_history_bytes(Some(12))
+            .build();
+        let mut h = SQLiteHistory::with_config(&config)?;
+        assert!(!h.add("line10")?);
+        h.add("line1")?;
+        h.add("line2")?;
+        h.add("line3")?;
+        let count = |h: &SQLiteHistory| -> Result<i32> {
+            Ok(h.conn
+                .query_row("SELECT count(1) FROM history;", [], |r| r.get(0))?)
+        };
+        assert_eq!(2, count(&h)?);
+        h.truncate_file(Path::new(":memory:"), 1)?;
+        assert_eq!(1, count(&h)?);
+        assert_eq!(
+            Some(Cow::Borrowed("line3")),
+            h.get(0, SearchDirection::Forward)?.map(|r| r.entry)
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn filter() -> Result<()> {
+        use crate::history::FilterResult;
+
+        let tf = tempfile::NamedTempFile::new()?;
+        let mut h = SQLiteHistory::open(&Config::default(), tf.path())?;
+        h.set_filter(Some(Box::new(|line: &str| match line {
+            "secret" => FilterResult::Reject,
+            "token=1234" => FilterResult::Rewrite("token=***".to_owned()),
+            "ls" => FilterResult::SessionOnly,
+            _ => FilterResult::Keep,
+        })))?;
+        assert!(!h.add("secret")?);
+        assert!(h.add("token=1234")?);
+        assert!(h.add("ls")?);
+        assert!(h.add("pwd")?);
+        // session-only entry can be navigated and searched
+        assert_eq!(3, h.len());
+        assert_eq!(
+            Some(Cow::Borrowed("ls")),
+            h.get(1, SearchDirection::Reverse)?.map(|r| r.entry)
+        );
+        assert_eq!(
+            Some(1),
+            h.starts_with("l", 2, SearchDirection::Reverse)?
+                .map(|r| r.idx)
+        );
+        assert_eq!(
+            Some(1),
+            h.search_with_mode("s", 2, SearchDirection::Reverse, SearchMode::Substring)?
+                .map(|r| r.idx)
+        );
+        // but not persisted
+        let h = SQLiteHistory::open(&Config::default(), tf.path())?;
+        assert_eq!(
+            vec!["token=***", "pwd"],
+            h.most_recent_unique(10)?
+                .into_iter()
+                .rev()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some(Cow::Borrowed("token=***")),
+            h.get(1, SearchDirection::Reverse)?.map(|r| r.entry)
+        );
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn erase_dups() -> Result<()> {
+        use crate::config::HistoryDuplicates;
+
+        let tf = tempfile::NamedTempFile::new()?;
+        let mut h1 = SQLiteHistory::open(&Config::default(), tf.path())?;
+        h1.ignore_dups(false)?;
+        h1.add("b")?;
+        let mut h2 = SQLiteHistory::open(&Config::default(), tf.path())?;
+        h2.ignore_dups(false)?;
+        h2.add("a")?;
+        h2.add("b")?;
+        h2.add("a")?;
+        h2.set_duplicates(HistoryDuplicates::EraseDups)?;
+        h2.add("b")?;
+        let entries: Vec<String> = h2
+            .conn
+            .prepare("SELECT entry FROM history ORDER BY rowid;")?
+            .query_map([], |r| r.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        // other session's entry is erased too
+        assert_eq!(vec!["a", "a", "b"], entries);
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn context() -> Result<()> {
+        let mut h = SQLiteHistory::with_config(&Config::default())?;
+        h.set_context(Some("/tmp".to_owned()))?;
+        h.add("cd tmp")?;
+        h.set_context(Some("/home".to_owned()))?;
+        h.add("cd home")?;
+        h.set_context(None)?;
+        h.add("pwd")?;
+        h.set_context_filter(Some("/tmp".to_owned()))?;
+        assert_eq!(
+            Some(Cow::Borrowed("cd tmp")),
+            h.get(2, SearchDirection::Reverse)?.map(|r| r.entry)
+        );
+        assert!(h.get(1, SearchDirection::Forward)?.is_none());
+        assert!(
+            h.starts_with("cd", 2, SearchDirection::Reverse)?
+                .is_some_and(|r| r.idx == 0)
+        );
+        assert!(
+            h.search_with_mode("home", 2, SearchDirection::Reverse, SearchMode::Substring)?
+                .is_none()
+        );
+        h.set_context_filter(None)?;
+        assert!(
+            h.search_with_mode("home", 2, SearchDirection::Reverse, SearchMode::Substring)?
+                .is_some_and(|r| r.idx == 1)
+        );
+        Ok(())
+    }
+
     #[test]
     fn load() -> Result<()> {
         let db1 = "file:db1?mode=memory";
@@ -568,7 +1223,7 @@ mod tests {
         h.load(Path::new(db1))?;
         h.add("line")?;
         h.load(Path::new(db2))?;
-        assert_eq!(db2, h.path.unwrap().as_os_str());
+        assert_eq!(db2, h.path.as_ref().unwrap().as_os_str());
         assert_eq!(0, h.session_id);
         assert_eq!(0, h.row_id.get());
         Ok(())
@@ -663,6 +1318,99 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn search_modes() -> Result<()> {
+        let mut h = SQLiteHistory::with_config(&Config::default())?;
+        h.add("git commit -m Fix")?;
+        h.add("ls Fixtures")?;
+        let search = |term, mode| -> Result<Option<(usize, usize)>> {
+            Ok(h.search_with_mode(term, 1, SearchDirection::Reverse, mode)?
+                .map(|r| (r.idx, r.pos)))
+        };
+        assert_eq!(None, search("fix", SearchMode::Substring)?);
+        assert_eq!(Some((1, 3)), search("Fix", SearchMode::Substring)?);
+        assert_eq!(Some((0, 3)), search("git", SearchMode::Prefix)?);
+        assert_eq!(Some((1, 3)), search("fIX", SearchMode::CaseInsensitive)?);
+        assert_eq!(Some((0, 4)), search("commit fix", SearchMode::Fts)?);
+        assert_eq!(None, search("\"commit", SearchMode::Fts)?);
+        if cfg!(feature = "regex") {
+            assert_eq!(Some((0, 14)), search("Fix$", SearchMode::Regex)?);
+            assert_eq!(None, search("(Fix", SearchMode::Regex)?);
+        } else {
+            assert!(search("Fix$", SearchMode::Regex).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn search_error() -> Result<()> {
+        let h = init()?;
+        h.conn.execute_batch("DROP TABLE fts;")?;
+        // not a malformed MATCH expression
+        assert!(h.search("line", 2, SearchDirection::Reverse).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn statistics() -> Result<()> {
+        use std::time::{Duration, SystemTime};
+
+        let mut h = SQLiteHistory::with_config(&Config::default())?;
+        h.ignore_dups(false)?;
+        let old = SystemTime::now() - Duration::from_secs(100 * 24 * 60 * 60);
+        for _ in 0..3 {
+            h.add_with_timestamp("make".to_owned(), old)?;
+        }
+        h.add("ls")?;
+        h.add("ls")?;
+        h.add("make test")?;
+        assert_eq!(
+            vec![("make".to_owned(), 3), ("ls".to_owned(), 2)],
+            h.most_used(2)?
+        );
+        assert_eq!(vec!["make test", "ls", "make"], h.most_recent_unique(5)?);
+        assert_eq!(
+            vec![("ls".to_owned(), 200), ("make test".to_owned(), 100)],
+            h.frecency(2)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn ranked() -> Result<()> {
+        use crate::HistoryRanking;
+
+        let mut h = SQLiteHistory::with_config(&Config::default())?;
+        h.ignore_dups(false)?;
+        h.add("make")?;
+        h.add("make")?;
+        h.add("make test")?;
+        h.add("ls")?;
+        let ranked = |ranking| -> Result<Vec<(usize, String)>> {
+            Ok(h.ranked("make", SearchMode::Prefix, ranking, 5)?
+                .into_iter()
+                .map(|sr| (sr.idx, sr.entry.into_owned()))
+                .collect())
+        };
+        assert_eq!(
+            vec![(2, "make test".to_owned()), (1, "make".to_owned())],
+            ranked(HistoryRanking::Recency)?
+        );
+        assert_eq!(
+            vec![(1, "make".to_owned()), (2, "make test".to_owned())],
+            ranked(HistoryRanking::Frequency)?
+        );
+        assert_eq!(
+            vec![(1, "make".to_owned()), (2, "make test".to_owned())],
+            ranked(HistoryRanking::Frecency)?
+        );
+        assert!(
+            h.ranked("\"make", SearchMode::Fts, HistoryRanking::Frequency, 5)?
+                .is_empty()
+        );
+        Ok(())
+    }
+
     #[test]
     fn starts_with() -> Result<()> {
         let h = init()?;
@@ -0,0 +1,277 @@
+//! Serve an [`Editor`] over arbitrary byte streams (an SSH channel, a
+//! WebSocket, ...), without local PTY.
+//!
+//! Keys are decoded from the bytes read, escape sequences are written back
+//! (the remote side is expected to interpret them, like a VT100 terminal
+//! emulator) and the window size is provided by the application.
+//!
+//! Applications implement [`Transport`] for their own connection, or use
+//! [`Streams`] over a [`Read`]er and a [`Write`]r (a terminal which is not
+//! VT100 compatible can implement [`Term`](crate::term::Term) instead):
+//! ```
+//! use rustyline::Config;
+//! use rustyline::stream::{StreamEditor, Streams, WindowSize};
+//!
+//! let input = std::io::Cursor::new("hello\r");
+//! let size = WindowSize::new(80, 24);
+//! let mut rl: StreamEditor<(), Streams<_, _>> =
+//!     StreamEditor::with_stream(Config::default(), input, std::io::sink(), size.clone());
+//! // on window change:
+//! size.set(100, 30);
+//! assert_eq!("hello", rl.readline("> ")?);
+//! # Ok::<(), rustyline::error::ReadlineError>(())
+//! ```
+use std::io::{Read, Write};
+
+use crate::config::Config;
+use crate::history::{DefaultHistory, History};
+pub use crate::tty::stream::{
+    StreamCursorGuard, StreamMode, StreamPrinter, StreamReader, StreamRenderer, StreamTerminal,
+    Streams, Transport, WindowSize,
+};
+use crate::{Editor, Helper};
+
+/// Editor reading keys from and writing to a `T` transport
+pub type StreamEditor<H, T, I = DefaultHistory> = Editor<H, I, StreamTerminal<T>>;
+
+impl<H: Helper, T: Transport> Editor<H, DefaultHistory, StreamTerminal<T>> {
+    /// Create an editor over `transport`.
+    pub fn with_transport(config: Config, transport: T) -> Self {
+        let history = DefaultHistory::with_config(&config);
+        Self::with_transport_and_history(config, history, transport)
+    }
+}
+
+impl<H: Helper, R: Read, W: Write> Editor<H, DefaultHistory, StreamTerminal<Streams<R, W>>> {
+    /// Create an editor reading keys from `input` and writing to `output`
+    /// a `size` window.
+    pub fn with_stream(config: Config, input: R, output: W, size: WindowSize) -> Self {
+        Self::with_transport(config, Streams::new(input, output, size))
+    }
+}
+
+impl<H: Helper, I: History, T: Transport> Editor<H, I, StreamTerminal<T>> {
+    /// Create an editor over `transport` with a custom history impl.
+    pub fn with_transport_and_history(config: Config, history: I, transport: T) -> Self {
+        Self::with_term(config, history, StreamTerminal::new(transport))
+    }
+}
+
+impl<H: Helper, I: History, R: Read, W: Write> Editor<H, I, StreamTerminal<Streams<R, W>>> {
+    /// Create an editor over streams with a custom history impl.
+    pub fn with_stream_and_history(
+        config: Config,
+        history: I,
+        input: R,
+        output: W,
+        size: WindowSize,
+    ) -> Self {
+        Self::with_transport_and_history(config, history, Streams::new(input, output, size))
+    }
+
+    /// Window size shared with the application
+    #[must_use]
+    pub fn window_size(&self) -> WindowSize {
+        self.term.transport().window_size().clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Cursor, Write};
+    use std::sync::{Arc, Mutex, mpsc};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{StreamEditor, Streams, Transport, WindowSize};
+    use crate::ExternalPrinter as _;
+    use crate::config::Config;
+    use crate::error::ReadlineError;
+    use crate::testing::Screen;
+
+    /// Output shared with the test
+    #[derive(Clone, Debug, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output {
+        fn screen(&self, cols: u16, rows: u16) -> Screen {
+            let mut screen = Screen::new(cols, rows);
+            screen.print(std::str::from_utf8(&self.0.lock().unwrap()).unwrap());
+            screen
+        }
+    }
+
+    fn editor(
+        input: &'static str,
+        cols: u16,
+    ) -> (
+        StreamEditor<(), Streams<Cursor<&'static str>, Output>>,
+        Output,
+    ) {
+        let output = Output::default();
+        let rl = StreamEditor::with_stream(
+            Config::default(),
+            Cursor::new(input),
+            output.clone(),
+            WindowSize::new(cols, 24),
+        );
+        (rl, output)
+    }
+
+    #[test]
+    fn escape_sequences() {
+        let (mut rl, output) = editor("ac\x1b[Db\x1b[F\u{e9}\r", 80);
+        assert_eq!("abc\u{e9}", rl.readline("> ").unwrap());
+        assert_eq!("> abc\u{e9}", output.screen(80, 24).to_string());
+        let output = output.0.lock().unwrap();
+        assert!(output.ends_with(b"\r\n"));
+        assert!(!output.windows(2).any(|w| w[1] == b'\n' && w[0] != b'\r'));
+    }
+
+    #[test]
+    fn type_ahead() {
+        let (mut rl, _) = editor("a\rb\r", 80);
+        assert_eq!("a", rl.readline("> ").unwrap());
+        assert_eq!("b", rl.readline("> ").unwrap());
+        assert!(matches!(rl.readline("> "), Err(ReadlineError::Eof)));
+    }
+
+    #[test]
+    fn bracketed_paste() {
+        let (mut rl, _) = editor("\x1b[200~a\r\nb\x1b[201~\r", 80);
+        assert_eq!("a\nb", rl.readline("> ").unwrap());
+    }
+
+    #[test]
+    fn mouse() {
+        // wheel up, motion (ignored), left button release (ignored: no cursor location)
+        let (mut rl, _) = editor("\x1b[<64;3;1M\x1b[<35;3;1M\x1b[<0;1;1m\r", 80);
+        rl.add_history_entry("previous").unwrap();
+        assert_eq!("previous", rl.readline("> ").unwrap());
+    }
+
+    #[test]
+    fn window_size() {
+        let (mut rl, output) = editor("0123456789\r", 80);
+        rl.window_size().set(8, 24);
+        assert_eq!("0123456789", rl.readline("> ").unwrap());
+        assert_eq!("> 012345\n6789", output.screen(8, 24).to_string());
+    }
+
+    /// Transport implemented by the application
+    struct Channel {
+        input: Cursor<&'static str>,
+        output: Output,
+        cols: u16,
+    }
+
+    impl Transport for Channel {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            io::Read::read(&mut self.input, buf)
+        }
+
+        fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+            self.output.write_all(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn size(&self) -> (u16, u16) {
+            (self.cols, 0)
+        }
+    }
+
+    /// Transport receiving keys from another thread
+    struct Remote {
+        keys: mpsc::Receiver<u8>,
+        /// Key received while polling
+        pending: Option<u8>,
+        output: Output,
+    }
+
+    impl Transport for Remote {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let b = match self.pending.take() {
+                Some(b) => b,
+                None => match self.keys.recv() {
+                    Ok(b) => b,
+                    Err(_) => return Ok(0),
+                },
+            };
+            buf[0] = b;
+            Ok(1)
+        }
+
+        fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+            self.output.write_all(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn size(&self) -> (u16, u16) {
+            (80, 24)
+        }
+
+        fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+            if self.pending.is_none() {
+                match self.keys.recv_timeout(timeout) {
+                    Ok(b) => self.pending = Some(b),
+                    Err(mpsc::RecvTimeoutError::Timeout) => return Ok(false),
+                    Err(mpsc::RecvTimeoutError::Disconnected) => {}
+                }
+            }
+            Ok(true)
+        }
+    }
+
+    #[test]
+    fn external_print() {
+        let (tx, keys) = mpsc::channel();
+        let output = Output::default();
+        let remote = Remote {
+            keys,
+            pending: None,
+            output: output.clone(),
+        };
+        let mut rl = StreamEditor::<(), _>::with_transport(Config::default(), remote);
+        let mut printer = rl.create_external_printer().unwrap();
+        let shown = output.clone();
+        let remote = thread::spawn(move || {
+            printer.print("message".to_owned()).unwrap();
+            // no key until the message has been displayed
+            while !shown.screen(80, 24).to_string().contains("message") {
+                thread::sleep(Duration::from_millis(10));
+            }
+            tx.send(b'\r').unwrap();
+        });
+        assert_eq!("", rl.readline("> ").unwrap());
+        remote.join().unwrap();
+    }
+
+    #[test]
+    fn custom_transport() {
+        let output = Output::default();
+        let channel = Channel {
+            input: Cursor::new("0123456789\r"),
+            output: output.clone(),
+            cols: 8,
+        };
+        let mut rl = StreamEditor::<(), _>::with_transport(Config::default(), channel);
+        assert_eq!("0123456789", rl.readline("> ").unwrap());
+        assert_eq!("> 012345\n6789", output.screen(8, 24).to_string());
+    }
+}
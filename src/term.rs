@@ -0,0 +1,9 @@
+//! Terminal abstraction used by [`Editor`](crate::Editor)
+//!
+//! [`Editor`](crate::Editor) is generic over a [`Term`]: by default the
+//! local terminal, chosen by `cfg` and bound to the process stdin / stdout.
+//! Another terminal (see [`crate::stream`] for a VT100 compatible one over
+//! any connection) reads keys with a [`RawReader`] and displays the edited
+//! line with a [`Renderer`], whose [`Layout`] is computed by the editor.
+pub use crate::layout::{HScroll, Layout, Position, Unit, Viewport};
+pub use crate::tty::{Event, ExternalPrinter, RawMode, RawReader, Renderer, Term};
@@ -2,9 +2,11 @@
 use std::assert_matches;
 
 use super::{assert_cursor, assert_line, assert_line_with_initial, init_editor};
-use crate::config::EditMode;
+use crate::ExternalPrinter as _;
+use crate::config::{Config, Configurer as _, EditMode};
 use crate::error::ReadlineError;
-use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
+use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M, Mouse, MouseButton};
+use crate::testing::HeadlessEditor;
 
 #[test]
 fn home_key() {
@@ -361,3 +363,90 @@ fn paste() {
         }
     }
 }
+
+fn mouse(button: MouseButton, pressed: bool, row: u16, col: u16) -> E {
+    E(
+        K::Mouse(Mouse {
+            button,
+            pressed,
+            col,
+            row,
+        }),
+        M::NONE,
+    )
+}
+
+fn click(row: u16, col: u16) -> [E; 2] {
+    [
+        mouse(MouseButton::Left, true, row, col),
+        mouse(MouseButton::Left, false, row, col),
+    ]
+}
+
+#[test]
+fn mouse_click() {
+    let mut rl = HeadlessEditor::<()>::headless(Config::default());
+    let mut printer = rl.create_external_printer().unwrap();
+    printer.print("message".to_owned()).unwrap();
+    rl.push_keys("hello world".chars().map(E::from));
+    rl.push_keys(click(1, 4));
+    rl.push_keys(click(0, 0)); // above the prompt: ignored
+    let outcome = rl.run("> ", [E::from('X'), E::ENTER]);
+    assert_eq!("heXllo world", outcome.line.unwrap());
+
+    rl.push_keys("hello".chars().map(E::from));
+    rl.push_keys(click(2, 1)); // on the prompt
+    rl.push_keys([E::from('X')]);
+    rl.push_keys(click(2, 40)); // after the end
+    let outcome = rl.run("> ", [E::from('Y'), E::ENTER]);
+    assert_eq!("XhelloY", outcome.line.unwrap());
+}
+
+#[test]
+fn mouse_click_after_scroll() {
+    let mut builder = Config::builder();
+    builder.enable_mouse(true);
+    let mut rl = HeadlessEditor::<()>::headless(builder.build());
+    for _ in 0..30 {
+        rl.run("> ", [E::ENTER]).line.unwrap();
+    }
+    // the prompt is on the last row, scrolled up by the second row of input
+    rl.push_keys(std::iter::repeat_n(E::from('a'), 100));
+    rl.push_keys(click(23, 0));
+    rl.push_keys([E::from('X')]);
+    rl.push_keys(click(22, 2));
+    let outcome = rl.run("> ", [E::from('Y'), E::ENTER]);
+    let expected = format!("Y{}X{}", "a".repeat(78), "a".repeat(22));
+    assert_eq!(expected, outcome.line.unwrap());
+}
+
+#[test]
+fn mouse_wheel() {
+    let mut rl = HeadlessEditor::<()>::headless(Config::default());
+    rl.add_history_entry("first").unwrap();
+    rl.add_history_entry("second").unwrap();
+    rl.push_keys([
+        mouse(MouseButton::WheelUp, true, 0, 0),
+        mouse(MouseButton::WheelUp, true, 0, 0),
+        mouse(MouseButton::WheelDown, true, 0, 0),
+    ]);
+    let outcome = rl.run("> ", [E::ENTER]);
+    assert_eq!("second", outcome.line.unwrap());
+}
+
+#[cfg(feature = "custom-bindings")]
+#[test]
+fn mouse_binding() {
+    use crate::{Cmd, Event};
+
+    let mut rl = HeadlessEditor::<()>::headless(Config::default());
+    rl.bind_sequence(
+        mouse(MouseButton::Right, true, 0, 0),
+        Cmd::Insert(1, "r".to_owned()),
+    );
+    rl.bind_sequence(Event::Mouse(), Cmd::Insert(1, "m".to_owned()));
+    rl.push_keys([mouse(MouseButton::Right, true, 5, 5)]);
+    rl.push_keys([mouse(MouseButton::Right, false, 5, 5)]);
+    let outcome = rl.run("> ", [E::ENTER]);
+    assert_eq!("rm", outcome.line.unwrap());
+}
@@ -1,7 +1,15 @@
 //! History related commands tests
 use super::assert_history;
-use crate::config::EditMode;
+use crate::config::{Config, EditMode};
+use crate::history::History;
 use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
+use crate::{DefaultEditor, Editor};
+
+// `keys`: keys to press, ending with enter
+fn readline<I: History>(editor: &mut Editor<(), I>, keys: &[E]) -> String {
+    editor.term.keys = keys.to_vec();
+    editor.readline("").unwrap()
+}
 
 #[test]
 fn down_key() {
@@ -241,3 +249,125 @@ fn meta_gt() {
         ("a", ""),
     );
 }
+
+#[cfg(not(feature = "case_insensitive_history_search"))]
+#[test]
+fn ctrl_r_toggle_search_mode() {
+    let mut editor = DefaultEditor::new().unwrap();
+    editor.add_history_entry("Make").unwrap();
+    editor.add_history_entry("ls").unwrap();
+    assert_eq!(
+        "Make",
+        readline(
+            &mut editor,
+            &[
+                E::ctrl('R'),
+                E::from('m'),
+                E::from('a'),
+                E::from('k'),
+                E::from('e'),
+                E::ctrl('O'), // prefix
+                E::ctrl('O'), // case insensitive
+                E::ENTER,
+            ]
+        )
+    );
+}
+
+#[cfg(feature = "with-sqlite-history")]
+#[test]
+fn ctrl_r_ranking() {
+    use crate::HistoryRanking;
+    use crate::sqlite_history::SQLiteHistory;
+
+    let config = Config::builder()
+        .history_ranking(HistoryRanking::Frequency)
+        .build();
+    let mut history = SQLiteHistory::with_config(&config).unwrap();
+    history.ignore_dups(false).unwrap();
+    for line in ["make test", "make test", "make", "ls"] {
+        history.add(line).unwrap();
+    }
+    let mut editor = Editor::<(), _>::with_history(config, history).unwrap();
+    let search = [
+        E::ctrl('R'),
+        E::from('m'),
+        E::from('a'),
+        E::from('k'),
+        E::from('e'),
+    ];
+    let mut keys = search.to_vec();
+    keys.push(E::ENTER);
+    assert_eq!("make test", readline(&mut editor, &keys));
+    keys.insert(search.len(), E::ctrl('R'));
+    assert_eq!("make", readline(&mut editor, &keys));
+    keys.insert(search.len(), E::ctrl('R'));
+    assert_eq!("make", readline(&mut editor, &keys));
+}
+
+#[test]
+fn find_no_dups() {
+    let config = Config::builder().history_find_no_dups(true).build();
+    let mut editor = DefaultEditor::with_config(config).unwrap();
+    for line in ["a", "b", "c", "b"] {
+        editor.add_history_entry(line).unwrap();
+    }
+    let up = E(K::Up, M::NONE);
+    let down = E(K::Down, M::NONE);
+    assert_eq!("a", readline(&mut editor, &[up, up, up, up, E::ENTER]));
+    assert_eq!(
+        "c",
+        readline(&mut editor, &[E::from('x'), up, up, up, down, E::ENTER])
+    );
+    assert_eq!(
+        "x",
+        readline(&mut editor, &[E::from('x'), up, down, E::ENTER])
+    );
+}
+
+#[test]
+fn context_filter() {
+    let mut editor = DefaultEditor::new().unwrap();
+    for (context, line) in [("/tmp", "a"), ("/home", "b"), ("/tmp", "c"), ("/home", "d")] {
+        editor
+            .history_mut()
+            .set_context(Some(context.to_owned()))
+            .unwrap();
+        editor.add_history_entry(line).unwrap();
+    }
+    editor
+        .history_mut()
+        .set_context_filter(Some("/tmp".to_owned()))
+        .unwrap();
+    let up = E(K::Up, M::NONE);
+    let down = E(K::Down, M::NONE);
+    assert_eq!("a", readline(&mut editor, &[up, up, up, E::ENTER]));
+    assert_eq!(
+        "c",
+        readline(&mut editor, &[E::from('x'), up, up, down, E::ENTER])
+    );
+    assert_eq!(
+        "x",
+        readline(&mut editor, &[E::from('x'), up, down, E::ENTER])
+    );
+}
+
+#[cfg(feature = "with-file-history")]
+#[test]
+fn history_sharing() {
+    let tf = tempfile::NamedTempFile::new().unwrap();
+    let mut editor = DefaultEditor::new().unwrap();
+    editor.add_history_entry("first").unwrap();
+    editor.save_history(tf.path()).unwrap();
+    let config = Config::builder().history_sharing(true).build();
+    let mut editor1 = DefaultEditor::with_config(config.clone()).unwrap();
+    editor1.load_history(tf.path()).unwrap();
+    let mut editor2 = DefaultEditor::with_config(config).unwrap();
+    editor2.load_history(tf.path()).unwrap();
+    editor1.add_history_entry("second").unwrap();
+    editor1.append_history(tf.path()).unwrap();
+    assert_eq!(
+        "second",
+        readline(&mut editor2, &[E(K::Up, M::NONE), E::ENTER])
+    );
+}
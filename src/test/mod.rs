@@ -100,6 +100,113 @@ fn complete_symbol() {
     assert_eq!(3, s.line.pos());
 }
 
+#[cfg(feature = "custom-bindings")]
+#[test]
+fn builtin_bindings() {
+    use crate::keymap::{InputMode, builtin_bindings};
+
+    let history = crate::history::DefaultHistory::new();
+    let bindings = Bindings::new();
+    for (mode, input_mode) in [
+        (EditMode::Emacs, InputMode::Insert),
+        (EditMode::Vi, InputMode::Command),
+        (EditMode::Vi, InputMode::Insert),
+    ] {
+        let config = Config::builder().edit_mode(mode).build();
+        for (keys, cmd) in builtin_bindings(mode, input_mode) {
+            let mut out = Sink::default();
+            let mut s = init_state(&mut out, "", 0, None::<&SimpleCompleter>, &history);
+            s.hint = None;
+            let mut input_state = InputState::new(&config, &bindings);
+            input_state.input_mode = input_mode;
+            let keys = keys.to_vec();
+            let mut rdr: IntoIter<KeyEvent> = keys.clone().into_iter();
+            let actual = input_state.next_cmd(&mut rdr, &mut s, false, true).unwrap();
+            assert_eq!(*cmd, actual, "{mode:?} {keys:?}");
+        }
+    }
+}
+
+#[cfg(feature = "custom-bindings")]
+#[test]
+fn key_bindings() {
+    use crate::keymap::{InputMode, Movement};
+    use crate::{BindingConflict, Event};
+
+    let mut editor = DefaultEditor::new().unwrap();
+    let ctrl_a = Event::from(KeyEvent::ctrl('A'));
+    let ctrl_x_e = Event::KeySeq(vec![KeyEvent::ctrl('X'), KeyEvent::ctrl('E')]);
+    let ctrl_b_x = Event::KeySeq(vec![KeyEvent::ctrl('B'), KeyEvent::from('x')]);
+    editor.bind_sequence(ctrl_a.clone(), Cmd::Noop);
+    editor.bind_sequence(ctrl_x_e.clone(), Cmd::ClearScreen);
+    editor.bind_sequence(ctrl_b_x.clone(), Cmd::Noop);
+
+    let bindings = editor.key_bindings(InputMode::Insert);
+    assert!(bindings.contains(&(ctrl_a.clone(), Cmd::Noop)));
+    assert!(bindings.contains(&(ctrl_x_e, Cmd::ClearScreen)));
+    assert!(bindings.iter().all(|(evt, _)| *evt != ctrl_b_x));
+    assert!(bindings.contains(&(
+        Event::from(KeyEvent::ctrl('E')),
+        Cmd::Move(Movement::EndOfLine)
+    )));
+
+    assert_eq!(
+        vec![
+            BindingConflict::Override(ctrl_a, Cmd::Move(Movement::BeginningOfLine)),
+            BindingConflict::Unreachable(ctrl_b_x, KeyEvent::ctrl('B').into()),
+        ],
+        editor.binding_conflicts(InputMode::Insert)
+    );
+
+    // built-in sequences shadowed by a custom binding of their first key
+    let mut editor = DefaultEditor::new().unwrap();
+    let ctrl_x = Event::from(KeyEvent::ctrl('X'));
+    editor.bind_sequence(ctrl_x.clone(), Cmd::Noop);
+    let bindings = editor.key_bindings(InputMode::Insert);
+    assert!(bindings.contains(&(ctrl_x, Cmd::Noop)));
+    assert!(!bindings.contains(&(
+        Event::KeySeq(vec![KeyEvent::ctrl('X'), KeyEvent::ctrl('U')]),
+        Cmd::Undo(1)
+    )));
+}
+
+#[cfg(feature = "custom-bindings")]
+#[test]
+fn describe_key() {
+    use crate::error::ReadlineError;
+    use crate::testing::HeadlessEditor;
+
+    let mut rl = HeadlessEditor::<()>::headless(Config::default());
+    rl.bind_sequence(E(K::F(1), M::NONE), Cmd::DescribeKey);
+    rl.bind_sequence(E::ctrl('T'), Cmd::Noop);
+    let outcome = rl.run(
+        "> ",
+        [
+            E::from('a'),
+            E::from('b'),
+            E(K::F(1), M::NONE),
+            E::ctrl('A'),
+        ],
+    );
+    assert!(matches!(outcome.line, Err(ReadlineError::Eof)));
+    assert_eq!(
+        vec![
+            "> ab".to_owned(),
+            "KeyEvent(Char('A'), Modifiers(CTRL)) runs Move(BeginningOfLine) (built-in)".to_owned()
+        ],
+        outcome.screen.rows()[..2]
+    );
+
+    let mut rl = HeadlessEditor::<()>::headless(Config::default());
+    rl.bind_sequence(E(K::F(1), M::NONE), Cmd::DescribeKey);
+    rl.bind_sequence(E::ctrl('T'), Cmd::Noop);
+    let outcome = rl.run("> ", [E(K::F(1), M::NONE), E::ctrl('T')]);
+    assert_eq!(
+        "KeyEvent(Char('T'), Modifiers(CTRL)) runs Noop (custom binding)",
+        outcome.screen.row(1)
+    );
+}
+
 // `keys`: keys to press
 // `expected_line`: line after enter key
 fn assert_line(mode: EditMode, keys: &[KeyEvent], expected_line: &str) {
@@ -197,3 +304,73 @@ fn test_readline_direct() {
     );
     assert_eq!(&output.unwrap(), "([\n\n\r\n])");
 }
+
+struct BracketHelper(crate::validate::MatchingBracketValidator);
+impl Completer for BracketHelper {
+    type Candidate = String;
+}
+impl Hinter for BracketHelper {
+    type Hint = String;
+}
+impl Highlighter for BracketHelper {}
+impl Validator for BracketHelper {
+    fn validate(
+        &self,
+        ctx: &mut crate::validate::ValidationContext,
+    ) -> Result<crate::validate::ValidationResult> {
+        self.0.validate(ctx)
+    }
+}
+impl Helper for BracketHelper {}
+
+#[test]
+fn test_script() {
+    use std::io::Cursor;
+
+    let config = Config::builder().auto_add_history(true).build();
+    let mut editor = crate::Editor::with_config(config).unwrap();
+    editor.set_helper(Some(BracketHelper(
+        crate::validate::MatchingBracketValidator::new(),
+    )));
+    editor.set_script(Cursor::new("(a\nb)\n(]\n c\n"));
+    assert_eq!("(a\nb)", editor.readline("> ").unwrap());
+    assert!(matches!(
+        editor.readline("> "),
+        Err(crate::error::ReadlineError::Validation(_))
+    ));
+    assert_eq!(" c", editor.readline("> ").unwrap());
+    assert!(matches!(
+        editor.readline("> "),
+        Err(crate::error::ReadlineError::Eof)
+    ));
+    assert_eq!(2, editor.history().len());
+
+    // unterminated input
+    editor.set_script(Cursor::new("(a\nb\n"));
+    match editor.readline("> ") {
+        Err(crate::error::ReadlineError::Validation(msg)) => {
+            assert_eq!(r#"incomplete input at end of script: "(a\nb\n""#, msg);
+        }
+        r => panic!("unexpected result: {r:?}"),
+    }
+    assert!(matches!(
+        editor.readline("> "),
+        Err(crate::error::ReadlineError::Eof)
+    ));
+}
+
+#[test]
+fn test_script_file() -> Result<()> {
+    use std::fs::File;
+    use std::io::{BufReader, Write as _};
+
+    let mut script = tempfile::NamedTempFile::new()?;
+    script.write_all(b"first\nsecond\n")?;
+    let config = Config::builder().history_ignore_space(true).build();
+    let mut editor = DefaultEditor::with_config(config)?;
+    editor.set_script(BufReader::new(File::open(script.path())?));
+    assert_eq!("first", editor.readline("> ")?);
+    assert_eq!("second", editor.readline("> ")?);
+    assert!(editor.readline("> ").is_err());
+    Ok(())
+}
@@ -0,0 +1,274 @@
+//! Run an [`Editor`] without a terminal, for end-to-end tests of helpers,
+//! bindings and validators (requires the `testing` feature).
+//!
+//! Keys are read from a script and the escape sequences emitted are
+//! interpreted into a virtual [`Screen`]:
+//! ```
+//! use rustyline::testing::HeadlessEditor;
+//! use rustyline::{Config, KeyCode, KeyEvent, Modifiers};
+//!
+//! let mut rl = HeadlessEditor::<()>::headless(Config::default());
+//! let outcome = rl.run(
+//!     "> ",
+//!     [
+//!         KeyEvent::from('h'),
+//!         KeyEvent::from('i'),
+//!         KeyEvent(KeyCode::Enter, Modifiers::NONE),
+//!     ],
+//! );
+//! assert_eq!("hi", outcome.line?);
+//! assert_eq!("> hi", outcome.screen.to_string());
+//! assert_eq!((1, 0), outcome.screen.cursor());
+//! # Ok::<(), rustyline::error::ReadlineError>(())
+//! ```
+use crate::config::Config;
+use crate::history::{DefaultHistory, History};
+use crate::keys::KeyEvent;
+pub use crate::tty::headless::{
+    HeadlessMode, HeadlessTerminal, Screen, ScreenRenderer, ScriptPrinter, ScriptReader,
+};
+use crate::{Editor, Helper, Prompt, Result};
+
+/// Editor reading keys from a script and drawing into a virtual [`Screen`]
+pub type HeadlessEditor<H, I = DefaultHistory> = Editor<H, I, HeadlessTerminal>;
+
+/// Result of [`Editor::run`]
+#[derive(Debug)]
+#[must_use]
+pub struct Outcome {
+    /// Accepted line (or error like
+    /// [`ReadlineError::Eof`](crate::error::ReadlineError::Eof)
+    /// when the script ends before the line is accepted)
+    pub line: Result<String>,
+    /// What the user sees once `readline` returns
+    pub screen: Screen,
+}
+
+impl<H: Helper> Editor<H, DefaultHistory, HeadlessTerminal> {
+    /// Create a headless editor with a 80x24 virtual screen.
+    pub fn headless(config: Config) -> Self {
+        let history = DefaultHistory::with_config(&config);
+        Self::headless_with_history(config, history)
+    }
+}
+
+impl<H: Helper, I: History> Editor<H, I, HeadlessTerminal> {
+    /// Create a headless editor with a custom history impl.
+    pub fn headless_with_history(config: Config, history: I) -> Self {
+        Self::with_term(config, history, HeadlessTerminal::default())
+    }
+
+    /// Append `keys` to the script consumed by [`Editor::readline`].
+    pub fn push_keys<K: IntoIterator<Item = KeyEvent>>(&mut self, keys: K) {
+        self.term.push_keys(keys);
+    }
+
+    /// Append a resize of the virtual screen to the script: it is handled
+    /// by [`Editor::readline`] like a `SIGWINCH`.
+    ///
+    /// Rows are truncated (not reflowed) like on most terminals.
+    pub fn push_resize(&mut self, cols: u16, rows: u16) {
+        self.term.push_resize(cols, rows);
+    }
+
+    /// Number of keys (and resizes) of the script not yet consumed
+    #[must_use]
+    pub fn pending_keys(&self) -> usize {
+        self.term.pending_keys()
+    }
+
+    /// Snapshot of the virtual screen
+    pub fn screen(&self) -> Screen {
+        self.term.screen().borrow().clone()
+    }
+
+    /// Append `keys` to the script and read a line.
+    pub fn run<P: Prompt + ?Sized, K: IntoIterator<Item = KeyEvent>>(
+        &mut self,
+        prompt: &P,
+        keys: K,
+    ) -> Outcome {
+        self.push_keys(keys);
+        let line = self.readline(prompt);
+        Outcome {
+            line,
+            screen: self.screen(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HeadlessEditor, Screen};
+    use crate::completion::Completer;
+    use crate::config::{CompletionType, Config};
+    use crate::error::ReadlineError;
+    use crate::highlight::Highlighter;
+    use crate::hint::Hinter;
+    use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
+    use crate::validate::{ValidationContext, ValidationResult, Validator};
+    use crate::{Context, ExternalPrinter as _, Helper, Result};
+
+    fn keys(s: &str) -> impl Iterator<Item = E> + '_ {
+        s.chars().map(E::from)
+    }
+
+    #[test]
+    fn wrapped_line() {
+        let mut rl = HeadlessEditor::<()>::headless(Config::default());
+        let text = "0123456789".repeat(10);
+        let outcome = rl.run("> ", keys(&text).chain([E::ENTER]));
+        assert_eq!(text, outcome.line.unwrap());
+        assert_eq!(
+            vec![format!("> {}", &text[..78]), text[78..].to_owned()],
+            outcome.screen.rows()[..2]
+        );
+        assert_eq!((2, 0), outcome.screen.cursor());
+        assert_eq!(0, rl.pending_keys());
+    }
+
+    #[test]
+    fn end_of_script() {
+        let mut rl = HeadlessEditor::<()>::headless(Config::default());
+        let outcome = rl.run("> ", keys("abc").chain([E(K::Left, M::NONE)]));
+        assert!(matches!(outcome.line, Err(ReadlineError::Eof)));
+        assert_eq!("> abc", outcome.screen.row(0));
+    }
+
+    #[test]
+    fn successive_lines() {
+        let mut rl = HeadlessEditor::<()>::headless(Config::default());
+        rl.push_keys(keys("a\rb\r"));
+        assert_eq!("a", rl.readline("> ").unwrap());
+        assert_eq!("b", rl.readline("> ").unwrap());
+        assert_eq!("> a\n> b", rl.screen().to_string());
+        assert_eq!((2, 0), rl.screen().cursor());
+    }
+
+    #[test]
+    fn external_print() {
+        let mut rl = HeadlessEditor::<()>::headless(Config::default());
+        let mut printer = rl.create_external_printer().unwrap();
+        printer.print("message".to_owned()).unwrap();
+        let outcome = rl.run("> ", keys("ok\r"));
+        assert_eq!("ok", outcome.line.unwrap());
+        assert_eq!("message\n> ok", outcome.screen.to_string());
+    }
+
+    struct TestHelper;
+    impl Completer for TestHelper {
+        type Candidate = String;
+
+        fn complete(&self, _: &str, _: usize, _: &Context<'_>) -> Result<(usize, Vec<String>)> {
+            Ok((0, vec!["alpha".to_owned(), "beta".to_owned()]))
+        }
+    }
+    impl Hinter for TestHelper {
+        type Hint = String;
+
+        fn hint(&self, line: &str, _: usize, _: &Context<'_>) -> Option<String> {
+            (line == "he").then(|| "llo".to_owned())
+        }
+    }
+    impl Highlighter for TestHelper {}
+    impl Validator for TestHelper {
+        fn validate(&self, ctx: &mut ValidationContext) -> Result<ValidationResult> {
+            Ok(if ctx.input().ends_with('\\') {
+                ValidationResult::Incomplete
+            } else {
+                ValidationResult::Valid(None)
+            })
+        }
+    }
+    impl Helper for TestHelper {}
+
+    #[test]
+    fn hint() {
+        let mut rl = HeadlessEditor::headless(Config::default());
+        rl.set_helper(Some(TestHelper));
+        rl.push_keys(keys("he"));
+        assert!(rl.readline("> ").is_err());
+        assert_eq!("> hello", rl.screen().row(0));
+    }
+
+    #[test]
+    fn completion_list() {
+        let config = Config::builder()
+            .completion_type(CompletionType::List)
+            .build();
+        let mut rl = HeadlessEditor::headless(config);
+        rl.set_helper(Some(TestHelper));
+        let outcome = rl.run("> ", [E(K::Tab, M::NONE), E(K::Tab, M::NONE)]);
+        assert!(outcome.line.is_err());
+        assert_eq!(">\nalpha  beta\n>", outcome.screen.to_string());
+    }
+
+    #[test]
+    fn multiline() {
+        let mut rl = HeadlessEditor::headless(Config::default());
+        rl.set_helper(Some(TestHelper));
+        let outcome = rl.run("> ", keys("a\\\rb\r"));
+        assert_eq!("a\\\nb", outcome.line.unwrap());
+        assert_eq!("> a\\\nb", outcome.screen.to_string());
+    }
+
+    #[test]
+    fn escape_sequences() {
+        let mut screen = Screen::new(10, 3);
+        screen.print("\x1b[1mabc\x1b[0m\ndef\x1b[1A\x1b[2Dx");
+        assert_eq!("axc\ndef", screen.to_string());
+        assert_eq!((0, 2), screen.cursor());
+        screen.print("\x1b[2;3H\x1b[K");
+        assert_eq!("axc\nde", screen.to_string());
+        screen.print("\x1b[H\x1b[J");
+        assert_eq!("", screen.to_string());
+        assert_eq!((0, 0), screen.cursor());
+    }
+
+    #[test]
+    fn wrap_and_scroll() {
+        let mut screen = Screen::new(4, 2);
+        screen.print("abcdefgh\nij");
+        assert_eq!(vec!["efgh", "ij"], screen.rows());
+        assert_eq!((1, 2), screen.cursor());
+    }
+
+    #[test]
+    fn resize() {
+        let mut rl = HeadlessEditor::<()>::headless(Config::default());
+        let text = "0123456789".repeat(3);
+        rl.push_keys(keys(&text));
+        rl.push_resize(20, 10);
+        rl.push_keys(keys("a"));
+        rl.push_resize(40, 10);
+        let outcome = rl.run("> ", [E::ENTER]);
+        assert_eq!(format!("{text}a"), outcome.line.unwrap());
+        assert_eq!((40, 10), outcome.screen.size());
+        assert_eq!(format!("> {text}a"), outcome.screen.row(0));
+        assert_eq!(0, rl.pending_keys());
+    }
+
+    #[test]
+    fn resize_narrower() {
+        let mut rl = HeadlessEditor::<()>::headless(Config::default());
+        let text = "0123456789".repeat(3);
+        rl.push_keys(keys(&text));
+        rl.push_resize(20, 10);
+        let outcome = rl.run("> ", keys("a"));
+        assert!(outcome.line.is_err());
+        assert_eq!(
+            vec![format!("> {}", &text[..18]), format!("{}a", &text[18..])],
+            outcome.screen.rows()[..2]
+        );
+    }
+
+    #[test]
+    fn clear_screen() {
+        let mut rl = HeadlessEditor::<()>::headless(Config::default());
+        rl.push_keys(keys("a\r"));
+        assert_eq!("a", rl.readline("> ").unwrap());
+        let outcome = rl.run("> ", keys("b").chain([E::ctrl('L'), E::ENTER]));
+        assert_eq!("b", outcome.line.unwrap());
+        assert_eq!("> b", outcome.screen.to_string());
+    }
+}
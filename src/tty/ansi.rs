@@ -0,0 +1,245 @@
+//! ANSI escape sequences used to render prompt, line and cursor on VT100
+//! compatible terminals
+use std::borrow::Cow;
+use std::cmp;
+use std::fmt::Write as _;
+
+use unicode_segmentation::UnicodeSegmentation as _;
+
+use crate::highlight::Highlighter;
+use crate::layout::{GraphemeClusterMode, Layout, Position, Unit};
+use crate::line_buffer::LineBuffer;
+use crate::{Prompt, Result};
+
+pub const BRACKETED_PASTE_ON: &str = "\x1b[?2004h";
+pub const BRACKETED_PASTE_OFF: &str = "\x1b[?2004l";
+pub const MOUSE_ON: &str = "\x1b[?1000h\x1b[?1006h";
+pub const MOUSE_OFF: &str = "\x1b[?1006l\x1b[?1000l";
+/// Push "disambiguate escape codes" flag (kitty keyboard protocol)
+pub const KITTY_KEYBOARD_PUSH: &str = "\x1b[>1u";
+/// Push "disambiguate escape codes" and "report event types" flags
+pub const KITTY_KEYBOARD_RELEASE_PUSH: &str = "\x1b[>3u";
+pub const KITTY_KEYBOARD_POP: &str = "\x1b[<u";
+pub const BEGIN_SYNCHRONIZED_UPDATE: &str = "\x1b[?2026h";
+pub const END_SYNCHRONIZED_UPDATE: &str = "\x1b[?2026l";
+
+/// Escape sequences are accumulated in `buffer` until it is written to the
+/// terminal.
+#[derive(Debug)]
+pub struct Ansi {
+    pub buffer: String,
+    pub cols: Unit, // Number of columns in terminal
+    pub tab_stop: Unit,
+    pub colors_enabled: bool,
+    pub horizontal_scroll: bool,
+    pub grapheme_cluster_mode: GraphemeClusterMode,
+}
+
+impl Ansi {
+    pub fn new(
+        cols: Unit,
+        tab_stop: Unit,
+        colors_enabled: bool,
+        horizontal_scroll: bool,
+        grapheme_cluster_mode: GraphemeClusterMode,
+    ) -> Self {
+        Self {
+            buffer: String::with_capacity(1024),
+            cols,
+            tab_stop,
+            colors_enabled,
+            horizontal_scroll,
+            grapheme_cluster_mode,
+        }
+    }
+
+    pub fn clear_old_rows(&mut self, layout: &Layout) {
+        let current_row = layout.screen_cursor_row();
+        let old_rows = layout.screen_end_row();
+        // old_rows < cursor_row if the prompt spans multiple lines and if
+        // this is the default State.
+        let cursor_row_movement = old_rows.saturating_sub(current_row);
+        // move the cursor down as required
+        if cursor_row_movement > 0 {
+            write!(self.buffer, "\x1b[{cursor_row_movement}B").unwrap();
+        }
+        // clear old rows
+        for _ in 0..old_rows {
+            self.buffer.push_str("\r\x1b[K\x1b[A");
+        }
+        // clear the line
+        self.buffer.push_str("\r\x1b[K");
+    }
+
+    pub fn move_cursor(&mut self, old: Position, new: Position) -> Result<()> {
+        let row_ordering = new.row.cmp(&old.row);
+        if row_ordering == cmp::Ordering::Greater {
+            // move down
+            let row_shift = new.row - old.row;
+            if row_shift == 1 {
+                self.buffer.push_str("\x1b[B");
+            } else {
+                write!(self.buffer, "\x1b[{row_shift}B")?;
+            }
+        } else if row_ordering == cmp::Ordering::Less {
+            // move up
+            let row_shift = old.row - new.row;
+            if row_shift == 1 {
+                self.buffer.push_str("\x1b[A");
+            } else {
+                write!(self.buffer, "\x1b[{row_shift}A")?;
+            }
+        }
+        let col_ordering = new.col.cmp(&old.col);
+        if col_ordering == cmp::Ordering::Greater {
+            // move right
+            let col_shift = new.col - old.col;
+            if col_shift == 1 {
+                self.buffer.push_str("\x1b[C");
+            } else {
+                write!(self.buffer, "\x1b[{col_shift}C")?;
+            }
+        } else if col_ordering == cmp::Ordering::Less {
+            // move left
+            let col_shift = old.col - new.col;
+            if col_shift == 1 {
+                self.buffer.push_str("\x1b[D");
+            } else {
+                write!(self.buffer, "\x1b[{col_shift}D")?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn refresh_line<P: Prompt + ?Sized>(
+        &mut self,
+        prompt: &P,
+        line: &LineBuffer,
+        hint: Option<&str>,
+        old_layout: Option<&Layout>,
+        new_layout: &Layout,
+        highlighter: Option<&dyn Highlighter>,
+    ) -> Result<()> {
+        let default_prompt = new_layout.default_prompt;
+        let cursor = new_layout.cursor;
+        let end_pos = new_layout.end;
+
+        if let Some(old_layout) = old_layout {
+            self.clear_old_rows(old_layout);
+        }
+        let start = self.buffer.len();
+
+        // display the prompt
+        if let Some(highlighter) = highlighter {
+            self.buffer
+                .push_str(&highlighter.highlight_prompt(prompt.styled(), default_prompt));
+        } else {
+            self.buffer.push_str(prompt.raw());
+        }
+        let line_start = self.buffer.len();
+        // display the input line (and continuation prompts)
+        let input = if let Some(highlighter) = highlighter {
+            highlighter.highlight(line, line.pos())
+        } else {
+            Cow::Borrowed(line.as_str())
+        };
+        self.buffer.push_str(&super::insert_continuation(
+            prompt,
+            input,
+            default_prompt,
+            highlighter.is_some(),
+            highlighter,
+        ));
+        // display hint
+        if let Some(hint) = hint {
+            if let Some(highlighter) = highlighter {
+                self.buffer.push_str(&highlighter.highlight_hint(hint));
+            } else {
+                self.buffer.push_str(hint);
+            }
+        }
+        if let Some(hscroll) = new_layout.hscroll {
+            // display only the columns around the cursor
+            let content = self.buffer.split_off(line_start);
+            self.buffer
+                .push_str(&super::clip_to_hscroll(&content, new_layout, hscroll));
+        } else if new_layout.viewport.is_some() {
+            // display only the rows around the cursor
+            let content = self.buffer.split_off(start);
+            self.buffer.push_str(&super::clip_to_viewport(
+                &content,
+                new_layout,
+                self.cols,
+                Some(self.tab_stop),
+                highlighter,
+            ));
+        }
+        // we have to generate our own newline on line wrap
+        if end_pos.col == 0
+            && end_pos.row > 0
+            && new_layout.rows_below() == 0
+            && !super::ends_with_newline(prompt, line, hint)
+        {
+            self.buffer.push('\n');
+        }
+        let mut current_row = new_layout.screen_end_row();
+        // display the right prompt
+        let right_prompt = if highlighter.is_some() {
+            prompt.right_styled()
+        } else {
+            prompt.right_raw()
+        };
+        if let Some((pos, right_prompt)) = new_layout.right_prompt.zip(right_prompt) {
+            let row = new_layout.screen_row(pos.row);
+            if current_row > row {
+                write!(self.buffer, "\x1b[{}A", current_row - row)?;
+            }
+            write!(self.buffer, "\r\x1b[{}C{right_prompt}", pos.col)?;
+            current_row = row;
+        }
+        // position the cursor
+        let cursor_row = new_layout.screen_cursor_row();
+        // move the cursor up / down as required
+        if current_row > cursor_row {
+            write!(self.buffer, "\x1b[{}A", current_row - cursor_row)?;
+        } else if current_row < cursor_row {
+            write!(self.buffer, "\x1b[{}B", cursor_row - current_row)?;
+        }
+        // position the cursor within the line
+        if cursor.col > 0 {
+            write!(self.buffer, "\r\x1b[{}C", cursor.col)?;
+        } else {
+            self.buffer.push('\r');
+        }
+        Ok(())
+    }
+
+    /// Control characters are treated as having zero width.
+    /// Characters with 2 column width are correctly handled (not split).
+    pub fn calculate_position(&self, s: &str, orig: Position) -> Position {
+        let mut pos = orig;
+        let mut esc_seq = 0;
+        for c in s.graphemes(true) {
+            if c == "\n" {
+                pos.row += 1;
+                pos.col = 0;
+                continue;
+            }
+            let cw = if c == "\t" {
+                self.tab_stop - (pos.col % self.tab_stop)
+            } else {
+                super::width(self.grapheme_cluster_mode, c, &mut esc_seq)
+            };
+            pos.col += cw;
+            if pos.col > self.cols {
+                pos.row += 1;
+                pos.col = cw;
+            }
+        }
+        if pos.col == self.cols {
+            pos.col = 0;
+            pos.row += 1;
+        }
+        pos
+    }
+}
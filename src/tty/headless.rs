@@ -0,0 +1,638 @@
+//! Headless terminal: keys are read from a script and the output is drawn
+//! into an in-memory screen by interpreting the escape sequences (see
+//! [`crate::testing`]).
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::rc::Rc;
+
+use unicode_segmentation::UnicodeSegmentation as _;
+
+use super::ansi::Ansi;
+use super::{Event, ExternalPrinter, RawMode, RawReader, Renderer, Term};
+use crate::config::{ColorMode, Config};
+use crate::error::{ReadlineError, Signal};
+use crate::highlight::Highlighter;
+use crate::keys::{KeyCode as K, KeyEvent, Modifiers as M};
+use crate::layout::{GraphemeClusterMode, Layout, Position, Unit};
+use crate::line_buffer::LineBuffer;
+use crate::{Cmd, Prompt, Result};
+
+const TAB_STOP: usize = 8;
+
+/// In-memory grid of cells with a cursor, like a terminal screen.
+///
+/// Rows are scrolled up when the cursor goes below the last row.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Screen {
+    cols: usize,
+    rows: usize,
+    /// One grapheme per cell, empty for the second half of a wide grapheme
+    cells: Vec<Vec<String>>,
+    row: usize,
+    col: usize,
+    /// Cursor is on the last column, after a grapheme has been written there
+    pending_wrap: bool,
+}
+
+impl Screen {
+    /// Create a blank screen with `cols` columns and `rows` rows.
+    #[must_use]
+    pub fn new(cols: Unit, rows: Unit) -> Self {
+        let cols = usize::from(cols.max(1));
+        let rows = usize::from(rows.max(1));
+        Self {
+            cols,
+            rows,
+            cells: vec![blank_row(cols); rows],
+            row: 0,
+            col: 0,
+            pending_wrap: false,
+        }
+    }
+
+    /// Number of columns and rows
+    #[must_use]
+    pub fn size(&self) -> (Unit, Unit) {
+        (
+            Unit::try_from(self.cols).unwrap(),
+            Unit::try_from(self.rows).unwrap(),
+        )
+    }
+
+    /// Cursor position: row and column (zero-based)
+    #[must_use]
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    /// Text of the `row`, without trailing spaces
+    #[must_use]
+    pub fn row(&self, row: usize) -> String {
+        self.cells
+            .get(row)
+            .map(|cells| cells.concat().trim_end().to_owned())
+            .unwrap_or_default()
+    }
+
+    /// Text of all rows, without trailing spaces
+    #[must_use]
+    pub fn rows(&self) -> Vec<String> {
+        (0..self.rows).map(|row| self.row(row)).collect()
+    }
+
+    /// Resize the screen: content is kept (and truncated) from the top left
+    /// corner, the cursor is kept inside.
+    pub fn resize(&mut self, cols: Unit, rows: Unit) {
+        let cols = usize::from(cols.max(1));
+        let rows = usize::from(rows.max(1));
+        for cells in &mut self.cells {
+            cells.resize(cols, " ".to_owned());
+            if cols > 1 && cells.last().is_some_and(String::is_empty) {
+                // wide grapheme cut
+                cells[cols - 1] = " ".to_owned();
+                cells[cols - 2] = " ".to_owned();
+            }
+        }
+        self.cells.resize(rows, blank_row(cols));
+        self.cols = cols;
+        self.rows = rows;
+        self.row = self.row.min(rows - 1);
+        self.col = self.col.min(cols - 1);
+        self.pending_wrap = false;
+    }
+
+    /// Interpret `s` like a VT100 compatible terminal would: text is printed
+    /// at the cursor position and wraps at the last column, `\n` moves the
+    /// cursor to the start of the next row, `\r` to the start of the current
+    /// row. Supported CSI sequences are cursor movements (`A`, `B`, `C`, `D`,
+    /// `G`, `H`) and erasures (`J`, `K`), others (like SGR) are ignored.
+    pub fn print(&mut self, s: &str) {
+        let mut csi: Option<String> = None;
+        let mut esc = false;
+        for g in s.graphemes(true) {
+            if let Some(ref mut params) = csi {
+                match g.chars().next() {
+                    Some(c @ '\x40'..='\x7e') if g.len() == 1 => {
+                        let params = std::mem::take(params);
+                        csi = None;
+                        self.csi(&params, c);
+                    }
+                    _ => params.push_str(g),
+                }
+                continue;
+            } else if esc {
+                // CSI or two-character sequence
+                esc = false;
+                if g == "[" {
+                    csi = Some(String::new());
+                }
+                continue;
+            }
+            match g {
+                "\x1b" => esc = true,
+                "\n" | "\r\n" => {
+                    self.carriage_return();
+                    self.line_feed();
+                }
+                "\r" => self.carriage_return(),
+                "\t" => {
+                    let next = (self.col / TAB_STOP + 1) * TAB_STOP;
+                    self.col = next.min(self.cols - 1);
+                    self.pending_wrap = false;
+                }
+                _ if g.chars().all(char::is_control) => {}
+                _ => self.put(g),
+            }
+        }
+    }
+
+    /// Control Sequence Introducer: `ESC [ params final`
+    fn csi(&mut self, params: &str, final_byte: char) {
+        if params.starts_with(['?', '>', '=']) {
+            // private modes (like synchronized output)
+            return;
+        }
+        let mut args = params
+            .split(';')
+            .map(|arg| arg.parse::<usize>().unwrap_or(0));
+        let mut arg = |default: usize| args.next().filter(|&n| n != 0).unwrap_or(default);
+        let (row, col) = (self.row, self.col);
+        match final_byte {
+            'A' => self.move_to(row.saturating_sub(arg(1)), col),
+            'B' => self.move_to(row + arg(1), col),
+            'C' => self.move_to(row, col + arg(1)),
+            'D' => self.move_to(row, col.saturating_sub(arg(1))),
+            'G' => self.move_to(row, arg(1) - 1),
+            'H' | 'f' => {
+                let row = arg(1) - 1;
+                let col = arg(1) - 1;
+                self.move_to(row, col);
+            }
+            'J' => match arg(0) {
+                0 => {
+                    self.clear_to_eol();
+                    for row in row + 1..self.rows {
+                        self.clear_row(row);
+                    }
+                }
+                1 => {
+                    for row in 0..row {
+                        self.clear_row(row);
+                    }
+                    self.clear_to_cursor();
+                }
+                _ => {
+                    for row in 0..self.rows {
+                        self.clear_row(row);
+                    }
+                }
+            },
+            'K' => match arg(0) {
+                0 => self.clear_to_eol(),
+                1 => self.clear_to_cursor(),
+                _ => self.clear_row(row),
+            },
+            _ => {}
+        }
+    }
+
+    fn put(&mut self, g: &str) {
+        let width = usize::from(GraphemeClusterMode::Unicode.width(g));
+        if width == 0 || width > self.cols {
+            return;
+        }
+        if self.pending_wrap || self.col + width > self.cols {
+            self.carriage_return();
+            self.line_feed();
+        }
+        self.erase_cell(self.col);
+        self.cells[self.row][self.col] = g.to_owned();
+        for col in self.col + 1..self.col + width {
+            self.erase_cell(col);
+            self.cells[self.row][col] = String::new();
+        }
+        self.col += width;
+        if self.col == self.cols {
+            self.col -= 1;
+            self.pending_wrap = true;
+        }
+    }
+
+    /// Blank the wide grapheme overlapping `col` (if any)
+    fn erase_cell(&mut self, col: usize) {
+        let cells = &mut self.cells[self.row];
+        let mut start = col;
+        while start > 0 && cells[start].is_empty() {
+            start -= 1;
+        }
+        let mut end = col + 1;
+        while end < self.cols && cells[end].is_empty() {
+            end += 1;
+        }
+        if end - start > 1 {
+            cells[start..end].fill(" ".to_owned());
+        }
+    }
+
+    fn carriage_return(&mut self) {
+        self.col = 0;
+        self.pending_wrap = false;
+    }
+
+    fn line_feed(&mut self) {
+        self.pending_wrap = false;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+        } else {
+            // scroll up
+            self.cells.remove(0);
+            self.cells.push(blank_row(self.cols));
+        }
+    }
+
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.row = row.min(self.rows - 1);
+        self.col = col.min(self.cols - 1);
+        self.pending_wrap = false;
+    }
+
+    /// Clear from the cursor to the end of the row
+    fn clear_to_eol(&mut self) {
+        self.erase_cell(self.col);
+        self.cells[self.row][self.col..].fill(" ".to_owned());
+    }
+
+    /// Clear from the start of the row to the cursor (included)
+    fn clear_to_cursor(&mut self) {
+        self.erase_cell(self.col);
+        self.cells[self.row][..=self.col].fill(" ".to_owned());
+    }
+
+    fn clear_row(&mut self, row: usize) {
+        if let Some(cells) = self.cells.get_mut(row) {
+            cells.fill(" ".to_owned());
+        }
+    }
+}
+
+fn blank_row(cols: usize) -> Vec<String> {
+    vec![" ".to_owned(); cols]
+}
+
+/// Rows (trailing blank rows excluded)
+impl fmt::Display for Screen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = self.rows();
+        let len = rows
+            .iter()
+            .rposition(|row| !row.is_empty())
+            .map_or(0, |i| i + 1);
+        for (i, row) in rows[..len].iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            f.write_str(row)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Screen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Screen")
+            .field("size", &self.size())
+            .field("cursor", &self.cursor())
+            .field("rows", &self.rows())
+            .finish()
+    }
+}
+
+/// Scripted input
+#[derive(Debug)]
+enum Step {
+    Key(KeyEvent),
+    /// Resize the screen (columns, rows) like `SIGWINCH`
+    Resize(Unit, Unit),
+}
+
+/// Steps and messages not yet consumed
+#[derive(Debug, Default)]
+struct Script {
+    steps: VecDeque<Step>,
+    messages: VecDeque<String>,
+}
+
+/// No raw mode to restore
+#[derive(Debug)]
+pub struct HeadlessMode;
+
+impl RawMode for HeadlessMode {
+    fn disable_raw_mode(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Read keys from the script
+#[derive(Debug)]
+pub struct ScriptReader {
+    script: Rc<RefCell<Script>>,
+    screen: Rc<RefCell<Screen>>,
+}
+
+impl ScriptReader {
+    fn next(&mut self) -> Result<KeyEvent> {
+        let step = self.script.borrow_mut().steps.pop_front();
+        match step {
+            Some(Step::Key(key)) => Ok(key),
+            Some(Step::Resize(cols, rows)) => {
+                self.screen.borrow_mut().resize(cols, rows);
+                Err(ReadlineError::Signal(Signal::Resize))
+            }
+            None => Err(ReadlineError::Eof),
+        }
+    }
+}
+
+impl RawReader for ScriptReader {
+    type Buffer = ();
+
+    fn wait_for_input(&mut self, single_esc_abort: bool) -> Result<Event> {
+        let msg = self.script.borrow_mut().messages.pop_front();
+        match msg {
+            Some(msg) => Ok(Event::ExternalPrint(msg)),
+            None => self.next_key(single_esc_abort).map(Event::KeyPress),
+        }
+    }
+
+    fn next_key(&mut self, _: bool) -> Result<KeyEvent> {
+        self.next()
+    }
+
+    #[cfg(unix)]
+    fn next_char(&mut self) -> Result<char> {
+        match self.next()? {
+            KeyEvent(K::Char(c), M::NONE) => Ok(c),
+            KeyEvent(K::Enter, M::NONE) => Ok('\r'),
+            KeyEvent(K::Tab, M::NONE) => Ok('\t'),
+            KeyEvent(K::Esc, M::NONE) => Ok('\x1b'),
+            key => Err(ReadlineError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{key:?} is not a char"),
+            ))),
+        }
+    }
+
+    /// Keys until [`KeyCode::BracketedPasteEnd`](crate::KeyCode)
+    fn read_pasted_text(&mut self) -> Result<String> {
+        let mut text = String::new();
+        loop {
+            match self.next()? {
+                KeyEvent(K::BracketedPasteEnd, _) => return Ok(text),
+                KeyEvent(K::Char(c), _) => text.push(c),
+                KeyEvent(K::Enter, _) => text.push('\n'),
+                KeyEvent(K::Tab, _) => text.push('\t'),
+                _ => {}
+            }
+        }
+    }
+
+    fn find_binding(&self, _: &KeyEvent) -> Option<Cmd> {
+        None
+    }
+
+    fn unbuffer(self) -> Option<()> {
+        None
+    }
+}
+
+/// Interpret the escape sequences into the [`Screen`]
+#[derive(Debug)]
+pub struct ScreenRenderer {
+    screen: Rc<RefCell<Screen>>,
+    ansi: Ansi,
+}
+
+impl ScreenRenderer {
+    fn flush(&mut self) {
+        self.screen.borrow_mut().print(&self.ansi.buffer);
+        self.ansi.buffer.clear();
+    }
+}
+
+impl Renderer for ScreenRenderer {
+    type Reader = ScriptReader;
+
+    fn move_cursor(&mut self, old: Position, new: Position) -> Result<()> {
+        self.ansi.move_cursor(old, new)?;
+        self.flush();
+        Ok(())
+    }
+
+    fn refresh_line<P: Prompt + ?Sized>(
+        &mut self,
+        prompt: &P,
+        line: &LineBuffer,
+        hint: Option<&str>,
+        old_layout: Option<&Layout>,
+        new_layout: &Layout,
+        highlighter: Option<&dyn Highlighter>,
+    ) -> Result<()> {
+        self.ansi
+            .refresh_line(prompt, line, hint, old_layout, new_layout, highlighter)?;
+        self.flush();
+        Ok(())
+    }
+
+    fn calculate_position(&self, s: &str, orig: Position) -> Position {
+        self.ansi.calculate_position(s, orig)
+    }
+
+    fn write_and_flush(&mut self, buf: &str) -> Result<()> {
+        self.screen.borrow_mut().print(buf);
+        Ok(())
+    }
+
+    fn beep(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn clear_screen(&mut self) -> Result<()> {
+        self.write_and_flush("\x1b[H\x1b[J")
+    }
+
+    fn clear_rows(&mut self, layout: &Layout) -> Result<()> {
+        self.ansi.clear_old_rows(layout);
+        self.flush();
+        Ok(())
+    }
+
+    fn clear_to_eol(&mut self) -> Result<()> {
+        self.write_and_flush("\x1b[K")
+    }
+
+    fn update_size(&mut self) {
+        self.ansi.cols = self.screen.borrow().size().0;
+    }
+
+    fn get_columns(&self) -> Unit {
+        self.ansi.cols
+    }
+
+    fn get_rows(&self) -> Unit {
+        self.screen.borrow().size().1
+    }
+
+    fn colors_enabled(&self) -> bool {
+        self.ansi.colors_enabled
+    }
+
+    fn horizontal_scroll(&self) -> bool {
+        self.ansi.horizontal_scroll
+    }
+
+    fn grapheme_cluster_mode(&self) -> GraphemeClusterMode {
+        self.ansi.grapheme_cluster_mode
+    }
+
+    /// Like the `ESC [ 6n` request on a real terminal
+    fn move_cursor_at_leftmost(&mut self, _: &mut ScriptReader) -> Result<()> {
+        let mut screen = self.screen.borrow_mut();
+        if screen.col != 0 || screen.pending_wrap {
+            screen.print("\n");
+        }
+        Ok(())
+    }
+
+    fn cursor_row(&mut self, _: &mut ScriptReader) -> Result<Option<Unit>> {
+        Ok(Unit::try_from(self.screen.borrow().row).ok())
+    }
+}
+
+/// Queue messages printed between key presses
+#[derive(Debug)]
+pub struct ScriptPrinter {
+    script: Rc<RefCell<Script>>,
+}
+
+impl ExternalPrinter for ScriptPrinter {
+    fn print(&mut self, msg: String) -> Result<()> {
+        self.script.borrow_mut().messages.push_back(msg);
+        Ok(())
+    }
+}
+
+/// Headless terminal: keys are read from a script and the output is drawn
+/// into a [`Screen`].
+#[derive(Debug)]
+pub struct HeadlessTerminal {
+    script: Rc<RefCell<Script>>,
+    screen: Rc<RefCell<Screen>>,
+}
+
+impl Default for HeadlessTerminal {
+    /// 80x24 blank screen and empty script
+    fn default() -> Self {
+        Self {
+            script: Rc::default(),
+            screen: Rc::new(RefCell::new(Screen::new(80, 24))),
+        }
+    }
+}
+
+impl HeadlessTerminal {
+    /// Append `keys` to the script
+    pub(crate) fn push_keys<I: IntoIterator<Item = KeyEvent>>(&self, keys: I) {
+        self.script
+            .borrow_mut()
+            .steps
+            .extend(keys.into_iter().map(Step::Key));
+    }
+
+    /// Append a resize of the screen to the script
+    pub(crate) fn push_resize(&self, cols: Unit, rows: Unit) {
+        self.script
+            .borrow_mut()
+            .steps
+            .push_back(Step::Resize(cols, rows));
+    }
+
+    /// Number of keys and resizes not yet consumed
+    pub(crate) fn pending_keys(&self) -> usize {
+        self.script.borrow().steps.len()
+    }
+
+    pub(crate) fn screen(&self) -> &RefCell<Screen> {
+        &self.screen
+    }
+}
+
+impl Term for HeadlessTerminal {
+    type Buffer = ();
+    type CursorGuard = ();
+    type ExternalPrinter = ScriptPrinter;
+    type KeyMap = ();
+    type Mode = HeadlessMode;
+    type Reader = ScriptReader;
+    type Writer = ScreenRenderer;
+
+    fn new(_: &Config) -> Result<Self> {
+        Ok(Self::default())
+    }
+
+    fn is_unsupported(&self) -> bool {
+        false
+    }
+
+    fn is_input_tty(&self) -> bool {
+        true
+    }
+
+    fn is_output_tty(&self) -> bool {
+        true
+    }
+
+    fn enable_raw_mode(&mut self, _: &Config) -> Result<(HeadlessMode, ())> {
+        Ok((HeadlessMode, ()))
+    }
+
+    fn create_reader(&self, _: Option<()>, _: &Config, _: ()) -> Result<ScriptReader> {
+        Ok(ScriptReader {
+            script: self.script.clone(),
+            screen: self.screen.clone(),
+        })
+    }
+
+    fn create_writer(&self, config: &Config) -> ScreenRenderer {
+        ScreenRenderer {
+            screen: self.screen.clone(),
+            ansi: Ansi::new(
+                self.screen.borrow().size().0,
+                Unit::from(config.tab_stop()),
+                config.color_mode() != ColorMode::Disabled,
+                config.horizontal_scroll(),
+                config.grapheme_cluster_mode(),
+            ),
+        }
+    }
+
+    fn writeln(&self) -> Result<()> {
+        self.screen.borrow_mut().print("\n");
+        Ok(())
+    }
+
+    fn create_external_printer(&mut self) -> Result<ScriptPrinter> {
+        Ok(ScriptPrinter {
+            script: self.script.clone(),
+        })
+    }
+
+    fn set_cursor_visibility(&mut self, _: bool) -> Result<Option<()>> {
+        Ok(None)
+    }
+
+    #[cfg(unix)]
+    fn suspend(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -3,12 +3,14 @@
 /// Unsupported Terminals that don't support RAW mode
 const UNSUPPORTED_TERM: [&str; 3] = ["dumb", "cons25", "emacs"];
 
+use std::borrow::Cow;
+
 use crate::config::Config;
 use crate::highlight::Highlighter;
 use crate::keys::KeyEvent;
-use crate::layout::{GraphemeClusterMode, Layout, Position, Unit};
+use crate::layout::{GraphemeClusterMode, HScroll, Layout, Position, Unit};
 use crate::line_buffer::LineBuffer;
-use crate::{Cmd, Prompt, Result};
+use crate::{Cmd, Prompt, Result, RightPromptPosition};
 
 /// Terminal state
 pub trait RawMode: Sized {
@@ -17,8 +19,13 @@ pub trait RawMode: Sized {
 }
 
 /// Input event
+#[non_exhaustive]
 pub enum Event {
+    /// Key pressed
     KeyPress(KeyEvent),
+    /// Only reported when enabled by `Config::enable_key_release`
+    KeyRelease(KeyEvent),
+    /// Message of an [`ExternalPrinter`]
     ExternalPrint(String),
     #[cfg(target_os = "macos")]
     Timeout(bool),
@@ -26,6 +33,7 @@ pub enum Event {
 
 /// Translate bytes read from stdin to keys.
 pub trait RawReader {
+    /// Type ahead kept between two lines
     type Buffer;
     /// Blocking wait for either a key press or an external print
     fn wait_for_input(&mut self, single_esc_abort: bool) -> Result<Event>; // TODO replace calls to `next_key` by `wait_for_input` where relevant
@@ -39,13 +47,15 @@ pub trait RawReader {
     /// Check if `key` is bound to a peculiar command
     fn find_binding(&self, key: &KeyEvent) -> Option<Cmd>;
     /// Backup type ahead
-    fn unbuffer(self) -> Option<Buffer>;
+    fn unbuffer(self) -> Option<Self::Buffer>;
 }
 
 /// Display prompt, line and cursor in terminal output
 pub trait Renderer {
+    /// Reader of the same terminal
     type Reader: RawReader;
 
+    /// Move the cursor from `old` to `new` position
     fn move_cursor(&mut self, old: Position, new: Position) -> Result<()>;
 
     /// Display `prompt`, line and cursor in terminal output
@@ -62,21 +72,26 @@ pub trait Renderer {
     /// Compute layout for rendering prompt + line + some info (either hint,
     /// validation msg, ...). on the screen. Depending on screen width, line
     /// wrapping may be applied.
-    fn compute_layout(
+    fn compute_layout<P: Prompt + ?Sized>(
         &self,
+        prompt: &P,
         prompt_size: Position,
         default_prompt: bool,
         line: &LineBuffer,
         info: Option<&str>,
     ) -> Layout {
+        if let Some(layout) = self.compute_hscroll_layout(prompt_size, default_prompt, line, info) {
+            return layout;
+        }
         // calculate the desired position of the cursor
         let pos = line.pos();
-        let cursor = self.calculate_position(&line[..pos], prompt_size);
+        let cursor = self.calculate_input_position(prompt, &line[..pos], 1, prompt_size);
         // calculate the position of the end of the input line
         let mut end = if pos == line.len() {
             cursor
         } else {
-            self.calculate_position(&line[pos..], cursor)
+            let line_number = 1 + line[..pos].matches('\n').count();
+            self.calculate_input_position(prompt, &line[pos..], line_number, cursor)
         };
         if let Some(info) = info {
             end = self.calculate_position(info, end);
@@ -89,16 +104,129 @@ pub trait Renderer {
             cursor,
             end,
             has_info: info.is_some(),
+            viewport: None,
+            hscroll: None,
+            right_prompt: None,
         };
         debug_assert!(new_layout.prompt_size <= new_layout.cursor);
         debug_assert!(new_layout.cursor <= new_layout.end);
         new_layout
     }
 
+    /// Compute layout for single-line mode: line + info are not wrapped but
+    /// scrolled horizontally (see [`Layout::update_hscroll`]).
+    ///
+    /// Returns `None` when horizontal scrolling is disabled or not applicable
+    /// (multi-line input, terminal too narrow).
+    fn compute_hscroll_layout(
+        &self,
+        prompt_size: Position,
+        default_prompt: bool,
+        line: &LineBuffer,
+        info: Option<&str>,
+    ) -> Option<Layout> {
+        if !self.horizontal_scroll()
+            || line.contains('\n')
+            || info.is_some_and(|info| info.contains('\n'))
+        {
+            return None;
+        }
+        // keep the last column free to avoid line wrap
+        let width = self
+            .get_columns()
+            .checked_sub(prompt_size.col + 1)
+            .filter(|width| *width >= 4)?;
+        let gcm = self.grapheme_cluster_mode();
+        let cursor = gcm.width(&line[..line.pos()]);
+        let text_width = gcm.width(line) + info.map_or(0, |info| gcm.width(info));
+        Some(Layout {
+            grapheme_cluster_mode: gcm,
+            prompt_size,
+            default_prompt,
+            cursor: Position {
+                col: prompt_size.col + cursor,
+                row: prompt_size.row,
+            },
+            end: Position {
+                col: prompt_size.col + text_width,
+                row: prompt_size.row,
+            },
+            has_info: info.is_some(),
+            viewport: None,
+            hscroll: Some(HScroll {
+                offset: 0,
+                width,
+                text_width,
+            }),
+            right_prompt: None,
+        })
+    }
+
+    /// Compute the position where `right_prompt` is displayed on the first
+    /// or last input row (depending on `position`).
+    ///
+    /// Returns `None` when the input line (or `info`) overlaps it or when
+    /// this row is scrolled out.
+    fn compute_right_prompt(
+        &self,
+        layout: &Layout,
+        right_prompt: &str,
+        position: RightPromptPosition,
+        line: &LineBuffer,
+        info: Option<&str>,
+    ) -> Option<Position> {
+        let width = self.grapheme_cluster_mode().width(right_prompt);
+        if width == 0 {
+            return None;
+        }
+        // keep the last column free to avoid line wrap
+        let col = self.get_columns().checked_sub(width + 1)?;
+        // position of the end of the row where the right prompt is displayed
+        let end = match position {
+            RightPromptPosition::FirstRow => {
+                let first_row = |s: &str| s.find('\n').map_or(s.len(), |i| i);
+                let mut end = self.calculate_position(&line[..first_row(line)], layout.prompt_size);
+                if let Some(info) = info.filter(|_| !line.contains('\n')) {
+                    end = self.calculate_position(&info[..first_row(info)], end);
+                }
+                if end.row != layout.prompt_size.row {
+                    return None;
+                }
+                end
+            }
+            RightPromptPosition::LastRow => layout.end,
+        };
+        (layout.is_row_visible(end.row) && end.col < col).then_some(Position { col, row: end.row })
+    }
+
     /// Calculate the number of columns and rows used to display `s` on a
     /// `cols` width terminal starting at `orig`.
     fn calculate_position(&self, s: &str, orig: Position) -> Position;
 
+    /// Calculate the number of columns and rows used to display `s`, a slice
+    /// of the input starting on input line `line_number`, with a continuation
+    /// prompt after each `\n`.
+    fn calculate_input_position<P: Prompt + ?Sized>(
+        &self,
+        prompt: &P,
+        s: &str,
+        mut line_number: usize,
+        orig: Position,
+    ) -> Position {
+        let mut pos = orig;
+        for segment in s.split_inclusive('\n') {
+            pos = self.calculate_position(segment, pos);
+            if segment.ends_with('\n') {
+                line_number += 1;
+                if let Some(continuation) = prompt.continuation_raw(line_number) {
+                    pos = self.calculate_position(&continuation, pos);
+                }
+            }
+        }
+        pos
+    }
+
+    /// Write `buf` as is
     fn write_and_flush(&mut self, buf: &str) -> Result<()>;
 
     /// Beep, used for completion when there is nothing to complete or when all
@@ -120,11 +248,17 @@ pub trait Renderer {
     fn get_rows(&self) -> Unit;
     /// Check if output supports colors.
     fn colors_enabled(&self) -> bool;
+    /// Check if the line is scrolled horizontally instead of being wrapped.
+    fn horizontal_scroll(&self) -> bool;
     /// Tell how grapheme clusters are rendered.
     fn grapheme_cluster_mode(&self) -> GraphemeClusterMode;
 
     /// Make sure prompt is at the leftmost edge of the screen
     fn move_cursor_at_leftmost(&mut self, rdr: &mut Self::Reader) -> Result<()>;
+    /// Screen row where the cursor is displayed (`None` if unknown)
+    fn cursor_row(&mut self, _: &mut Self::Reader) -> Result<Option<Unit>> {
+        Ok(None)
+    }
     /// Begin synchronized update on unix platform
     fn begin_synchronized_update(&mut self) -> Result<()> {
         Ok(())
@@ -166,6 +300,177 @@ fn width(gcm: GraphemeClusterMode, s: &str, esc_seq: &mut u8) -> Unit {
     }
 }
 
+/// Insert the continuation prompt (if any) after each `\n` of the input
+/// `line` (possibly highlighted).
+fn insert_continuation<'l, P: Prompt + ?Sized>(
+    prompt: &P,
+    line: Cow<'l, str>,
+    default_prompt: bool,
+    styled: bool,
+    highlighter: Option<&dyn Highlighter>,
+) -> Cow<'l, str> {
+    if !line.contains('\n') {
+        return line;
+    }
+    let mut out = String::with_capacity(line.len());
+    let mut line_number = 1;
+    for segment in line.split_inclusive('\n') {
+        out.push_str(segment);
+        if !segment.ends_with('\n') {
+            continue;
+        }
+        line_number += 1;
+        let continuation = if styled {
+            prompt.continuation_styled(line_number)
+        } else {
+            prompt.continuation_raw(line_number)
+        };
+        if let Some(continuation) = continuation {
+            if let Some(highlighter) = highlighter {
+                out.push_str(&highlighter.highlight_prompt(&continuation, default_prompt));
+            } else {
+                out.push_str(&continuation);
+            }
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Tell if prompt + line + hint ends with a `\n` (and not with a line wrap)
+fn ends_with_newline<P: Prompt + ?Sized>(prompt: &P, line: &str, hint: Option<&str>) -> bool {
+    if let Some(hint) = hint.filter(|hint| !hint.is_empty()) {
+        hint.ends_with('\n')
+    } else if line.is_empty() {
+        prompt.raw().ends_with('\n')
+    } else {
+        line.ends_with('\n')
+            && prompt
+                .continuation_raw(1 + line.matches('\n').count())
+                .is_none_or(|continuation| continuation.is_empty() || continuation.ends_with('\n'))
+    }
+}
+
+/// Keep only the rows of `s` (prompt + line + info) displayed in `layout`
+/// viewport and draw scroll indicators.
+///
+/// ANSI escape sequences of hidden rows are kept to preserve styles.
+/// `tab_stop` should be `None` when tabs are not expanded by the renderer.
+fn clip_to_viewport(
+    s: &str,
+    layout: &Layout,
+    cols: Unit,
+    tab_stop: Option<Unit>,
+    highlighter: Option<&dyn Highlighter>,
+) -> String {
+    use unicode_segmentation::UnicodeSegmentation as _;
+    let Some(viewport) = layout.viewport else {
+        return s.to_owned();
+    };
+    let mut out = String::with_capacity(s.len());
+    let rows_above = layout.rows_above();
+    if rows_above > 0 {
+        push_scroll_indicator(&mut out, rows_above, "above", cols, highlighter);
+        out.push('\n');
+    }
+    let mut pos = Position::default();
+    let mut esc_seq = 0;
+    for c in s.graphemes(true) {
+        if c == "\n" {
+            if pos.row >= viewport.first_row && pos.row < viewport.last_row {
+                out.push('\n');
+            }
+            pos.row += 1;
+            pos.col = 0;
+            continue;
+        }
+        let cw = match tab_stop {
+            Some(tab_stop) if c == "\t" => tab_stop - (pos.col % tab_stop),
+            _ => width(layout.grapheme_cluster_mode, c, &mut esc_seq),
+        };
+        pos.col += cw;
+        if pos.col > cols {
+            pos.row += 1;
+            pos.col = cw;
+        }
+        if cw == 0 || layout.is_row_visible(pos.row) {
+            out.push_str(c);
+        }
+    }
+    let rows_below = layout.rows_below();
+    if rows_below > 0 {
+        out.push('\n');
+        push_scroll_indicator(&mut out, rows_below, "below", cols, highlighter);
+    }
+    out
+}
+
+/// Keep only the columns of `s` (line + info) displayed in `layout`
+/// horizontal window and draw `<` / `>` markers.
+///
+/// ANSI escape sequences of hidden columns are kept to preserve styles.
+fn clip_to_hscroll(s: &str, layout: &Layout, hscroll: HScroll) -> String {
+    use unicode_segmentation::UnicodeSegmentation as _;
+    let mut out = String::with_capacity(s.len());
+    // columns of `s` displayed: [first, last)
+    let mut first = hscroll.offset;
+    let mut last = hscroll.offset + hscroll.width;
+    if hscroll.has_left_marker() {
+        out.push('<');
+        first += 1;
+    }
+    if hscroll.has_right_marker() {
+        last -= 1;
+    }
+    let mut col = 0;
+    let mut drawn = first;
+    let mut esc_seq = 0;
+    for c in s.graphemes(true) {
+        if c == "\n" {
+            // manual line wrap
+            continue;
+        }
+        let cw = width(layout.grapheme_cluster_mode, c, &mut esc_seq);
+        if cw == 0 {
+            out.push_str(c);
+            continue;
+        }
+        if col >= first && col + cw <= last {
+            // wide grapheme partially hidden by the `<` marker
+            for _ in drawn..col {
+                out.push(' ');
+            }
+            out.push_str(c);
+            drawn = col + cw;
+        }
+        col += cw;
+    }
+    if hscroll.has_right_marker() {
+        // wide grapheme partially hidden by the `>` marker
+        for _ in drawn..last {
+            out.push(' ');
+        }
+        out.push('>');
+    }
+    out
+}
+
+fn push_scroll_indicator(
+    out: &mut String,
+    rows: Unit,
+    direction: &str,
+    cols: Unit,
+    highlighter: Option<&dyn Highlighter>,
+) {
+    let mut indicator = format!("-- {rows} more row(s) {direction} --");
+    // avoid line wrap
+    indicator.truncate(usize::from(cols.saturating_sub(1)));
+    if let Some(highlighter) = highlighter {
+        out.push_str(&highlighter.highlight_hint(&indicator));
+    } else {
+        out.push_str(&indicator);
+    }
+}
+
 /// External printer
 pub trait ExternalPrinter {
     /// Print message to stdout
@@ -174,14 +479,22 @@ pub trait ExternalPrinter {
 
 /// Terminal contract
 pub trait Term {
+    /// Type ahead kept between two lines
     type Buffer;
+    /// Terminal specific key bindings
     type KeyMap;
-    type Reader: RawReader<Buffer = Self::Buffer>; // rl_instream
-    type Writer: Renderer<Reader = Self::Reader>; // rl_outstream
+    /// Input (`rl_instream`)
+    type Reader: RawReader<Buffer = Self::Buffer>;
+    /// Output (`rl_outstream`)
+    type Writer: Renderer<Reader = Self::Reader>;
+    /// Terminal state restored when the line is read
     type Mode: RawMode;
+    /// Printer used while a line is edited
     type ExternalPrinter: ExternalPrinter;
+    /// Restore cursor visibility when dropped
     type CursorGuard;
 
+    /// Open the terminal
     fn new(config: &Config) -> Result<Self>
     where
         Self: Sized;
@@ -203,11 +516,20 @@ pub trait Term {
     ) -> Result<Self::Reader>;
     /// Create a writer
     fn create_writer(&self, config: &Config) -> Self::Writer;
+    /// Move to the next line (after the edited one)
     fn writeln(&self) -> Result<()>;
     /// Create an external printer
     fn create_external_printer(&mut self) -> Result<Self::ExternalPrinter>;
     /// Change cursor visibility
     fn set_cursor_visibility(&mut self, visible: bool) -> Result<Option<Self::CursorGuard>>;
+    /// Suspend the current process (Ctrl-Z)
+    #[cfg(unix)]
+    fn suspend(&mut self) -> Result<()> {
+        suspend()
+    }
+    /// Tiny test quirk: remember the cursor position before the last command
+    #[cfg(test)]
+    fn set_cursor(&mut self, _cursor: usize) {}
 }
 
 /// Check TERM environment variable to see if current term is in our
@@ -237,10 +559,16 @@ pub use self::windows::*;
 // If on Unix platform import Unix TTY module
 // and re-export into mod.rs scope
 #[cfg(all(unix, not(target_arch = "wasm32")))]
-mod unix;
+pub(crate) mod unix;
 #[cfg(all(unix, not(target_arch = "wasm32"), not(test)))]
 pub use self::unix::*;
 
+mod ansi;
+#[cfg(any(test, feature = "testing"))]
+pub mod headless;
+pub mod stream;
+mod vt100;
+
 #[cfg(any(test, target_arch = "wasm32"))]
 mod test;
 #[cfg(any(test, target_arch = "wasm32"))]
@@ -248,10 +576,69 @@ pub use self::test::*;
 
 #[cfg(test)]
 mod test_ {
+    use crate::layout::{HScroll, Layout, Position, Viewport};
+
     #[test]
     fn test_unsupported_term() {
         assert!(!super::is_unsupported("xterm"));
 
         assert!(super::is_unsupported("dumb"));
     }
+
+    #[test]
+    fn clip_to_viewport() {
+        let layout = Layout {
+            cursor: Position { col: 1, row: 2 },
+            end: Position { col: 1, row: 4 },
+            viewport: Some(Viewport {
+                first_row: 1,
+                last_row: 2,
+            }),
+            ..Layout::default()
+        };
+        // 3 columns: "> abcde" is wrapped on rows 0, 1 and 2
+        let s = "> \x1b[1mabcd\x1b[0me\nf\ng";
+        assert_eq!(
+            "--\n\x1b[1mbcd\x1b[0me\n--",
+            super::clip_to_viewport(s, &layout, 3, Some(8), None)
+        );
+        let layout = Layout {
+            cursor: Position { col: 1, row: 1 },
+            end: Position { col: 1, row: 2 },
+            viewport: Some(Viewport {
+                first_row: 1,
+                last_row: 1,
+            }),
+            ..Layout::default()
+        };
+        assert_eq!(
+            "-- 1 more row(s) above --\nf\n-- 1 more row(s) below --",
+            super::clip_to_viewport("> abcde\nf\ng", &layout, 80, Some(8), None)
+        );
+    }
+
+    #[test]
+    fn clip_to_hscroll() {
+        let layout = Layout::default();
+        let hscroll = HScroll {
+            offset: 2,
+            width: 6,
+            text_width: 12,
+        };
+        assert_eq!(
+            "<\x1b[1mdef\x1b[0mg>",
+            super::clip_to_hscroll("ab\x1b[1mcdef\x1b[0mghijkl", &layout, hscroll)
+        );
+        // wide graphemes cut by markers
+        assert_eq!(
+            "< 中 >",
+            super::clip_to_hscroll("ab中中中中中", &layout, hscroll)
+        );
+        let hscroll = HScroll {
+            offset: 0,
+            width: 6,
+            text_width: 5,
+        };
+        assert_eq!("abcde", super::clip_to_hscroll("abcde", &layout, hscroll));
+    }
 }
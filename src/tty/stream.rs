@@ -0,0 +1,559 @@
+//! Terminal over arbitrary byte streams (see [`crate::stream`]): keys are
+//! decoded from the bytes read from a [`Transport`] and escape sequences are
+//! written to it.
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use log::debug;
+
+use super::ansi::{
+    Ansi, BEGIN_SYNCHRONIZED_UPDATE, BRACKETED_PASTE_OFF, BRACKETED_PASTE_ON,
+    END_SYNCHRONIZED_UPDATE,
+};
+use super::vt100::Vt100Decoder;
+use super::{Event, ExternalPrinter, RawMode, RawReader, Renderer, Term};
+use crate::config::{BellStyle, ColorMode, Config};
+use crate::error::{ReadlineError, Signal};
+use crate::highlight::Highlighter;
+use crate::keys::{KeyEvent, Modifiers as M};
+use crate::layout::{GraphemeClusterMode, Layout, Position, Unit};
+use crate::line_buffer::LineBuffer;
+use crate::{Cmd, Prompt, Result};
+
+/// Window size of the remote terminal.
+///
+/// The size is not queried but provided by the application (for example on
+/// an SSH `window-change` request) and can be updated from any thread: the
+/// edited line is redrawn before the next key is processed, like on
+/// `SIGWINCH`.
+#[derive(Clone, Debug)]
+pub struct WindowSize(Arc<Mutex<(Unit, Unit)>>);
+
+impl WindowSize {
+    /// Initial size: `cols` columns and `rows` rows
+    #[must_use]
+    pub fn new(cols: Unit, rows: Unit) -> Self {
+        Self(Arc::new(Mutex::new((cols, rows))))
+    }
+
+    /// Update the size
+    pub fn set(&self, cols: Unit, rows: Unit) {
+        *self.0.lock().unwrap() = (cols, rows);
+    }
+
+    /// Number of columns and rows (or 80x24 when unknown, i.e. zero)
+    #[must_use]
+    pub fn get(&self) -> (Unit, Unit) {
+        known_size(*self.0.lock().unwrap())
+    }
+}
+
+fn known_size((cols, rows): (Unit, Unit)) -> (Unit, Unit) {
+    let cols = if cols == 0 { 80 } else { cols };
+    let rows = if rows == 0 { 24 } else { rows };
+    (cols, rows)
+}
+
+/// Connection to a VT100 compatible terminal emulator (an SSH channel, a
+/// WebSocket, ...), implemented by the application.
+///
+/// The remote terminal is expected to be in raw mode already (no echo, no
+/// line buffering): nothing is negotiated.
+pub trait Transport {
+    /// Blocking read of the bytes typed on the remote terminal.
+    ///
+    /// Returns `Ok(0)` at the end of the stream.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+    /// Write all the escape sequences and text in `buf`.
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()>;
+    /// Flush what has been written.
+    fn flush(&mut self) -> io::Result<()>;
+    /// Number of columns and rows of the remote terminal (zero when unknown).
+    ///
+    /// It is checked before each key: the edited line is redrawn when it
+    /// has changed, like on `SIGWINCH`.
+    fn size(&self) -> (Unit, Unit);
+    /// Wait at most `timeout` for bytes to read: returns `false` when there
+    /// is nothing to read yet.
+    ///
+    /// While waiting for a key, messages from a [`StreamPrinter`] and size
+    /// changes are checked between two polls. By default, `true` is returned
+    /// without waiting so they are only handled once the next key has been
+    /// read.
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+        let _ = timeout;
+        Ok(true)
+    }
+}
+
+/// [`Transport`] over a [`Read`]er and a [`Write`]r, with a window size
+/// provided by the application
+///
+/// A [`Read`]er cannot be polled: messages from a [`StreamPrinter`] are
+/// shown only once the next key has been read.
+#[derive(Debug)]
+pub struct Streams<R: Read, W: Write> {
+    input: R,
+    output: W,
+    size: WindowSize,
+}
+
+impl<R: Read, W: Write> Streams<R, W> {
+    /// Read keys from `input` and write to `output` a `size` window.
+    pub fn new(input: R, output: W, size: WindowSize) -> Self {
+        Self {
+            input,
+            output,
+            size,
+        }
+    }
+
+    /// Window size shared with the application
+    pub fn window_size(&self) -> &WindowSize {
+        &self.size
+    }
+}
+
+impl<R: Read, W: Write> Transport for Streams<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.output.write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+
+    fn size(&self) -> (Unit, Unit) {
+        self.size.get()
+    }
+}
+
+type Messages = Arc<Mutex<VecDeque<String>>>;
+
+/// How long to wait for a key before checking messages and size again
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn write_all<T: Transport>(transport: &Mutex<T>, s: &str) -> Result<()> {
+    let mut transport = transport.lock().unwrap();
+    // like `ONLCR` on a PTY
+    transport.write_all(s.replace('\n', "\r\n").as_bytes())?;
+    transport.flush()?;
+    Ok(())
+}
+
+fn size<T: Transport>(transport: &Mutex<T>) -> (Unit, Unit) {
+    known_size(transport.lock().unwrap().size())
+}
+
+/// Disable bracketed paste
+#[derive(Debug)]
+pub struct StreamMode<T: Transport> {
+    out: Option<Arc<Mutex<T>>>,
+}
+
+impl<T: Transport> RawMode for StreamMode<T> {
+    fn disable_raw_mode(&self) -> Result<()> {
+        if let Some(ref out) = self.out {
+            write_all(out, BRACKETED_PASTE_OFF)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decode keys from the input stream
+///
+/// Bytes received together are decoded together: an `ESC` is an escape
+/// sequence prefix only if it is followed by bytes already received.
+#[derive(Debug)]
+pub struct StreamReader<T: Transport> {
+    transport: Arc<Mutex<T>>,
+    /// Bytes received but not yet decoded
+    buffer: VecDeque<u8>,
+    /// Last size seen
+    size: (Unit, Unit),
+    messages: Messages,
+}
+
+impl<T: Transport> StreamReader<T> {
+    /// Make sure that at least one byte is buffered
+    fn fill(&mut self) -> Result<()> {
+        if !self.buffer.is_empty() {
+            return Ok(());
+        }
+        let mut buf = [0; 1024];
+        let n = loop {
+            match self.transport.lock().unwrap().read(&mut buf) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        };
+        if n == 0 {
+            return Err(ReadlineError::Eof);
+        }
+        self.buffer.extend(&buf[..n]);
+        Ok(())
+    }
+
+    fn next_byte(&mut self) -> Result<u8> {
+        self.fill()?;
+        Ok(self.buffer.pop_front().unwrap())
+    }
+
+    fn check_resize(&mut self) -> Result<()> {
+        let size = size(&self.transport);
+        if size != self.size {
+            self.size = size;
+            return Err(ReadlineError::Signal(Signal::Resize));
+        }
+        Ok(())
+    }
+}
+
+impl<T: Transport> Vt100Decoder for StreamReader<T> {
+    fn read_char(&mut self) -> Result<char> {
+        let first = self.next_byte()?;
+        let len = match first {
+            0x00..=0x7f => return Ok(char::from(first)),
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => return Err(ReadlineError::from(ErrorKind::InvalidData)),
+        };
+        let mut bytes = [first, 0, 0, 0];
+        for byte in &mut bytes[1..len] {
+            *byte = self.next_byte()?;
+        }
+        std::str::from_utf8(&bytes[..len])
+            .ok()
+            .and_then(|s| s.chars().next())
+            .ok_or_else(|| ReadlineError::from(ErrorKind::InvalidData))
+    }
+
+    fn poll_sequence(&mut self) -> bool {
+        !self.buffer.is_empty()
+    }
+}
+
+impl<T: Transport> RawReader for StreamReader<T> {
+    type Buffer = Vec<u8>;
+
+    fn wait_for_input(&mut self, single_esc_abort: bool) -> Result<Event> {
+        loop {
+            let msg = self.messages.lock().unwrap().pop_front();
+            if let Some(msg) = msg {
+                return Ok(Event::ExternalPrint(msg));
+            }
+            if !self.buffer.is_empty() {
+                break;
+            }
+            self.check_resize()?;
+            let ready = loop {
+                match self.transport.lock().unwrap().poll(POLL_INTERVAL) {
+                    Ok(ready) => break ready,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e.into()),
+                }
+            };
+            if ready {
+                break;
+            }
+        }
+        self.next_key(single_esc_abort).map(Event::KeyPress)
+    }
+
+    fn next_key(&mut self, _: bool) -> Result<KeyEvent> {
+        self.check_resize()?;
+        let c = self.read_char()?;
+        let mut key = KeyEvent::new(c, M::NONE);
+        if key == KeyEvent::ESC && self.poll_sequence() {
+            // escape sequence
+            key = self.escape_sequence()?;
+        }
+        debug!(target: "rustyline", "c: {c:?} => key: {key:?}");
+        Ok(key)
+    }
+
+    #[cfg(unix)]
+    fn next_char(&mut self) -> Result<char> {
+        self.read_char()
+    }
+
+    fn read_pasted_text(&mut self) -> Result<String> {
+        self.pasted_text()
+    }
+
+    fn find_binding(&self, _: &KeyEvent) -> Option<Cmd> {
+        None
+    }
+
+    fn unbuffer(self) -> Option<Vec<u8>> {
+        (!self.buffer.is_empty()).then(|| self.buffer.into())
+    }
+}
+
+/// Write escape sequences to the output stream
+#[derive(Debug)]
+pub struct StreamRenderer<T: Transport> {
+    out: Arc<Mutex<T>>,
+    ansi: Ansi,
+    enable_synchronized_output: bool,
+    bell_style: BellStyle,
+    /// 0 when BSU is first used or after last ESU
+    synchronized_update: usize,
+}
+
+impl<T: Transport> StreamRenderer<T> {
+    fn write_buffer(&mut self) -> Result<()> {
+        write_all(&self.out, &self.ansi.buffer)
+    }
+}
+
+impl<T: Transport> Renderer for StreamRenderer<T> {
+    type Reader = StreamReader<T>;
+
+    fn move_cursor(&mut self, old: Position, new: Position) -> Result<()> {
+        self.ansi.buffer.clear();
+        self.ansi.move_cursor(old, new)?;
+        self.write_buffer()
+    }
+
+    fn refresh_line<P: Prompt + ?Sized>(
+        &mut self,
+        prompt: &P,
+        line: &LineBuffer,
+        hint: Option<&str>,
+        old_layout: Option<&Layout>,
+        new_layout: &Layout,
+        highlighter: Option<&dyn Highlighter>,
+    ) -> Result<()> {
+        self.begin_synchronized_update()?;
+        self.ansi.buffer.clear();
+        self.ansi
+            .refresh_line(prompt, line, hint, old_layout, new_layout, highlighter)?;
+        self.write_buffer()?;
+        self.end_synchronized_update()
+    }
+
+    fn calculate_position(&self, s: &str, orig: Position) -> Position {
+        self.ansi.calculate_position(s, orig)
+    }
+
+    fn write_and_flush(&mut self, buf: &str) -> Result<()> {
+        write_all(&self.out, buf)
+    }
+
+    fn beep(&mut self) -> Result<()> {
+        match self.bell_style {
+            BellStyle::Audible => self.write_and_flush("\x07"),
+            _ => Ok(()),
+        }
+    }
+
+    fn clear_screen(&mut self) -> Result<()> {
+        self.write_and_flush("\x1b[H\x1b[J")
+    }
+
+    fn clear_rows(&mut self, layout: &Layout) -> Result<()> {
+        self.ansi.buffer.clear();
+        self.ansi.clear_old_rows(layout);
+        self.write_buffer()
+    }
+
+    fn clear_to_eol(&mut self) -> Result<()> {
+        self.write_and_flush("\x1b[K")
+    }
+
+    fn update_size(&mut self) {
+        self.ansi.cols = size(&self.out).0;
+    }
+
+    fn get_columns(&self) -> Unit {
+        self.ansi.cols
+    }
+
+    fn get_rows(&self) -> Unit {
+        size(&self.out).1
+    }
+
+    fn colors_enabled(&self) -> bool {
+        self.ansi.colors_enabled
+    }
+
+    fn horizontal_scroll(&self) -> bool {
+        self.ansi.horizontal_scroll
+    }
+
+    fn grapheme_cluster_mode(&self) -> GraphemeClusterMode {
+        self.ansi.grapheme_cluster_mode
+    }
+
+    /// Cursor location cannot be requested without blocking on the input:
+    /// the prompt is displayed where the cursor is.
+    fn move_cursor_at_leftmost(&mut self, _: &mut StreamReader<T>) -> Result<()> {
+        Ok(())
+    }
+
+    fn begin_synchronized_update(&mut self) -> Result<()> {
+        if self.enable_synchronized_output {
+            if self.synchronized_update == 0 {
+                self.write_and_flush(BEGIN_SYNCHRONIZED_UPDATE)?;
+            }
+            self.synchronized_update = self.synchronized_update.saturating_add(1);
+        }
+        Ok(())
+    }
+
+    fn end_synchronized_update(&mut self) -> Result<()> {
+        if self.enable_synchronized_output {
+            self.synchronized_update = self.synchronized_update.saturating_sub(1);
+            if self.synchronized_update == 0 {
+                self.write_and_flush(END_SYNCHRONIZED_UPDATE)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Queue messages printed while waiting for a key (see
+/// [`Transport::poll`])
+#[derive(Debug)]
+pub struct StreamPrinter {
+    messages: Messages,
+}
+
+impl ExternalPrinter for StreamPrinter {
+    fn print(&mut self, msg: String) -> Result<()> {
+        self.messages.lock().unwrap().push_back(msg);
+        Ok(())
+    }
+}
+
+/// Show the cursor when dropped
+#[derive(Debug)]
+pub struct StreamCursorGuard<T: Transport>(Arc<Mutex<T>>);
+
+impl<T: Transport> Drop for StreamCursorGuard<T> {
+    fn drop(&mut self) {
+        let _ = write_all(&self.0, "\x1b[?25h");
+    }
+}
+
+/// Terminal over a [`Transport`] connected to a VT100 compatible terminal
+/// emulator.
+#[derive(Debug)]
+pub struct StreamTerminal<T: Transport> {
+    transport: Arc<Mutex<T>>,
+    messages: Messages,
+}
+
+impl<T: Transport> StreamTerminal<T> {
+    /// Read keys from and write to `transport`.
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport: Arc::new(Mutex::new(transport)),
+            messages: Arc::default(),
+        }
+    }
+
+    /// Access the transport (not while reading a line: it is locked)
+    pub(crate) fn transport(&self) -> MutexGuard<'_, T> {
+        self.transport.lock().unwrap()
+    }
+}
+
+impl<T: Transport> Term for StreamTerminal<T> {
+    type Buffer = Vec<u8>;
+    type CursorGuard = StreamCursorGuard<T>;
+    type ExternalPrinter = StreamPrinter;
+    type KeyMap = ();
+    type Mode = StreamMode<T>;
+    type Reader = StreamReader<T>;
+    type Writer = StreamRenderer<T>;
+
+    /// A transport must be provided: use [`StreamTerminal::new`] instead.
+    fn new(_: &Config) -> Result<Self> {
+        Err(io::Error::new(ErrorKind::Unsupported, "no transport to read from").into())
+    }
+
+    fn is_unsupported(&self) -> bool {
+        false
+    }
+
+    fn is_input_tty(&self) -> bool {
+        true
+    }
+
+    fn is_output_tty(&self) -> bool {
+        true
+    }
+
+    fn enable_raw_mode(&mut self, config: &Config) -> Result<(StreamMode<T>, ())> {
+        let out = if config.enable_bracketed_paste() {
+            write_all(&self.transport, BRACKETED_PASTE_ON)?;
+            Some(self.transport.clone())
+        } else {
+            None
+        };
+        Ok((StreamMode { out }, ()))
+    }
+
+    fn create_reader(&self, buffer: Option<Vec<u8>>, _: &Config, _: ()) -> Result<StreamReader<T>> {
+        Ok(StreamReader {
+            transport: self.transport.clone(),
+            buffer: buffer.map(VecDeque::from).unwrap_or_default(),
+            size: size(&self.transport),
+            messages: self.messages.clone(),
+        })
+    }
+
+    fn create_writer(&self, config: &Config) -> StreamRenderer<T> {
+        StreamRenderer {
+            out: self.transport.clone(),
+            ansi: Ansi::new(
+                size(&self.transport).0,
+                Unit::from(config.tab_stop()),
+                config.color_mode() != ColorMode::Disabled,
+                config.horizontal_scroll(),
+                config.grapheme_cluster_mode(),
+            ),
+            enable_synchronized_output: config.enable_synchronized_output(),
+            bell_style: config.bell_style(),
+            synchronized_update: 0,
+        }
+    }
+
+    fn writeln(&self) -> Result<()> {
+        write_all(&self.transport, "\n")
+    }
+
+    fn create_external_printer(&mut self) -> Result<StreamPrinter> {
+        Ok(StreamPrinter {
+            messages: self.messages.clone(),
+        })
+    }
+
+    fn set_cursor_visibility(&mut self, visible: bool) -> Result<Option<StreamCursorGuard<T>>> {
+        write_all(
+            &self.transport,
+            if visible { "\x1b[?25h" } else { "\x1b[?25l" },
+        )?;
+        Ok(if visible {
+            None
+        } else {
+            Some(StreamCursorGuard(self.transport.clone()))
+        })
+    }
+
+    /// Only the local process could be suspended
+    #[cfg(unix)]
+    fn suspend(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -58,7 +58,9 @@ impl RawReader for IntoIter<KeyEvent> {
 }
 
 #[derive(Default)]
-pub struct Sink {}
+pub struct Sink {
+    horizontal_scroll: bool,
+}
 
 impl Renderer for Sink {
     type Reader = IntoIter<KeyEvent>;
@@ -119,6 +121,10 @@ impl Renderer for Sink {
         false
     }
 
+    fn horizontal_scroll(&self) -> bool {
+        self.horizontal_scroll
+    }
+
     fn grapheme_cluster_mode(&self) -> GraphemeClusterMode {
         GraphemeClusterMode::Unicode
     }
@@ -190,8 +196,10 @@ impl Term for DummyTerminal {
         Ok(self.keys.clone().into_iter())
     }
 
-    fn create_writer(&self, _: &Config) -> Sink {
-        Sink::default()
+    fn create_writer(&self, config: &Config) -> Sink {
+        Sink {
+            horizontal_scroll: config.horizontal_scroll(),
+        }
     }
 
     fn writeln(&self) -> Result<()> {
@@ -205,6 +213,10 @@ impl Term for DummyTerminal {
     fn set_cursor_visibility(&mut self, _: bool) -> Result<Option<()>> {
         Ok(None)
     }
+
+    fn set_cursor(&mut self, cursor: usize) {
+        self.cursor = cursor;
+    }
 }
 
 #[cfg(unix)]
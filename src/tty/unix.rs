@@ -1,11 +1,10 @@
 //! Unix specific definitions
-use std::cmp;
 use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 #[cfg(not(feature = "buffer-redux"))]
 use std::io::BufReader;
 use std::io::{self, ErrorKind, Read, Write as _};
-use std::os::fd::{AsFd, AsRawFd as _, BorrowedFd, IntoRawFd, RawFd};
+use std::os::fd::{AsFd, AsRawFd as _, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
 use std::os::unix::net::UnixStream;
 use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
 use std::sync::mpsc::{self, SyncSender};
@@ -23,29 +22,30 @@ use nix::sys::time::TimeValLike as _;
 use nix::unistd::{close, isatty, read, write};
 #[cfg(feature = "termios")]
 use termios::Termios;
-use unicode_segmentation::UnicodeSegmentation as _;
 use utf8parse::{Parser, Receiver};
 
-use super::{Event, RawMode, RawReader, Renderer, Term, width};
+use super::ansi::{
+    Ansi, BEGIN_SYNCHRONIZED_UPDATE, BRACKETED_PASTE_OFF, BRACKETED_PASTE_ON,
+    END_SYNCHRONIZED_UPDATE, KITTY_KEYBOARD_POP, KITTY_KEYBOARD_PUSH, KITTY_KEYBOARD_RELEASE_PUSH,
+    MOUSE_OFF, MOUSE_ON,
+};
+use super::vt100::Vt100Decoder;
+use super::{Event, RawMode, RawReader, Renderer, Term};
 use crate::config::{Behavior, BellStyle, ColorMode, Config};
 use crate::error::Signal;
 use crate::highlight::Highlighter;
-use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E, Modifiers as M};
+use crate::keys::{KeyEvent, KeyEvent as E, Modifiers as M};
 use crate::layout::{GraphemeClusterMode, Layout, Position, Unit};
 use crate::line_buffer::LineBuffer;
 use crate::{Cmd, Prompt, ReadlineError, Result, error};
 
-const BRACKETED_PASTE_ON: &str = "\x1b[?2004h";
-const BRACKETED_PASTE_OFF: &str = "\x1b[?2004l";
-const BEGIN_SYNCHRONIZED_UPDATE: &str = "\x1b[?2026h";
-const END_SYNCHRONIZED_UPDATE: &str = "\x1b[?2026l";
-
 nix::ioctl_read_bad!(win_size, libc::TIOCGWINSZ, libc::winsize);
 
 fn get_win_size(fd: AltFd) -> (Unit, Unit) {
     use std::mem::zeroed;
 
-    if cfg!(test) {
+    // renderer tests write to stdout with a fixed size (unlike PTY tests)
+    if cfg!(test) && fd.0 == libc::STDOUT_FILENO {
         return (80, 24);
     }
 
@@ -94,31 +94,26 @@ fn is_a_tty(fd: AltFd) -> bool {
 pub type PosixBuffer = ();
 #[cfg(all(feature = "buffer-redux", not(test)))]
 pub type PosixBuffer = buffer_redux::Buffer;
-#[cfg(not(test))]
-pub type Buffer = PosixBuffer;
 
 pub type PosixKeyMap = HashMap<KeyEvent, Cmd>;
-#[cfg(not(test))]
-pub type KeyMap = PosixKeyMap;
 
 #[must_use = "You must restore default mode (disable_raw_mode)"]
 pub struct PosixMode {
     termios: Termios,
     tty_in: AltFd,
     tty_out: Option<AltFd>,
+    /// Disable bracketed paste / mouse / kitty keyboard
+    modes_off: String,
     raw_mode: Arc<AtomicBool>,
 }
 
-#[cfg(not(test))]
-pub type Mode = PosixMode;
-
 impl RawMode for PosixMode {
     /// Disable RAW mode for the terminal.
     fn disable_raw_mode(&self) -> Result<()> {
         termios_::disable_raw_mode(self.tty_in, &self.termios)?;
-        // disable bracketed paste
+        // disable bracketed paste / mouse reporting / kitty keyboard
         if let Some(out) = self.tty_out {
-            write_all(out, BRACKETED_PASTE_OFF)?;
+            write_all(out, &self.modes_off)?;
         }
         self.raw_mode.store(false, Ordering::SeqCst);
         Ok(())
@@ -130,6 +125,8 @@ impl RawMode for PosixMode {
 struct TtyIn {
     fd: AltFd,
     sig: Option<Sig>,
+    /// Read end of the session resize pipe (see [`ResizeNotifier`])
+    resize: Option<Arc<UnixStream>>,
 }
 
 impl Read for TtyIn {
@@ -187,7 +184,10 @@ impl TtyIn {
     }
 
     fn sig_fd(&self) -> Option<BorrowedFd<'_>> {
-        self.sig.as_ref().map(|s| s.pipe.as_fd())
+        self.sig
+            .as_ref()
+            .map(|s| s.pipe.as_fd())
+            .or_else(|| self.resize.as_ref().map(|pipe| pipe.as_fd()))
     }
 }
 
@@ -204,6 +204,8 @@ pub struct PosixRawReader {
     key_map: PosixKeyMap,
     // external print reader
     pipe_reader: Option<PipeReader>,
+    // key release decoded by the last escape sequence
+    released: Option<KeyEvent>,
     #[cfg(target_os = "macos")]
     is_dev_tty: bool,
 }
@@ -219,43 +221,18 @@ struct Utf8 {
     valid: bool,
 }
 
-const UP: char = 'A'; // kcuu1, kUP*
-const DOWN: char = 'B'; // kcud1, kDN*
-const RIGHT: char = 'C'; // kcuf1, kRIT*
-const LEFT: char = 'D'; // kcub1, kLFT*
-const END: char = 'F'; // kend*
-const HOME: char = 'H'; // khom*
-const INSERT: char = '2'; // kic*
-const DELETE: char = '3'; // kdch1, kDC*
-const PAGE_UP: char = '5'; // kpp, kPRV*
-const PAGE_DOWN: char = '6'; // knp, kNXT*
-
-const RXVT_HOME: char = '7';
-const RXVT_END: char = '8';
-
-const SHIFT: char = '2';
-const ALT: char = '3';
-const ALT_SHIFT: char = '4';
-const CTRL: char = '5';
-const CTRL_SHIFT: char = '6';
-const CTRL_ALT: char = '7';
-const CTRL_ALT_SHIFT: char = '8';
-
-const RXVT_SHIFT: char = '$';
-const RXVT_CTRL: char = '\x1e';
-const RXVT_CTRL_SHIFT: char = '@';
-
 impl PosixRawReader {
     fn new(
         fd: AltFd,
         sig: Option<Sig>,
+        resize: Option<Arc<UnixStream>>,
         buffer: Option<PosixBuffer>,
         config: &Config,
         key_map: PosixKeyMap,
         pipe_reader: Option<PipeReader>,
         #[cfg(target_os = "macos")] is_dev_tty: bool,
     ) -> Self {
-        let inner = TtyIn { fd, sig };
+        let inner = TtyIn { fd, sig, resize };
         #[cfg(any(not(feature = "buffer-redux"), test))]
         let (tty_in, _) = (BufReader::with_capacity(1024, inner), buffer);
         #[cfg(all(feature = "buffer-redux", not(test)))]
@@ -270,449 +247,44 @@ impl PosixRawReader {
             parser: Parser::new(),
             key_map,
             pipe_reader,
+            released: None,
             #[cfg(target_os = "macos")]
             is_dev_tty,
         }
     }
 
-    /// Handle \E <seq1> sequences
-    // https://invisible-island.net/xterm/xterm-function-keys.html
-    fn escape_sequence(&mut self) -> Result<KeyEvent> {
-        self._do_escape_sequence(true)
-    }
-
-    /// Don't call directly, call `PosixRawReader::escape_sequence` instead
-    fn _do_escape_sequence(&mut self, allow_recurse: bool) -> Result<KeyEvent> {
-        // Read the next byte representing the escape sequence.
-        let seq1 = self.next_char()?;
-        if seq1 == '[' {
-            // \E[ sequences. (CSI)
-            self.escape_csi()
-        } else if seq1 == 'O' {
-            // xterm
-            // \EO sequences. (SS3)
-            self.escape_o()
-        } else if seq1 == '\x1b' {
-            // \E\E — used by rxvt, iTerm (under default config), etc.
-            // ```
-            // \E\E[A => Alt-Up
-            // \E\E[B => Alt-Down
-            // \E\E[C => Alt-Right
-            // \E\E[D => Alt-Left
-            // ```
-            //
-            // In general this more or less works just adding ALT to an existing
-            // key, but has a wrinkle in that `ESC ESC` without anything
-            // following should be interpreted as the escape key.
-            //
-            // We handle this by polling to see if there's anything coming
-            // within our timeout, and if so, recursing once, but adding alt to
-            // what we read.
-            if !allow_recurse {
-                return Ok(E::ESC);
+    /// Blocking read of key pressed or released
+    fn next_event(&mut self, single_esc_abort: bool) -> Result<Event> {
+        let c = self.next_char()?;
+
+        let mut key = KeyEvent::new(c, M::NONE);
+        if key == E::ESC {
+            if !self.tty_in.buffer().is_empty() {
+                debug!(target: "rustyline", "read buffer {:?}", self.tty_in.buffer());
             }
-            let timeout = if self.timeout_ms.is_none() {
-                100u8.into()
+            let timeout_ms = if single_esc_abort && self.timeout_ms.is_none() {
+                PollTimeout::ZERO
             } else {
                 self.timeout_ms
             };
-            match self.poll(timeout) {
-                // Ignore poll errors, it's very likely we'll pick them up on
-                // the next read anyway.
-                Ok(false) | Err(_) => Ok(E::ESC),
-                Ok(true) => {
-                    // recurse, and add the alt modifier.
-                    let E(k, m) = self._do_escape_sequence(false)?;
-                    Ok(E(k, m | M::ALT))
-                }
-            }
-        } else {
-            Ok(E::alt(seq1))
-        }
-    }
-
-    /// Handle \E[ <seq2> escape sequences
-    fn escape_csi(&mut self) -> Result<KeyEvent> {
-        let seq2 = self.next_char()?;
-        if seq2.is_ascii_digit() {
-            match seq2 {
-                '0' | '9' => {
-                    debug!(target: "rustyline", "unsupported esc sequence: \\E[{seq2:?}");
-                    Ok(E(K::UnknownEscSeq, M::NONE))
+            match self.poll(timeout_ms) {
+                Ok(false) => {
+                    // single escape
                 }
-                _ => {
-                    // Extended escape, read additional byte.
-                    self.extended_escape(seq2)
+                Ok(_) => {
+                    // escape sequence
+                    key = self.escape_sequence()?;
                 }
+                // Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
             }
-        } else if seq2 == '[' {
-            let seq3 = self.next_char()?;
-            // Linux console
-            Ok(match seq3 {
-                'A' => E(K::F(1), M::NONE),
-                'B' => E(K::F(2), M::NONE),
-                'C' => E(K::F(3), M::NONE),
-                'D' => E(K::F(4), M::NONE),
-                'E' => E(K::F(5), M::NONE),
-                _ => {
-                    debug!(target: "rustyline", "unsupported esc sequence: \\E[[{seq3:?}");
-                    E(K::UnknownEscSeq, M::NONE)
-                }
-            })
-        } else {
-            // ANSI
-            Ok(match seq2 {
-                UP => E(K::Up, M::NONE),
-                DOWN => E(K::Down, M::NONE),
-                RIGHT => E(K::Right, M::NONE),
-                LEFT => E(K::Left, M::NONE),
-                //'E' => E(K::, M::), // Ignore
-                END => E(K::End, M::NONE),
-                //'G' => E(K::, M::), // Ignore
-                HOME => E(K::Home, M::NONE), // khome
-                //'J' => E(K::, M::), // clr_eos
-                //'K' => E(K::, M::), // clr_eol
-                //'L' => E(K::, M::), // il1
-                //'M' => E(K::, M::), // kmous
-                //'P' => E(K::Delete, M::NONE), // dch1
-                'Z' => E(K::BackTab, M::NONE),
-                'a' => E(K::Up, M::SHIFT),    // rxvt: kind or kUP
-                'b' => E(K::Down, M::SHIFT),  // rxvt: kri or kDN
-                'c' => E(K::Right, M::SHIFT), // rxvt
-                'd' => E(K::Left, M::SHIFT),  // rxvt
-                _ => {
-                    debug!(target: "rustyline", "unsupported esc sequence: \\E[{seq2:?}");
-                    E(K::UnknownEscSeq, M::NONE)
-                }
-            })
         }
-    }
-
-    /// Handle \E[ <seq2:digit> escape sequences
-    #[expect(clippy::cognitive_complexity)]
-    fn extended_escape(&mut self, seq2: char) -> Result<KeyEvent> {
-        let seq3 = self.next_char()?;
-        if seq3 == '~' {
-            Ok(match seq2 {
-                '1' | RXVT_HOME => E(K::Home, M::NONE), // tmux, xrvt
-                INSERT => E(K::Insert, M::NONE),
-                DELETE => E(K::Delete, M::NONE),
-                '4' | RXVT_END => E(K::End, M::NONE), // tmux, xrvt
-                PAGE_UP => E(K::PageUp, M::NONE),
-                PAGE_DOWN => E(K::PageDown, M::NONE),
-                _ => {
-                    debug!(target: "rustyline",
-                           "unsupported esc sequence: \\E[{seq2}~");
-                    E(K::UnknownEscSeq, M::NONE)
-                }
-            })
-        } else if seq3.is_ascii_digit() {
-            let seq4 = self.next_char()?;
-            if seq4 == '~' {
-                Ok(match (seq2, seq3) {
-                    ('1', '1') => E(K::F(1), M::NONE),  // rxvt-unicode
-                    ('1', '2') => E(K::F(2), M::NONE),  // rxvt-unicode
-                    ('1', '3') => E(K::F(3), M::NONE),  // rxvt-unicode
-                    ('1', '4') => E(K::F(4), M::NONE),  // rxvt-unicode
-                    ('1', '5') => E(K::F(5), M::NONE),  // kf5
-                    ('1', '7') => E(K::F(6), M::NONE),  // kf6
-                    ('1', '8') => E(K::F(7), M::NONE),  // kf7
-                    ('1', '9') => E(K::F(8), M::NONE),  // kf8
-                    ('2', '0') => E(K::F(9), M::NONE),  // kf9
-                    ('2', '1') => E(K::F(10), M::NONE), // kf10
-                    ('2', '3') => E(K::F(11), M::NONE), // kf11
-                    ('2', '4') => E(K::F(12), M::NONE), // kf12
-                    //('6', '2') => KeyCode::ScrollUp,
-                    //('6', '3') => KeyCode::ScrollDown,
-                    _ => {
-                        debug!(target: "rustyline",
-                               "unsupported esc sequence: \\E[{seq2}{seq3}~");
-                        E(K::UnknownEscSeq, M::NONE)
-                    }
-                })
-            } else if seq4 == ';' {
-                let seq5 = self.next_char()?;
-                if seq5.is_ascii_digit() {
-                    let seq6 = self.next_char()?;
-                    if seq6.is_ascii_digit() {
-                        self.next_char()?; // 'R' expected
-                        Ok(E(K::UnknownEscSeq, M::NONE))
-                    } else if seq6 == 'R' {
-                        Ok(E(K::UnknownEscSeq, M::NONE))
-                    } else if seq6 == '~' {
-                        Ok(match (seq2, seq3, seq5) {
-                            ('1', '5', CTRL) => E(K::F(5), M::CTRL),
-                            //('1', '5', '6') => E(K::F(17), M::CTRL),
-                            ('1', '7', CTRL) => E(K::F(6), M::CTRL),
-                            //('1', '7', '6') => E(K::F(18), M::CTRL),
-                            ('1', '8', CTRL) => E(K::F(7), M::CTRL),
-                            ('1', '9', CTRL) => E(K::F(8), M::CTRL),
-                            //('1', '9', '6') => E(K::F(19), M::CTRL),
-                            ('2', '0', CTRL) => E(K::F(9), M::CTRL),
-                            //('2', '0', '6') => E(K::F(21), M::CTRL),
-                            ('2', '1', CTRL) => E(K::F(10), M::CTRL),
-                            //('2', '1', '6') => E(K::F(22), M::CTRL),
-                            ('2', '3', CTRL) => E(K::F(11), M::CTRL),
-                            //('2', '3', '6') => E(K::F(23), M::CTRL),
-                            ('2', '4', CTRL) => E(K::F(12), M::CTRL),
-                            //('2', '4', '6') => E(K::F(24), M::CTRL),
-                            _ => {
-                                debug!(target: "rustyline",
-                                       "unsupported esc sequence: \\E[{seq2}{seq3};{seq5}~");
-                                E(K::UnknownEscSeq, M::NONE)
-                            }
-                        })
-                    } else {
-                        debug!(target: "rustyline",
-                               "unsupported esc sequence: \\E[{seq2}{seq3};{seq5}{seq6}");
-                        Ok(E(K::UnknownEscSeq, M::NONE))
-                    }
-                } else {
-                    debug!(target: "rustyline",
-                           "unsupported esc sequence: \\E[{seq2}{seq3};{seq5:?}");
-                    Ok(E(K::UnknownEscSeq, M::NONE))
-                }
-            } else if seq4.is_ascii_digit() {
-                let seq5 = self.next_char()?;
-                if seq5 == '~' {
-                    Ok(match (seq2, seq3, seq4) {
-                        ('2', '0', '0') => E(K::BracketedPasteStart, M::NONE),
-                        ('2', '0', '1') => E(K::BracketedPasteEnd, M::NONE),
-                        _ => {
-                            debug!(target: "rustyline",
-                                   "unsupported esc sequence: \\E[{seq2}{seq3}{seq4}~");
-                            E(K::UnknownEscSeq, M::NONE)
-                        }
-                    })
-                } else {
-                    debug!(target: "rustyline",
-                           "unsupported esc sequence: \\E[{seq2}{seq3}{seq4}{seq5}");
-                    Ok(E(K::UnknownEscSeq, M::NONE))
-                }
-            } else {
-                debug!(target: "rustyline",
-                       "unsupported esc sequence: \\E[{seq2}{seq3}{seq4:?}");
-                Ok(E(K::UnknownEscSeq, M::NONE))
-            }
-        } else if seq3 == ';' {
-            let seq4 = self.next_char()?;
-            if seq4.is_ascii_digit() {
-                let seq5 = self.next_char()?;
-                if seq5.is_ascii_digit() {
-                    self.next_char()?; // 'R' expected
-                    //
-                    //('1', '0', UP) => E(K::, M::), // Alt + Shift + Up
-                    Ok(E(K::UnknownEscSeq, M::NONE))
-                } else if seq2 == '1' {
-                    Ok(match (seq4, seq5) {
-                        (SHIFT, UP) => E(K::Up, M::SHIFT),     // ~ key_sr
-                        (SHIFT, DOWN) => E(K::Down, M::SHIFT), // ~ key_sf
-                        (SHIFT, RIGHT) => E(K::Right, M::SHIFT),
-                        (SHIFT, LEFT) => E(K::Left, M::SHIFT),
-                        (SHIFT, END) => E(K::End, M::SHIFT), // kEND
-                        (SHIFT, HOME) => E(K::Home, M::SHIFT), // kHOM
-                        //('2', 'P') => E(K::F(13), M::NONE),
-                        //('2', 'Q') => E(K::F(14), M::NONE),
-                        //('2', 'S') => E(K::F(16), M::NONE),
-                        (ALT, UP) => E(K::Up, M::ALT),
-                        (ALT, DOWN) => E(K::Down, M::ALT),
-                        (ALT, RIGHT) => E(K::Right, M::ALT),
-                        (ALT, LEFT) => E(K::Left, M::ALT),
-                        (ALT, END) => E(K::End, M::ALT),
-                        (ALT, HOME) => E(K::Home, M::ALT),
-                        (ALT_SHIFT, UP) => E(K::Up, M::ALT_SHIFT),
-                        (ALT_SHIFT, DOWN) => E(K::Down, M::ALT_SHIFT),
-                        (ALT_SHIFT, RIGHT) => E(K::Right, M::ALT_SHIFT),
-                        (ALT_SHIFT, LEFT) => E(K::Left, M::ALT_SHIFT),
-                        (ALT_SHIFT, END) => E(K::End, M::ALT_SHIFT),
-                        (ALT_SHIFT, HOME) => E(K::Home, M::ALT_SHIFT),
-                        (CTRL, UP) => E(K::Up, M::CTRL),
-                        (CTRL, DOWN) => E(K::Down, M::CTRL),
-                        (CTRL, RIGHT) => E(K::Right, M::CTRL),
-                        (CTRL, LEFT) => E(K::Left, M::CTRL),
-                        (CTRL, END) => E(K::End, M::CTRL),
-                        (CTRL, HOME) => E(K::Home, M::CTRL),
-                        (CTRL, 'P') => E(K::F(1), M::CTRL),
-                        (CTRL, 'Q') => E(K::F(2), M::CTRL),
-                        (CTRL, 'S') => E(K::F(4), M::CTRL),
-                        (CTRL, 'p') => E(K::Char('0'), M::CTRL),
-                        (CTRL, 'q') => E(K::Char('1'), M::CTRL),
-                        (CTRL, 'r') => E(K::Char('2'), M::CTRL),
-                        (CTRL, 's') => E(K::Char('3'), M::CTRL),
-                        (CTRL, 't') => E(K::Char('4'), M::CTRL),
-                        (CTRL, 'u') => E(K::Char('5'), M::CTRL),
-                        (CTRL, 'v') => E(K::Char('6'), M::CTRL),
-                        (CTRL, 'w') => E(K::Char('7'), M::CTRL),
-                        (CTRL, 'x') => E(K::Char('8'), M::CTRL),
-                        (CTRL, 'y') => E(K::Char('9'), M::CTRL),
-                        (CTRL_SHIFT, UP) => E(K::Up, M::CTRL_SHIFT),
-                        (CTRL_SHIFT, DOWN) => E(K::Down, M::CTRL_SHIFT),
-                        (CTRL_SHIFT, RIGHT) => E(K::Right, M::CTRL_SHIFT),
-                        (CTRL_SHIFT, LEFT) => E(K::Left, M::CTRL_SHIFT),
-                        (CTRL_SHIFT, END) => E(K::End, M::CTRL_SHIFT),
-                        (CTRL_SHIFT, HOME) => E(K::Home, M::CTRL_SHIFT),
-                        //('6', 'P') => E(K::F(13), M::CTRL),
-                        //('6', 'Q') => E(K::F(14), M::CTRL),
-                        //('6', 'S') => E(K::F(16), M::CTRL),
-                        (CTRL_SHIFT, 'p') => E(K::Char('0'), M::CTRL_SHIFT),
-                        (CTRL_SHIFT, 'q') => E(K::Char('1'), M::CTRL_SHIFT),
-                        (CTRL_SHIFT, 'r') => E(K::Char('2'), M::CTRL_SHIFT),
-                        (CTRL_SHIFT, 's') => E(K::Char('3'), M::CTRL_SHIFT),
-                        (CTRL_SHIFT, 't') => E(K::Char('4'), M::CTRL_SHIFT),
-                        (CTRL_SHIFT, 'u') => E(K::Char('5'), M::CTRL_SHIFT),
-                        (CTRL_SHIFT, 'v') => E(K::Char('6'), M::CTRL_SHIFT),
-                        (CTRL_SHIFT, 'w') => E(K::Char('7'), M::CTRL_SHIFT),
-                        (CTRL_SHIFT, 'x') => E(K::Char('8'), M::CTRL_SHIFT),
-                        (CTRL_SHIFT, 'y') => E(K::Char('9'), M::CTRL_SHIFT),
-                        (CTRL_ALT, UP) => E(K::Up, M::CTRL_ALT),
-                        (CTRL_ALT, DOWN) => E(K::Down, M::CTRL_ALT),
-                        (CTRL_ALT, RIGHT) => E(K::Right, M::CTRL_ALT),
-                        (CTRL_ALT, LEFT) => E(K::Left, M::CTRL_ALT),
-                        (CTRL_ALT, END) => E(K::End, M::CTRL_ALT),
-                        (CTRL_ALT, HOME) => E(K::Home, M::CTRL_ALT),
-                        (CTRL_ALT, 'p') => E(K::Char('0'), M::CTRL_ALT),
-                        (CTRL_ALT, 'q') => E(K::Char('1'), M::CTRL_ALT),
-                        (CTRL_ALT, 'r') => E(K::Char('2'), M::CTRL_ALT),
-                        (CTRL_ALT, 's') => E(K::Char('3'), M::CTRL_ALT),
-                        (CTRL_ALT, 't') => E(K::Char('4'), M::CTRL_ALT),
-                        (CTRL_ALT, 'u') => E(K::Char('5'), M::CTRL_ALT),
-                        (CTRL_ALT, 'v') => E(K::Char('6'), M::CTRL_ALT),
-                        (CTRL_ALT, 'w') => E(K::Char('7'), M::CTRL_ALT),
-                        (CTRL_ALT, 'x') => E(K::Char('8'), M::CTRL_ALT),
-                        (CTRL_ALT, 'y') => E(K::Char('9'), M::CTRL_ALT),
-                        (CTRL_ALT_SHIFT, UP) => E(K::Up, M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, DOWN) => E(K::Down, M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, RIGHT) => E(K::Right, M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, LEFT) => E(K::Left, M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, END) => E(K::End, M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, HOME) => E(K::Home, M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, 'p') => E(K::Char('0'), M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, 'q') => E(K::Char('1'), M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, 'r') => E(K::Char('2'), M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, 's') => E(K::Char('3'), M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, 't') => E(K::Char('4'), M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, 'u') => E(K::Char('5'), M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, 'v') => E(K::Char('6'), M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, 'w') => E(K::Char('7'), M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, 'x') => E(K::Char('8'), M::CTRL_ALT_SHIFT),
-                        (CTRL_ALT_SHIFT, 'y') => E(K::Char('9'), M::CTRL_ALT_SHIFT),
-                        // Meta + arrow on (some?) Macs when using iTerm defaults
-                        ('9', UP) => E(K::Up, M::ALT),
-                        ('9', DOWN) => E(K::Down, M::ALT),
-                        ('9', RIGHT) => E(K::Right, M::ALT),
-                        ('9', LEFT) => E(K::Left, M::ALT),
-                        _ => {
-                            debug!(target: "rustyline",
-                                   "unsupported esc sequence: \\E[1;{seq4}{seq5:?}");
-                            E(K::UnknownEscSeq, M::NONE)
-                        }
-                    })
-                } else if seq5 == '~' {
-                    Ok(match (seq2, seq4) {
-                        (INSERT, SHIFT) => E(K::Insert, M::SHIFT),
-                        (INSERT, ALT) => E(K::Insert, M::ALT),
-                        (INSERT, ALT_SHIFT) => E(K::Insert, M::ALT_SHIFT),
-                        (INSERT, CTRL) => E(K::Insert, M::CTRL),
-                        (INSERT, CTRL_SHIFT) => E(K::Insert, M::CTRL_SHIFT),
-                        (INSERT, CTRL_ALT) => E(K::Insert, M::CTRL_ALT),
-                        (INSERT, CTRL_ALT_SHIFT) => E(K::Insert, M::CTRL_ALT_SHIFT),
-                        (DELETE, SHIFT) => E(K::Delete, M::SHIFT),
-                        (DELETE, ALT) => E(K::Delete, M::ALT),
-                        (DELETE, ALT_SHIFT) => E(K::Delete, M::ALT_SHIFT),
-                        (DELETE, CTRL) => E(K::Delete, M::CTRL),
-                        (DELETE, CTRL_SHIFT) => E(K::Delete, M::CTRL_SHIFT),
-                        (DELETE, CTRL_ALT) => E(K::Delete, M::CTRL_ALT),
-                        (DELETE, CTRL_ALT_SHIFT) => E(K::Delete, M::CTRL_ALT_SHIFT),
-                        (PAGE_UP, SHIFT) => E(K::PageUp, M::SHIFT),
-                        (PAGE_UP, ALT) => E(K::PageUp, M::ALT),
-                        (PAGE_UP, ALT_SHIFT) => E(K::PageUp, M::ALT_SHIFT),
-                        (PAGE_UP, CTRL) => E(K::PageUp, M::CTRL),
-                        (PAGE_UP, CTRL_SHIFT) => E(K::PageUp, M::CTRL_SHIFT),
-                        (PAGE_UP, CTRL_ALT) => E(K::PageUp, M::CTRL_ALT),
-                        (PAGE_UP, CTRL_ALT_SHIFT) => E(K::PageUp, M::CTRL_ALT_SHIFT),
-                        (PAGE_DOWN, SHIFT) => E(K::PageDown, M::SHIFT),
-                        (PAGE_DOWN, ALT) => E(K::PageDown, M::ALT),
-                        (PAGE_DOWN, ALT_SHIFT) => E(K::PageDown, M::ALT_SHIFT),
-                        (PAGE_DOWN, CTRL) => E(K::PageDown, M::CTRL),
-                        (PAGE_DOWN, CTRL_SHIFT) => E(K::PageDown, M::CTRL_SHIFT),
-                        (PAGE_DOWN, CTRL_ALT) => E(K::PageDown, M::CTRL_ALT),
-                        (PAGE_DOWN, CTRL_ALT_SHIFT) => E(K::PageDown, M::CTRL_ALT_SHIFT),
-                        _ => {
-                            debug!(target: "rustyline",
-                                   "unsupported esc sequence: \\E[{seq2};{seq4:?}~");
-                            E(K::UnknownEscSeq, M::NONE)
-                        }
-                    })
-                } else {
-                    debug!(target: "rustyline",
-                           "unsupported esc sequence: \\E[{seq2};{seq4}{seq5:?}");
-                    Ok(E(K::UnknownEscSeq, M::NONE))
-                }
-            } else {
-                debug!(target: "rustyline",
-                       "unsupported esc sequence: \\E[{seq2};{seq4:?}");
-                Ok(E(K::UnknownEscSeq, M::NONE))
-            }
-        } else {
-            Ok(match (seq2, seq3) {
-                (DELETE, RXVT_CTRL) => E(K::Delete, M::CTRL),
-                (DELETE, RXVT_CTRL_SHIFT) => E(K::Delete, M::CTRL_SHIFT),
-                (CTRL, UP) => E(K::Up, M::CTRL),
-                (CTRL, DOWN) => E(K::Down, M::CTRL),
-                (CTRL, RIGHT) => E(K::Right, M::CTRL),
-                (CTRL, LEFT) => E(K::Left, M::CTRL),
-                (PAGE_UP, RXVT_CTRL) => E(K::PageUp, M::CTRL),
-                (PAGE_UP, RXVT_SHIFT) => E(K::PageUp, M::SHIFT),
-                (PAGE_UP, RXVT_CTRL_SHIFT) => E(K::PageUp, M::CTRL_SHIFT),
-                (PAGE_DOWN, RXVT_CTRL) => E(K::PageDown, M::CTRL),
-                (PAGE_DOWN, RXVT_SHIFT) => E(K::PageDown, M::SHIFT),
-                (PAGE_DOWN, RXVT_CTRL_SHIFT) => E(K::PageDown, M::CTRL_SHIFT),
-                (RXVT_HOME, RXVT_CTRL) => E(K::Home, M::CTRL),
-                (RXVT_HOME, RXVT_SHIFT) => E(K::Home, M::SHIFT),
-                (RXVT_HOME, RXVT_CTRL_SHIFT) => E(K::Home, M::CTRL_SHIFT),
-                (RXVT_END, RXVT_CTRL) => E(K::End, M::CTRL), // kEND5 or kel
-                (RXVT_END, RXVT_SHIFT) => E(K::End, M::SHIFT),
-                (RXVT_END, RXVT_CTRL_SHIFT) => E(K::End, M::CTRL_SHIFT),
-                _ => {
-                    debug!(target: "rustyline",
-                           "unsupported esc sequence: \\E[{seq2}{seq3:?}");
-                    E(K::UnknownEscSeq, M::NONE)
-                }
-            })
+        if let Some(key) = self.released.take() {
+            debug!(target: "rustyline", "key released: {key:?}");
+            return Ok(Event::KeyRelease(key));
         }
-    }
-
-    /// Handle \EO <seq2> escape sequences
-    fn escape_o(&mut self) -> Result<KeyEvent> {
-        let seq2 = self.next_char()?;
-        Ok(match seq2 {
-            UP => E(K::Up, M::NONE),
-            DOWN => E(K::Down, M::NONE),
-            RIGHT => E(K::Right, M::NONE),
-            LEFT => E(K::Left, M::NONE),
-            //'E' => E(K::, M::),// key_b2, kb2
-            END => E(K::End, M::NONE),   // kend
-            HOME => E(K::Home, M::NONE), // khome
-            'M' => E::ENTER,             // kent
-            'P' => E(K::F(1), M::NONE),  // kf1
-            'Q' => E(K::F(2), M::NONE),  // kf2
-            'R' => E(K::F(3), M::NONE),  // kf3
-            'S' => E(K::F(4), M::NONE),  // kf4
-            'a' => E(K::Up, M::CTRL),
-            'b' => E(K::Down, M::CTRL),
-            'c' => E(K::Right, M::CTRL), // rxvt
-            'd' => E(K::Left, M::CTRL),  // rxvt
-            'l' => E(K::F(8), M::NONE),
-            't' => E(K::F(5), M::NONE),  // kf5 or kb1
-            'u' => E(K::F(6), M::NONE),  // kf6 or kb2
-            'v' => E(K::F(7), M::NONE),  // kf7 or kb3
-            'w' => E(K::F(9), M::NONE),  // kf9 or ka1
-            'x' => E(K::F(10), M::NONE), // kf10 or ka2
-            _ => {
-                debug!(target: "rustyline", "unsupported esc sequence: \\EO{seq2:?}");
-                E(K::UnknownEscSeq, M::NONE)
-            }
-        })
+        debug!(target: "rustyline", "c: {c:?} => key: {key:?}");
+        Ok(Event::KeyPress(key))
     }
 
     fn poll(&mut self, timeout: PollTimeout) -> Result<bool> {
@@ -794,7 +366,7 @@ impl PosixRawReader {
                     return Ok(Event::Timeout(false));
                 }
                 // prefer user input over external print
-                return self.next_key(single_esc_abort).map(Event::KeyPress);
+                return self.next_event(single_esc_abort);
             } else if timeout.is_some() {
                 cfg_select! {
                     target_os = "macos" => {
@@ -818,44 +390,28 @@ impl RawReader for PosixRawReader {
     type Buffer = PosixBuffer;
 
     fn wait_for_input(&mut self, single_esc_abort: bool) -> Result<Event> {
+        if !self.tty_in.buffer().is_empty() {
+            // type ahead: `select` would block
+            return self.next_event(single_esc_abort);
+        }
         cfg_select! {
           feature = "signal-hook" => {
               self.select(None, single_esc_abort)
           }
-          _ => match self.pipe_reader {
-              Some(_) => self.select(None, single_esc_abort),
-              None => self.next_key(single_esc_abort).map(Event::KeyPress),
+          _ => if self.pipe_reader.is_some() || self.tty_in.get_ref().resize.is_some() {
+              self.select(None, single_esc_abort)
+          } else {
+              self.next_event(single_esc_abort)
           }
         }
     }
 
     fn next_key(&mut self, single_esc_abort: bool) -> Result<KeyEvent> {
-        let c = self.next_char()?;
-
-        let mut key = KeyEvent::new(c, M::NONE);
-        if key == E::ESC {
-            if !self.tty_in.buffer().is_empty() {
-                debug!(target: "rustyline", "read buffer {:?}", self.tty_in.buffer());
-            }
-            let timeout_ms = if single_esc_abort && self.timeout_ms.is_none() {
-                PollTimeout::ZERO
-            } else {
-                self.timeout_ms
-            };
-            match self.poll(timeout_ms) {
-                Ok(false) => {
-                    // single escape
-                }
-                Ok(_) => {
-                    // escape sequence
-                    key = self.escape_sequence()?;
-                }
-                // Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
-                Err(e) => return Err(e),
+        loop {
+            if let Event::KeyPress(key) = self.next_event(single_esc_abort)? {
+                return Ok(key);
             }
         }
-        debug!(target: "rustyline", "c: {c:?} => key: {key:?}");
-        Ok(key)
     }
 
     fn next_char(&mut self) -> Result<char> {
@@ -880,23 +436,7 @@ impl RawReader for PosixRawReader {
     }
 
     fn read_pasted_text(&mut self) -> Result<String> {
-        let mut buffer = String::new();
-        loop {
-            match self.next_char()? {
-                '\x1b' => {
-                    let key = self.escape_sequence()?;
-                    if key == E(K::BracketedPasteEnd, M::NONE) {
-                        break;
-                    } else {
-                        continue; // TODO validate
-                    }
-                }
-                c => buffer.push(c),
-            }
-        }
-        let buffer = buffer.replace("\r\n", "\n");
-        let buffer = buffer.replace('\r', "\n");
-        Ok(buffer)
+        self.pasted_text()
     }
 
     fn find_binding(&self, key: &KeyEvent) -> Option<Cmd> {
@@ -919,6 +459,27 @@ impl RawReader for PosixRawReader {
     }
 }
 
+impl Vt100Decoder for PosixRawReader {
+    fn read_char(&mut self) -> Result<char> {
+        self.next_char()
+    }
+
+    fn poll_sequence(&mut self) -> bool {
+        let timeout = if self.timeout_ms.is_none() {
+            100u8.into()
+        } else {
+            self.timeout_ms
+        };
+        // Ignore poll errors, it's very likely we'll pick them up on
+        // the next read anyway.
+        matches!(self.poll(timeout), Ok(true))
+    }
+
+    fn key_released(&mut self, key: KeyEvent) {
+        self.released = Some(key);
+    }
+}
+
 impl Receiver for Utf8 {
     /// Called whenever a code point is parsed successfully
     fn codepoint(&mut self, c: char) {
@@ -936,12 +497,8 @@ impl Receiver for Utf8 {
 /// Console output writer
 pub struct PosixRenderer {
     out: AltFd,
-    cols: Unit, // Number of columns in terminal
-    buffer: String,
-    tab_stop: Unit,
-    colors_enabled: bool,
+    ansi: Ansi,
     enable_synchronized_output: bool,
-    grapheme_cluster_mode: GraphemeClusterMode,
     bell_style: BellStyle,
     /// 0 when BSU is first used or after last ESU
     synchronized_update: usize,
@@ -952,6 +509,7 @@ impl PosixRenderer {
         out: AltFd,
         tab_stop: Unit,
         colors_enabled: bool,
+        horizontal_scroll: bool,
         enable_synchronized_output: bool,
         grapheme_cluster_mode: GraphemeClusterMode,
         bell_style: BellStyle,
@@ -959,34 +517,40 @@ impl PosixRenderer {
         let (cols, _) = get_win_size(out);
         Self {
             out,
-            cols,
-            buffer: String::with_capacity(1024),
-            tab_stop,
-            colors_enabled,
+            ansi: Ansi::new(
+                cols,
+                tab_stop,
+                colors_enabled,
+                horizontal_scroll,
+                grapheme_cluster_mode,
+            ),
             enable_synchronized_output,
-            grapheme_cluster_mode,
             bell_style,
             synchronized_update: 0,
         }
     }
 
-    fn clear_old_rows(&mut self, layout: &Layout) {
-        use std::fmt::Write as _;
-        let current_row = layout.cursor.row;
-        let old_rows = layout.end.row;
-        // old_rows < cursor_row if the prompt spans multiple lines and if
-        // this is the default State.
-        let cursor_row_movement = old_rows.saturating_sub(current_row);
-        // move the cursor down as required
-        if cursor_row_movement > 0 {
-            write!(self.buffer, "\x1b[{cursor_row_movement}B").unwrap();
+    /// Request the cursor location: (row, col) starting at 1
+    fn cursor_position(&mut self, rdr: &mut PosixRawReader) -> Result<Option<(u32, u32)>> {
+        if rdr.poll(PollTimeout::ZERO)? {
+            debug!(target: "rustyline", "cannot request cursor location");
+            return Ok(None);
         }
-        // clear old rows
-        for _ in 0..old_rows {
-            self.buffer.push_str("\r\x1b[K\x1b[A");
+        /* Report cursor location */
+        self.write_and_flush("\x1b[6n")?;
+        /* Read the response: ESC [ rows ; cols R */
+        if !rdr.poll(PollTimeout::from(100u8))?
+            || rdr.next_char()? != '\x1b'
+            || rdr.next_char()? != '['
+        {
+            warn!(target: "rustyline", "cannot read cursor location");
+            return Ok(None);
         }
-        // clear the line
-        self.buffer.push_str("\r\x1b[K");
+        let Some(row) = read_digits_until(rdr, ';')? else {
+            warn!(target: "rustyline", "cannot read cursor location");
+            return Ok(None);
+        };
+        Ok(read_digits_until(rdr, 'R')?.map(|col| (row, col)))
     }
 }
 
@@ -994,45 +558,9 @@ impl Renderer for PosixRenderer {
     type Reader = PosixRawReader;
 
     fn move_cursor(&mut self, old: Position, new: Position) -> Result<()> {
-        use std::fmt::Write as _;
-        self.buffer.clear();
-        let row_ordering = new.row.cmp(&old.row);
-        if row_ordering == cmp::Ordering::Greater {
-            // move down
-            let row_shift = new.row - old.row;
-            if row_shift == 1 {
-                self.buffer.push_str("\x1b[B");
-            } else {
-                write!(self.buffer, "\x1b[{row_shift}B")?;
-            }
-        } else if row_ordering == cmp::Ordering::Less {
-            // move up
-            let row_shift = old.row - new.row;
-            if row_shift == 1 {
-                self.buffer.push_str("\x1b[A");
-            } else {
-                write!(self.buffer, "\x1b[{row_shift}A")?;
-            }
-        }
-        let col_ordering = new.col.cmp(&old.col);
-        if col_ordering == cmp::Ordering::Greater {
-            // move right
-            let col_shift = new.col - old.col;
-            if col_shift == 1 {
-                self.buffer.push_str("\x1b[C");
-            } else {
-                write!(self.buffer, "\x1b[{col_shift}C")?;
-            }
-        } else if col_ordering == cmp::Ordering::Less {
-            // move left
-            let col_shift = old.col - new.col;
-            if col_shift == 1 {
-                self.buffer.push_str("\x1b[D");
-            } else {
-                write!(self.buffer, "\x1b[{col_shift}D")?;
-            }
-        }
-        write_all(self.out, self.buffer.as_str())?;
+        self.ansi.buffer.clear();
+        self.ansi.move_cursor(old, new)?;
+        write_all(self.out, self.ansi.buffer.as_str())?;
         Ok(())
     }
 
@@ -1045,60 +573,11 @@ impl Renderer for PosixRenderer {
         new_layout: &Layout,
         highlighter: Option<&dyn Highlighter>,
     ) -> Result<()> {
-        use std::fmt::Write as _;
         self.begin_synchronized_update()?;
-        self.buffer.clear();
-
-        let default_prompt = new_layout.default_prompt;
-        let cursor = new_layout.cursor;
-        let end_pos = new_layout.end;
-
-        if let Some(old_layout) = old_layout {
-            self.clear_old_rows(old_layout);
-        }
-
-        if let Some(highlighter) = highlighter {
-            // display the prompt
-            self.buffer
-                .push_str(&highlighter.highlight_prompt(prompt.styled(), default_prompt));
-            // display the input line
-            self.buffer
-                .push_str(&highlighter.highlight(line, line.pos()));
-        } else {
-            // display the prompt
-            self.buffer.push_str(prompt.raw());
-            // display the input line
-            self.buffer.push_str(line);
-        }
-        // display hint
-        if let Some(hint) = hint {
-            if let Some(highlighter) = highlighter {
-                self.buffer.push_str(&highlighter.highlight_hint(hint));
-            } else {
-                self.buffer.push_str(hint);
-            }
-        }
-        // we have to generate our own newline on line wrap
-        if end_pos.col == 0
-            && end_pos.row > 0
-            && !hint.map_or_else(|| line.ends_with('\n'), |h| h.ends_with('\n'))
-        {
-            self.buffer.push('\n');
-        }
-        // position the cursor
-        let new_cursor_row_movement = end_pos.row - cursor.row;
-        // move the cursor up as required
-        if new_cursor_row_movement > 0 {
-            write!(self.buffer, "\x1b[{new_cursor_row_movement}A")?;
-        }
-        // position the cursor within the line
-        if cursor.col > 0 {
-            write!(self.buffer, "\r\x1b[{}C", cursor.col)?;
-        } else {
-            self.buffer.push('\r');
-        }
-
-        write_all(self.out, self.buffer.as_str())?;
+        self.ansi.buffer.clear();
+        self.ansi
+            .refresh_line(prompt, line, hint, old_layout, new_layout, highlighter)?;
+        write_all(self.out, self.ansi.buffer.as_str())?;
         self.end_synchronized_update()?;
         Ok(())
     }
@@ -1108,33 +587,8 @@ impl Renderer for PosixRenderer {
         Ok(())
     }
 
-    /// Control characters are treated as having zero width.
-    /// Characters with 2 column width are correctly handled (not split).
     fn calculate_position(&self, s: &str, orig: Position) -> Position {
-        let mut pos = orig;
-        let mut esc_seq = 0;
-        for c in s.graphemes(true) {
-            if c == "\n" {
-                pos.row += 1;
-                pos.col = 0;
-                continue;
-            }
-            let cw = if c == "\t" {
-                self.tab_stop - (pos.col % self.tab_stop)
-            } else {
-                width(self.grapheme_cluster_mode, c, &mut esc_seq)
-            };
-            pos.col += cw;
-            if pos.col > self.cols {
-                pos.row += 1;
-                pos.col = cw;
-            }
-        }
-        if pos.col == self.cols {
-            pos.col = 0;
-            pos.row += 1;
-        }
-        pos
+        self.ansi.calculate_position(s, orig)
     }
 
     fn beep(&mut self) -> Result<()> {
@@ -1150,9 +604,9 @@ impl Renderer for PosixRenderer {
     }
 
     fn clear_rows(&mut self, layout: &Layout) -> Result<()> {
-        self.buffer.clear();
-        self.clear_old_rows(layout);
-        write_all(self.out, self.buffer.as_str())?;
+        self.ansi.buffer.clear();
+        self.ansi.clear_old_rows(layout);
+        write_all(self.out, self.ansi.buffer.as_str())?;
         Ok(())
     }
 
@@ -1164,11 +618,11 @@ impl Renderer for PosixRenderer {
     /// Try to update the number of columns in the current terminal,
     fn update_size(&mut self) {
         let (cols, _) = get_win_size(self.out);
-        self.cols = cols;
+        self.ansi.cols = cols;
     }
 
     fn get_columns(&self) -> Unit {
-        self.cols
+        self.ansi.cols
     }
 
     /// Try to get the number of rows in the current terminal,
@@ -1179,37 +633,34 @@ impl Renderer for PosixRenderer {
     }
 
     fn colors_enabled(&self) -> bool {
-        self.colors_enabled
+        self.ansi.colors_enabled
+    }
+
+    fn horizontal_scroll(&self) -> bool {
+        self.ansi.horizontal_scroll
     }
 
     fn grapheme_cluster_mode(&self) -> GraphemeClusterMode {
-        self.grapheme_cluster_mode
+        self.ansi.grapheme_cluster_mode
     }
 
     fn move_cursor_at_leftmost(&mut self, rdr: &mut PosixRawReader) -> Result<()> {
-        if rdr.poll(PollTimeout::ZERO)? {
-            debug!(target: "rustyline", "cannot request cursor location");
-            return Ok(());
-        }
-        /* Report cursor location */
-        self.write_and_flush("\x1b[6n")?;
-        /* Read the response: ESC [ rows ; cols R */
-        if !rdr.poll(PollTimeout::from(100u8))?
-            || rdr.next_char()? != '\x1b'
-            || rdr.next_char()? != '['
-            || read_digits_until(rdr, ';')?.is_none()
-        {
-            warn!(target: "rustyline", "cannot read initial cursor location");
+        let Some((_, col)) = self.cursor_position(rdr)? else {
             return Ok(());
-        }
-        let col = read_digits_until(rdr, 'R')?;
+        };
         debug!(target: "rustyline", "initial cursor location: {col:?}");
-        if col != Some(1) {
+        if col != 1 {
             self.write_and_flush("\n")?;
         }
         Ok(())
     }
 
+    fn cursor_row(&mut self, rdr: &mut PosixRawReader) -> Result<Option<Unit>> {
+        Ok(self
+            .cursor_position(rdr)?
+            .and_then(|(row, _)| Unit::try_from(row.saturating_sub(1)).ok()))
+    }
+
     fn begin_synchronized_update(&mut self) -> Result<()> {
         if self.enable_synchronized_output {
             if self.synchronized_update == 0 {
@@ -1363,6 +814,21 @@ impl Sig {
 #[cfg(not(test))]
 pub type Terminal = PosixTerminal;
 
+/// Notify an editor bound to a [`PtyTerminal`] that its window size has
+/// changed (there is no `SIGWINCH` for sessions).
+#[derive(Clone, Debug)]
+pub struct ResizeNotifier(Arc<UnixStream>);
+
+impl ResizeNotifier {
+    /// Redraw the edited line with the current window size of the session
+    /// terminal (to be called after `TIOCSWINSZ` on the PTY master).
+    pub fn notify(&self) -> io::Result<()> {
+        (&*self.0).write_all(b"W")
+    }
+}
+
+/// Unix terminal bound to the process stdin / stdout (or `/dev/tty`) or to
+/// arbitrary file descriptors (see [`PtyTerminal`])
 #[derive(Clone, Debug)]
 pub struct PosixTerminal {
     unsupported: bool,
@@ -1376,9 +842,32 @@ pub struct PosixTerminal {
     pipe_reader: Option<PipeReader>,
     // external print writer
     pipe_writer: Option<PipeWriter>,
+    // read end of the resize pipe, `None` when bound to the process terminal
+    session: Option<Arc<UnixStream>>,
 }
 
 impl PosixTerminal {
+    /// Bind to `input` and `output` instead of the process terminal.
+    fn from_fds(input: OwnedFd, output: OwnedFd) -> Result<(Self, ResizeNotifier)> {
+        let (reader, writer) = UnixStream::pair()?;
+        reader.set_nonblocking(true)?;
+        let tty_in = AltFd(input.into_raw_fd());
+        let tty_out = AltFd(output.into_raw_fd());
+        let terminal = Self {
+            unsupported: false,
+            tty_in,
+            is_in_a_tty: is_a_tty(tty_in),
+            tty_out,
+            is_out_a_tty: is_a_tty(tty_out),
+            close_on_drop: true,
+            raw_mode: Arc::new(AtomicBool::new(false)),
+            pipe_reader: None,
+            pipe_writer: None,
+            session: Some(Arc::new(reader)),
+        };
+        Ok((terminal, ResizeNotifier(Arc::new(writer))))
+    }
+
     fn colors_enabled(&self, config: &Config) -> bool {
         match config.color_mode() {
             ColorMode::Enabled => self.is_out_a_tty,
@@ -1424,6 +913,7 @@ impl Term for PosixTerminal {
             raw_mode: Arc::new(AtomicBool::new(false)),
             pipe_reader: None,
             pipe_writer: None,
+            session: None,
         })
     }
 
@@ -1450,14 +940,40 @@ impl Term for PosixTerminal {
         if !self.is_in_a_tty {
             return Err(ENOTTY.into());
         }
-        let (original_mode, key_map) = termios_::enable_raw_mode(self.tty_in, c.enable_signals())?;
+        // signals would be sent to the foreground process group of the session
+        let enable_signals = c.enable_signals() && self.session.is_none();
+        let (original_mode, key_map) = termios_::enable_raw_mode(self.tty_in, enable_signals)?;
 
         self.raw_mode.store(true, Ordering::SeqCst);
-        // enable bracketed paste
-        let out = if !c.enable_bracketed_paste() {
+        // enable bracketed paste / mouse reporting / kitty keyboard
+        let mut modes_on = String::new();
+        let mut modes_off = String::new();
+        for (enabled, on, off) in [
+            (
+                c.enable_bracketed_paste(),
+                BRACKETED_PASTE_ON,
+                BRACKETED_PASTE_OFF,
+            ),
+            (c.enable_mouse(), MOUSE_ON, MOUSE_OFF),
+            (
+                c.enable_kitty_keyboard(),
+                if c.enable_key_release() {
+                    KITTY_KEYBOARD_RELEASE_PUSH
+                } else {
+                    KITTY_KEYBOARD_PUSH
+                },
+                KITTY_KEYBOARD_POP,
+            ),
+        ] {
+            if enabled {
+                modes_on.push_str(on);
+                modes_off.insert_str(0, off);
+            }
+        }
+        let out = if modes_on.is_empty() {
             None
-        } else if let Err(e) = write_all(self.tty_out, BRACKETED_PASTE_ON) {
-            debug!(target: "rustyline", "Cannot enable bracketed paste: {e}");
+        } else if let Err(e) = write_all(self.tty_out, &modes_on) {
+            debug!(target: "rustyline", "Cannot enable bracketed paste / mouse / kitty keyboard: {e}");
             None
         } else {
             Some(self.tty_out)
@@ -1474,6 +990,7 @@ impl Term for PosixTerminal {
                 termios: original_mode,
                 tty_in: self.tty_in,
                 tty_out: out,
+                modes_off,
                 raw_mode: self.raw_mode.clone(),
             },
             key_map,
@@ -1488,7 +1005,7 @@ impl Term for PosixTerminal {
         key_map: PosixKeyMap,
     ) -> Result<PosixRawReader> {
         debug_assert!(!self.unsupported && self.is_in_a_tty);
-        let sig = if self.is_out_a_tty {
+        let sig = if self.is_out_a_tty && self.session.is_none() {
             Some(Sig::install_sigwinch_handler()?)
         } else {
             None
@@ -1496,6 +1013,7 @@ impl Term for PosixTerminal {
         Ok(PosixRawReader::new(
             self.tty_in,
             sig,
+            self.session.clone(),
             buffer,
             config,
             key_map,
@@ -1510,6 +1028,7 @@ impl Term for PosixTerminal {
             self.tty_out,
             Unit::from(c.tab_stop()),
             self.colors_enabled(c),
+            c.horizontal_scroll(),
             c.enable_synchronized_output(),
             c.grapheme_cluster_mode(),
             c.bell_style(),
@@ -1553,6 +1072,102 @@ impl Term for PosixTerminal {
             Ok(None)
         }
     }
+
+    /// Sessions are not suspended: `Ctrl-Z` would stop the whole process.
+    fn suspend(&mut self) -> Result<()> {
+        if self.session.is_some() {
+            return Ok(());
+        }
+        super::suspend()
+    }
+}
+
+/// Unix terminal bound to arbitrary file descriptors (like the slave side of
+/// a PTY) instead of the process terminal.
+///
+/// Signal handlers are not installed: `SIGWINCH` is replaced by
+/// [`ResizeNotifier`], `SIGINT` and `SIGTSTP` by key bindings. So several
+/// sessions can run concurrently in one process.
+#[derive(Clone, Debug)]
+pub struct PtyTerminal {
+    term: PosixTerminal,
+    resize: ResizeNotifier,
+}
+
+impl PtyTerminal {
+    /// Bind to `input` and `output`.
+    ///
+    /// Both file descriptors are closed when the terminal is dropped.
+    pub fn new(input: OwnedFd, output: OwnedFd) -> Result<Self> {
+        let (term, resize) = PosixTerminal::from_fds(input, output)?;
+        Ok(Self { term, resize })
+    }
+
+    /// Notifier of window size changes
+    #[must_use]
+    pub fn resize_notifier(&self) -> ResizeNotifier {
+        self.resize.clone()
+    }
+}
+
+impl Term for PtyTerminal {
+    type Buffer = PosixBuffer;
+    type CursorGuard = PosixCursorGuard;
+    type ExternalPrinter = ExternalPrinter;
+    type KeyMap = PosixKeyMap;
+    type Mode = PosixMode;
+    type Reader = PosixRawReader;
+    type Writer = PosixRenderer;
+
+    /// File descriptors must be provided: use [`PtyTerminal::new`] instead.
+    fn new(_: &Config) -> Result<Self> {
+        Err(io::Error::new(ErrorKind::Unsupported, "no file descriptor to read from").into())
+    }
+
+    fn is_unsupported(&self) -> bool {
+        self.term.is_unsupported()
+    }
+
+    fn is_input_tty(&self) -> bool {
+        self.term.is_input_tty()
+    }
+
+    fn is_output_tty(&self) -> bool {
+        self.term.is_output_tty()
+    }
+
+    fn enable_raw_mode(&mut self, config: &Config) -> Result<(PosixMode, PosixKeyMap)> {
+        self.term.enable_raw_mode(config)
+    }
+
+    fn create_reader(
+        &self,
+        buffer: Option<PosixBuffer>,
+        config: &Config,
+        key_map: PosixKeyMap,
+    ) -> Result<PosixRawReader> {
+        self.term.create_reader(buffer, config, key_map)
+    }
+
+    fn create_writer(&self, config: &Config) -> PosixRenderer {
+        self.term.create_writer(config)
+    }
+
+    fn writeln(&self) -> Result<()> {
+        self.term.writeln()
+    }
+
+    fn create_external_printer(&mut self) -> Result<ExternalPrinter> {
+        self.term.create_external_printer()
+    }
+
+    fn set_cursor_visibility(&mut self, visible: bool) -> Result<Option<PosixCursorGuard>> {
+        self.term.set_cursor_visibility(visible)
+    }
+
+    fn suspend(&mut self) -> Result<()> {
+        self.term.suspend()
+    }
 }
 
 #[expect(unused_must_use)]
@@ -1560,7 +1175,11 @@ impl Drop for PosixTerminal {
     fn drop(&mut self) {
         if self.close_on_drop {
             close(self.tty_in);
-            debug_assert_eq!(self.tty_in, self.tty_out);
+            if self.session.is_some() && self.tty_out != self.tty_in {
+                close(self.tty_out);
+            } else {
+                debug_assert_eq!(self.tty_in, self.tty_out);
+            }
         }
     }
 }
@@ -1728,10 +1347,53 @@ mod termios_ {
 
 #[cfg(test)]
 mod test {
-    use super::{AltFd, Position, PosixRenderer, PosixTerminal, Renderer as _};
+    use std::borrow::Cow;
+
+    use super::{
+        AltFd, Layout, Position, PosixRawReader, PosixRenderer, PosixTerminal, Renderer as _,
+    };
     use crate::config::BellStyle;
     use crate::layout::GraphemeClusterMode;
     use crate::line_buffer::{LineBuffer, NoListener};
+    use crate::prompt::{Prompt, RightPromptPosition};
+
+    #[test]
+    fn key_release() {
+        use std::io::Write as _;
+        use std::os::fd::AsRawFd as _;
+
+        use crate::config::Config;
+        use crate::keys::KeyEvent as E;
+        use crate::tty::{Event, RawReader as _};
+
+        let (input, output) = std::os::unix::net::UnixStream::pair().unwrap();
+        let mut rdr = PosixRawReader::new(
+            AltFd(input.as_raw_fd()),
+            None,
+            None,
+            None,
+            &Config::default(),
+            Default::default(),
+            None,
+            #[cfg(target_os = "macos")]
+            false,
+        );
+        (&output)
+            .write_all(b"\x1b[97;5u\x1b[97;5:3u\x1b[98;5u\x1b[98;5:3u")
+            .unwrap();
+        assert!(matches!(
+            rdr.wait_for_input(false).unwrap(),
+            Event::KeyPress(k) if k == E::ctrl('A')
+        ));
+        assert!(matches!(
+            rdr.wait_for_input(false).unwrap(),
+            Event::KeyRelease(k) if k == E::ctrl('A')
+        ));
+        // releases are skipped by `next_key`
+        assert_eq!(E::ctrl('B'), rdr.next_key(false).unwrap());
+        drop(output);
+        assert!(rdr.next_key(false).is_err());
+    }
 
     #[test]
     #[ignore]
@@ -1740,6 +1402,7 @@ mod test {
             AltFd(libc::STDOUT_FILENO),
             4,
             true,
+            false,
             true,
             GraphemeClusterMode::default(),
             BellStyle::default(),
@@ -1767,6 +1430,7 @@ mod test {
             AltFd(libc::STDOUT_FILENO),
             4,
             true,
+            false,
             true,
             GraphemeClusterMode::default(),
             BellStyle::default(),
@@ -1776,15 +1440,15 @@ mod test {
         let prompt_size = out.calculate_position(prompt, Position::default());
 
         let mut line = LineBuffer::init("", 0);
-        let old_layout = out.compute_layout(prompt_size, default_prompt, &line, None);
+        let old_layout = out.compute_layout(prompt, prompt_size, default_prompt, &line, None);
         assert_eq!(Position { col: 2, row: 0 }, old_layout.cursor);
         assert_eq!(old_layout.cursor, old_layout.end);
 
         assert_eq!(
             Some(true),
-            line.insert('a', out.cols - prompt_size.col + 1, &mut NoListener)
+            line.insert('a', out.ansi.cols - prompt_size.col + 1, &mut NoListener)
         );
-        let new_layout = out.compute_layout(prompt_size, default_prompt, &line, None);
+        let new_layout = out.compute_layout(prompt, prompt_size, default_prompt, &line, None);
         assert_eq!(Position { col: 1, row: 1 }, new_layout.cursor);
         assert_eq!(new_layout.cursor, new_layout.end);
         out.refresh_line(prompt, &line, None, Some(&old_layout), &new_layout, None)
@@ -1792,7 +1456,205 @@ mod test {
         #[rustfmt::skip]
         assert_eq!(
             "\r\u{1b}[K> aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\u{1b}[1C",
-            out.buffer
+            out.ansi.buffer
+        );
+    }
+
+    #[test]
+    fn test_vertical_scroll() {
+        let mut out = PosixRenderer::new(
+            AltFd(libc::STDOUT_FILENO),
+            4,
+            false,
+            false,
+            false,
+            GraphemeClusterMode::default(),
+            BellStyle::default(),
+        );
+        let prompt = "> ";
+        let prompt_size = out.calculate_position(prompt, Position::default());
+        let text = (0..30)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let line = LineBuffer::init(&text, text.len());
+
+        let mut layout = out.compute_layout(prompt, prompt_size, true, &line, None);
+        layout.update_viewport(0, out.get_rows());
+        assert_eq!(Position { col: 2, row: 29 }, layout.cursor);
+        assert_eq!(7, layout.rows_above());
+        assert_eq!(0, layout.rows_below());
+        out.refresh_line(prompt, &line, None, None, &layout, None)
+            .unwrap();
+        let expected = (7..30)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            format!("-- 7 more row(s) above --\n{expected}\r\u{1b}[2C"),
+            out.ansi.buffer
+        );
+    }
+
+    #[test]
+    fn test_horizontal_scroll() {
+        let mut out = PosixRenderer::new(
+            AltFd(libc::STDOUT_FILENO),
+            4,
+            false,
+            true,
+            false,
+            GraphemeClusterMode::default(),
+            BellStyle::default(),
+        );
+        let prompt = "> ";
+        let prompt_size = out.calculate_position(prompt, Position::default());
+        let text = "0123456789".repeat(10);
+        let line = LineBuffer::init(&text, text.len());
+
+        let old_layout = Layout::default();
+        let mut layout = out.compute_layout(prompt, prompt_size, true, &line, None);
+        layout.scroll(&old_layout, out.get_rows());
+        assert_eq!(Position { col: 78, row: 0 }, layout.cursor);
+        assert_eq!(layout.cursor, layout.end);
+        out.refresh_line(prompt, &line, None, None, &layout, None)
+            .unwrap();
+        assert_eq!(format!("> <{}\r\u{1b}[78C", &text[25..]), out.ansi.buffer);
+
+        // multi-row prompt
+        let prompt = "info\n> ";
+        let prompt_size = out.calculate_position(prompt, Position::default());
+        let mut layout = out.compute_layout(prompt, prompt_size, true, &line, None);
+        layout.scroll(&old_layout, out.get_rows());
+        assert_eq!(Position { col: 78, row: 1 }, layout.cursor);
+        assert_eq!(layout.cursor, layout.end);
+        out.refresh_line(prompt, &line, None, None, &layout, None)
+            .unwrap();
+        assert_eq!(
+            format!("info\n> <{}\r\u{1b}[78C", &text[25..]),
+            out.ansi.buffer
+        );
+    }
+
+    #[test]
+    fn test_continuation_prompt() {
+        struct MultiLinePrompt(&'static str);
+        impl Prompt for MultiLinePrompt {
+            fn raw(&self) -> &str {
+                self.0
+            }
+
+            fn continuation_raw(&self, line_number: usize) -> Option<Cow<'_, str>> {
+                Some(Cow::Owned(format!("{line_number}| ")))
+            }
+        }
+
+        let mut out = PosixRenderer::new(
+            AltFd(libc::STDOUT_FILENO),
+            4,
+            false,
+            false,
+            false,
+            GraphemeClusterMode::default(),
+            BellStyle::default(),
+        );
+        let prompt = MultiLinePrompt("info\n> ");
+        let prompt_size = out.calculate_position(prompt.raw(), Position::default());
+        assert_eq!(Position { col: 2, row: 1 }, prompt_size);
+
+        let line = LineBuffer::init("abc\ndef\ng", 6);
+        let layout = out.compute_layout(&prompt, prompt_size, true, &line, None);
+        assert_eq!(Position { col: 5, row: 2 }, layout.cursor);
+        assert_eq!(Position { col: 4, row: 3 }, layout.end);
+        out.refresh_line(&prompt, &line, None, None, &layout, None)
+            .unwrap();
+        assert_eq!(
+            "info\n> abc\n2| def\n3| g\u{1b}[1A\r\u{1b}[5C",
+            out.ansi.buffer
+        );
+
+        // no newline added after a prompt ending with a newline
+        let prompt = MultiLinePrompt("info\n");
+        let prompt_size = out.calculate_position(prompt.raw(), Position::default());
+        let line = LineBuffer::init("", 0);
+        let layout = out.compute_layout(&prompt, prompt_size, true, &line, None);
+        assert_eq!(Position { col: 0, row: 1 }, layout.end);
+        out.refresh_line(&prompt, &line, None, None, &layout, None)
+            .unwrap();
+        assert_eq!("info\n\r", out.ansi.buffer);
+    }
+
+    #[test]
+    fn test_right_prompt() {
+        struct RightPrompt(RightPromptPosition);
+        impl Prompt for RightPrompt {
+            fn raw(&self) -> &str {
+                "> "
+            }
+
+            fn right_raw(&self) -> Option<&str> {
+                Some("main")
+            }
+
+            fn right_position(&self) -> RightPromptPosition {
+                self.0
+            }
+        }
+
+        let mut out = PosixRenderer::new(
+            AltFd(libc::STDOUT_FILENO),
+            4,
+            true,
+            true,
+            false,
+            GraphemeClusterMode::default(),
+            BellStyle::default(),
+        );
+        let prompt = RightPrompt(RightPromptPosition::FirstRow);
+        let prompt_size = out.calculate_position(prompt.raw(), Position::default());
+        let compute = |out: &PosixRenderer, prompt: &RightPrompt, line: &LineBuffer| {
+            let mut layout = out.compute_layout(prompt, prompt_size, true, line, None);
+            layout.right_prompt = out.compute_right_prompt(&layout, "main", prompt.0, line, None);
+            layout
+        };
+
+        let line = LineBuffer::init("abc", 3);
+        let layout = compute(&out, &prompt, &line);
+        assert_eq!(Some(Position { col: 75, row: 0 }), layout.right_prompt);
+        out.refresh_line(&prompt, &line, None, None, &layout, None)
+            .unwrap();
+        assert_eq!("> abc\r\u{1b}[75Cmain\r\u{1b}[5C", out.ansi.buffer);
+
+        // input grows into the right prompt
+        let text = "a".repeat(73);
+        let line = LineBuffer::init(&text, 0);
+        let layout = compute(&out, &prompt, &line);
+        assert_eq!(None, layout.right_prompt);
+        out.refresh_line(&prompt, &line, None, None, &layout, None)
+            .unwrap();
+        assert_eq!(format!("> {text}\r\u{1b}[2C"), out.ansi.buffer);
+
+        // right prompt is drawn on the first row by default
+        let line = LineBuffer::init("abc\ndef", 0);
+        let layout = compute(&out, &prompt, &line);
+        assert_eq!(Some(Position { col: 75, row: 0 }), layout.right_prompt);
+        out.refresh_line(&prompt, &line, None, None, &layout, None)
+            .unwrap();
+        assert_eq!(
+            "> abc\ndef\u{1b}[1A\r\u{1b}[75Cmain\r\u{1b}[2C",
+            out.ansi.buffer
+        );
+
+        // right prompt is drawn on the last row
+        let prompt = RightPrompt(RightPromptPosition::LastRow);
+        let line = LineBuffer::init("abc\ndef", 0);
+        let layout = compute(&out, &prompt, &line);
+        assert_eq!(Some(Position { col: 75, row: 1 }), layout.right_prompt);
+        out.refresh_line(&prompt, &line, None, None, &layout, None)
+            .unwrap();
+        assert_eq!(
+            "> abc\ndef\r\u{1b}[75Cmain\u{1b}[1A\r\u{1b}[2C",
+            out.ansi.buffer
         );
     }
 }
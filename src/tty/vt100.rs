@@ -0,0 +1,760 @@
+//! Decode the keys sent by VT100 compatible terminals
+use log::debug;
+
+use crate::Result;
+use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E, Modifiers as M, Mouse, MouseButton};
+
+const UP: char = 'A'; // kcuu1, kUP*
+const DOWN: char = 'B'; // kcud1, kDN*
+const RIGHT: char = 'C'; // kcuf1, kRIT*
+const LEFT: char = 'D'; // kcub1, kLFT*
+const END: char = 'F'; // kend*
+const HOME: char = 'H'; // khom*
+const INSERT: char = '2'; // kic*
+const DELETE: char = '3'; // kdch1, kDC*
+const PAGE_UP: char = '5'; // kpp, kPRV*
+const PAGE_DOWN: char = '6'; // knp, kNXT*
+
+const RXVT_HOME: char = '7';
+const RXVT_END: char = '8';
+
+const SHIFT: char = '2';
+const ALT: char = '3';
+const ALT_SHIFT: char = '4';
+const CTRL: char = '5';
+const CTRL_SHIFT: char = '6';
+const CTRL_ALT: char = '7';
+const CTRL_ALT_SHIFT: char = '8';
+
+const RXVT_SHIFT: char = '$';
+const RXVT_CTRL: char = '\x1e';
+const RXVT_CTRL_SHIFT: char = '@';
+
+/// Escape sequences decoder
+pub trait Vt100Decoder {
+    /// Blocking read of the next char
+    fn read_char(&mut self) -> Result<char>;
+    /// Tell if a char is available (without blocking for longer than the
+    /// `keyseq_timeout`) after `ESC ESC`
+    fn poll_sequence(&mut self) -> bool;
+    /// Key release reported by the kitty keyboard protocol (ignored by
+    /// default: the sequence is then decoded as an unknown one)
+    fn key_released(&mut self, key: KeyEvent) {
+        let _ = key;
+    }
+
+    /// Handle \E <seq1> sequences
+    // https://invisible-island.net/xterm/xterm-function-keys.html
+    fn escape_sequence(&mut self) -> Result<KeyEvent> {
+        self._do_escape_sequence(true)
+    }
+
+    /// Don't call directly, call `Vt100Decoder::escape_sequence` instead
+    fn _do_escape_sequence(&mut self, allow_recurse: bool) -> Result<KeyEvent> {
+        // Read the next byte representing the escape sequence.
+        let seq1 = self.read_char()?;
+        if seq1 == '[' {
+            // \E[ sequences. (CSI)
+            self.escape_csi()
+        } else if seq1 == 'O' {
+            // xterm
+            // \EO sequences. (SS3)
+            self.escape_o()
+        } else if seq1 == '\x1b' {
+            // \E\E — used by rxvt, iTerm (under default config), etc.
+            // ```
+            // \E\E[A => Alt-Up
+            // \E\E[B => Alt-Down
+            // \E\E[C => Alt-Right
+            // \E\E[D => Alt-Left
+            // ```
+            //
+            // In general this more or less works just adding ALT to an existing
+            // key, but has a wrinkle in that `ESC ESC` without anything
+            // following should be interpreted as the escape key.
+            //
+            // We handle this by polling to see if there's anything coming
+            // within our timeout, and if so, recursing once, but adding alt to
+            // what we read.
+            if !allow_recurse {
+                return Ok(E::ESC);
+            }
+            if self.poll_sequence() {
+                // recurse, and add the alt modifier.
+                let E(k, m) = self._do_escape_sequence(false)?;
+                Ok(E(k, m | M::ALT))
+            } else {
+                Ok(E::ESC)
+            }
+        } else {
+            Ok(E::alt(seq1))
+        }
+    }
+
+    /// Handle \E[ <seq2> escape sequences
+    fn escape_csi(&mut self) -> Result<KeyEvent> {
+        let seq2 = self.read_char()?;
+        if seq2.is_ascii_digit() {
+            // read parameters up to the final byte
+            let mut seq = String::new();
+            let end = loop {
+                let c = self.read_char()?;
+                seq.push(c);
+                if !matches!(c, '0'..='9' | ';' | ':') {
+                    break c;
+                }
+            };
+            if end == 'u' {
+                // kitty keyboard protocol
+                seq.pop();
+                seq.insert(0, seq2);
+                let (key, released) = kitty_key(&seq);
+                if released {
+                    self.key_released(key);
+                    return Ok(E(K::UnknownEscSeq, M::NONE));
+                }
+                return Ok(key);
+            }
+            match seq2 {
+                '0' | '9' => {
+                    debug!(target: "rustyline", "unsupported esc sequence: \\E[{seq2}{seq}");
+                    Ok(E(K::UnknownEscSeq, M::NONE))
+                }
+                _ => {
+                    // Extended escape, replay additional bytes.
+                    let mut replay = Replay {
+                        chars: seq.chars(),
+                        decoder: self,
+                    };
+                    replay.extended_escape(seq2)
+                }
+            }
+        } else if seq2 == '<' {
+            self.escape_sgr_mouse()
+        } else if seq2 == '[' {
+            let seq3 = self.read_char()?;
+            // Linux console
+            Ok(match seq3 {
+                'A' => E(K::F(1), M::NONE),
+                'B' => E(K::F(2), M::NONE),
+                'C' => E(K::F(3), M::NONE),
+                'D' => E(K::F(4), M::NONE),
+                'E' => E(K::F(5), M::NONE),
+                _ => {
+                    debug!(target: "rustyline", "unsupported esc sequence: \\E[[{seq3:?}");
+                    E(K::UnknownEscSeq, M::NONE)
+                }
+            })
+        } else {
+            // ANSI
+            Ok(match seq2 {
+                UP => E(K::Up, M::NONE),
+                DOWN => E(K::Down, M::NONE),
+                RIGHT => E(K::Right, M::NONE),
+                LEFT => E(K::Left, M::NONE),
+                //'E' => E(K::, M::), // Ignore
+                END => E(K::End, M::NONE),
+                //'G' => E(K::, M::), // Ignore
+                HOME => E(K::Home, M::NONE), // khome
+                //'J' => E(K::, M::), // clr_eos
+                //'K' => E(K::, M::), // clr_eol
+                //'L' => E(K::, M::), // il1
+                //'M' => E(K::, M::), // kmous
+                //'P' => E(K::Delete, M::NONE), // dch1
+                'Z' => E(K::BackTab, M::NONE),
+                'a' => E(K::Up, M::SHIFT),    // rxvt: kind or kUP
+                'b' => E(K::Down, M::SHIFT),  // rxvt: kri or kDN
+                'c' => E(K::Right, M::SHIFT), // rxvt
+                'd' => E(K::Left, M::SHIFT),  // rxvt
+                _ => {
+                    debug!(target: "rustyline", "unsupported esc sequence: \\E[{seq2:?}");
+                    E(K::UnknownEscSeq, M::NONE)
+                }
+            })
+        }
+    }
+
+    /// Handle \E[< <button> ; <col> ; <row> (M|m) SGR mouse sequences
+    fn escape_sgr_mouse(&mut self) -> Result<KeyEvent> {
+        let mut params = [0u32; 3];
+        let mut i = 0;
+        let end = loop {
+            match self.read_char()? {
+                c @ '0'..='9' => {
+                    params[i] = params[i]
+                        .saturating_mul(10)
+                        .saturating_add(c.to_digit(10).unwrap());
+                }
+                ';' if i < params.len() - 1 => i += 1,
+                c => break c,
+            }
+        };
+        let [cb, col, row] = params;
+        let button = match cb & !0b1_1100 {
+            0 => Some(MouseButton::Left),
+            1 => Some(MouseButton::Middle),
+            2 => Some(MouseButton::Right),
+            64 => Some(MouseButton::WheelUp),
+            65 => Some(MouseButton::WheelDown),
+            _ => None, // motion, extra buttons
+        };
+        let (Some(button), 2, 'M' | 'm', 1.., 1..) = (button, i, end, col, row) else {
+            debug!(target: "rustyline", "unsupported esc sequence: \\E[<{params:?}{end:?}");
+            return Ok(E(K::UnknownEscSeq, M::NONE));
+        };
+        let mut mods = M::NONE;
+        if cb & 4 != 0 {
+            mods |= M::SHIFT;
+        }
+        if cb & 8 != 0 {
+            mods |= M::ALT;
+        }
+        if cb & 16 != 0 {
+            mods |= M::CTRL;
+        }
+        let mouse = Mouse {
+            button,
+            pressed: end == 'M',
+            col: u16::try_from(col - 1).unwrap_or(u16::MAX),
+            row: u16::try_from(row - 1).unwrap_or(u16::MAX),
+        };
+        Ok(E(K::Mouse(mouse), mods))
+    }
+
+    /// Handle \E[ <seq2:digit> escape sequences
+    #[expect(clippy::cognitive_complexity)]
+    fn extended_escape(&mut self, seq2: char) -> Result<KeyEvent> {
+        let seq3 = self.read_char()?;
+        if seq3 == '~' {
+            Ok(match seq2 {
+                '1' | RXVT_HOME => E(K::Home, M::NONE), // tmux, xrvt
+                INSERT => E(K::Insert, M::NONE),
+                DELETE => E(K::Delete, M::NONE),
+                '4' | RXVT_END => E(K::End, M::NONE), // tmux, xrvt
+                PAGE_UP => E(K::PageUp, M::NONE),
+                PAGE_DOWN => E(K::PageDown, M::NONE),
+                _ => {
+                    debug!(target: "rustyline",
+                           "unsupported esc sequence: \\E[{seq2}~");
+                    E(K::UnknownEscSeq, M::NONE)
+                }
+            })
+        } else if seq3.is_ascii_digit() {
+            let seq4 = self.read_char()?;
+            if seq4 == '~' {
+                Ok(match (seq2, seq3) {
+                    ('1', '1') => E(K::F(1), M::NONE),  // rxvt-unicode
+                    ('1', '2') => E(K::F(2), M::NONE),  // rxvt-unicode
+                    ('1', '3') => E(K::F(3), M::NONE),  // rxvt-unicode
+                    ('1', '4') => E(K::F(4), M::NONE),  // rxvt-unicode
+                    ('1', '5') => E(K::F(5), M::NONE),  // kf5
+                    ('1', '7') => E(K::F(6), M::NONE),  // kf6
+                    ('1', '8') => E(K::F(7), M::NONE),  // kf7
+                    ('1', '9') => E(K::F(8), M::NONE),  // kf8
+                    ('2', '0') => E(K::F(9), M::NONE),  // kf9
+                    ('2', '1') => E(K::F(10), M::NONE), // kf10
+                    ('2', '3') => E(K::F(11), M::NONE), // kf11
+                    ('2', '4') => E(K::F(12), M::NONE), // kf12
+                    //('6', '2') => KeyCode::ScrollUp,
+                    //('6', '3') => KeyCode::ScrollDown,
+                    _ => {
+                        debug!(target: "rustyline",
+                               "unsupported esc sequence: \\E[{seq2}{seq3}~");
+                        E(K::UnknownEscSeq, M::NONE)
+                    }
+                })
+            } else if seq4 == ';' {
+                let seq5 = self.read_char()?;
+                if seq5.is_ascii_digit() {
+                    let seq6 = self.read_char()?;
+                    if seq6.is_ascii_digit() {
+                        self.read_char()?; // 'R' expected
+                        Ok(E(K::UnknownEscSeq, M::NONE))
+                    } else if seq6 == 'R' {
+                        Ok(E(K::UnknownEscSeq, M::NONE))
+                    } else if seq6 == '~' {
+                        Ok(match (seq2, seq3, seq5) {
+                            ('1', '5', CTRL) => E(K::F(5), M::CTRL),
+                            //('1', '5', '6') => E(K::F(17), M::CTRL),
+                            ('1', '7', CTRL) => E(K::F(6), M::CTRL),
+                            //('1', '7', '6') => E(K::F(18), M::CTRL),
+                            ('1', '8', CTRL) => E(K::F(7), M::CTRL),
+                            ('1', '9', CTRL) => E(K::F(8), M::CTRL),
+                            //('1', '9', '6') => E(K::F(19), M::CTRL),
+                            ('2', '0', CTRL) => E(K::F(9), M::CTRL),
+                            //('2', '0', '6') => E(K::F(21), M::CTRL),
+                            ('2', '1', CTRL) => E(K::F(10), M::CTRL),
+                            //('2', '1', '6') => E(K::F(22), M::CTRL),
+                            ('2', '3', CTRL) => E(K::F(11), M::CTRL),
+                            //('2', '3', '6') => E(K::F(23), M::CTRL),
+                            ('2', '4', CTRL) => E(K::F(12), M::CTRL),
+                            //('2', '4', '6') => E(K::F(24), M::CTRL),
+                            _ => {
+                                debug!(target: "rustyline",
+                                       "unsupported esc sequence: \\E[{seq2}{seq3};{seq5}~");
+                                E(K::UnknownEscSeq, M::NONE)
+                            }
+                        })
+                    } else {
+                        debug!(target: "rustyline",
+                               "unsupported esc sequence: \\E[{seq2}{seq3};{seq5}{seq6}");
+                        Ok(E(K::UnknownEscSeq, M::NONE))
+                    }
+                } else {
+                    debug!(target: "rustyline",
+                           "unsupported esc sequence: \\E[{seq2}{seq3};{seq5:?}");
+                    Ok(E(K::UnknownEscSeq, M::NONE))
+                }
+            } else if seq4.is_ascii_digit() {
+                let seq5 = self.read_char()?;
+                if seq5 == '~' {
+                    Ok(match (seq2, seq3, seq4) {
+                        ('2', '0', '0') => E(K::BracketedPasteStart, M::NONE),
+                        ('2', '0', '1') => E(K::BracketedPasteEnd, M::NONE),
+                        _ => {
+                            debug!(target: "rustyline",
+                                   "unsupported esc sequence: \\E[{seq2}{seq3}{seq4}~");
+                            E(K::UnknownEscSeq, M::NONE)
+                        }
+                    })
+                } else {
+                    debug!(target: "rustyline",
+                           "unsupported esc sequence: \\E[{seq2}{seq3}{seq4}{seq5}");
+                    Ok(E(K::UnknownEscSeq, M::NONE))
+                }
+            } else {
+                debug!(target: "rustyline",
+                       "unsupported esc sequence: \\E[{seq2}{seq3}{seq4:?}");
+                Ok(E(K::UnknownEscSeq, M::NONE))
+            }
+        } else if seq3 == ';' {
+            let seq4 = self.read_char()?;
+            if seq4.is_ascii_digit() {
+                let seq5 = self.read_char()?;
+                if seq5.is_ascii_digit() {
+                    self.read_char()?; // 'R' expected
+                    //
+                    //('1', '0', UP) => E(K::, M::), // Alt + Shift + Up
+                    Ok(E(K::UnknownEscSeq, M::NONE))
+                } else if seq2 == '1' {
+                    Ok(match (seq4, seq5) {
+                        (SHIFT, UP) => E(K::Up, M::SHIFT),     // ~ key_sr
+                        (SHIFT, DOWN) => E(K::Down, M::SHIFT), // ~ key_sf
+                        (SHIFT, RIGHT) => E(K::Right, M::SHIFT),
+                        (SHIFT, LEFT) => E(K::Left, M::SHIFT),
+                        (SHIFT, END) => E(K::End, M::SHIFT), // kEND
+                        (SHIFT, HOME) => E(K::Home, M::SHIFT), // kHOM
+                        //('2', 'P') => E(K::F(13), M::NONE),
+                        //('2', 'Q') => E(K::F(14), M::NONE),
+                        //('2', 'S') => E(K::F(16), M::NONE),
+                        (ALT, UP) => E(K::Up, M::ALT),
+                        (ALT, DOWN) => E(K::Down, M::ALT),
+                        (ALT, RIGHT) => E(K::Right, M::ALT),
+                        (ALT, LEFT) => E(K::Left, M::ALT),
+                        (ALT, END) => E(K::End, M::ALT),
+                        (ALT, HOME) => E(K::Home, M::ALT),
+                        (ALT_SHIFT, UP) => E(K::Up, M::ALT_SHIFT),
+                        (ALT_SHIFT, DOWN) => E(K::Down, M::ALT_SHIFT),
+                        (ALT_SHIFT, RIGHT) => E(K::Right, M::ALT_SHIFT),
+                        (ALT_SHIFT, LEFT) => E(K::Left, M::ALT_SHIFT),
+                        (ALT_SHIFT, END) => E(K::End, M::ALT_SHIFT),
+                        (ALT_SHIFT, HOME) => E(K::Home, M::ALT_SHIFT),
+                        (CTRL, UP) => E(K::Up, M::CTRL),
+                        (CTRL, DOWN) => E(K::Down, M::CTRL),
+                        (CTRL, RIGHT) => E(K::Right, M::CTRL),
+                        (CTRL, LEFT) => E(K::Left, M::CTRL),
+                        (CTRL, END) => E(K::End, M::CTRL),
+                        (CTRL, HOME) => E(K::Home, M::CTRL),
+                        (CTRL, 'P') => E(K::F(1), M::CTRL),
+                        (CTRL, 'Q') => E(K::F(2), M::CTRL),
+                        (CTRL, 'S') => E(K::F(4), M::CTRL),
+                        (CTRL, 'p') => E(K::Char('0'), M::CTRL),
+                        (CTRL, 'q') => E(K::Char('1'), M::CTRL),
+                        (CTRL, 'r') => E(K::Char('2'), M::CTRL),
+                        (CTRL, 's') => E(K::Char('3'), M::CTRL),
+                        (CTRL, 't') => E(K::Char('4'), M::CTRL),
+                        (CTRL, 'u') => E(K::Char('5'), M::CTRL),
+                        (CTRL, 'v') => E(K::Char('6'), M::CTRL),
+                        (CTRL, 'w') => E(K::Char('7'), M::CTRL),
+                        (CTRL, 'x') => E(K::Char('8'), M::CTRL),
+                        (CTRL, 'y') => E(K::Char('9'), M::CTRL),
+                        (CTRL_SHIFT, UP) => E(K::Up, M::CTRL_SHIFT),
+                        (CTRL_SHIFT, DOWN) => E(K::Down, M::CTRL_SHIFT),
+                        (CTRL_SHIFT, RIGHT) => E(K::Right, M::CTRL_SHIFT),
+                        (CTRL_SHIFT, LEFT) => E(K::Left, M::CTRL_SHIFT),
+                        (CTRL_SHIFT, END) => E(K::End, M::CTRL_SHIFT),
+                        (CTRL_SHIFT, HOME) => E(K::Home, M::CTRL_SHIFT),
+                        //('6', 'P') => E(K::F(13), M::CTRL),
+                        //('6', 'Q') => E(K::F(14), M::CTRL),
+                        //('6', 'S') => E(K::F(16), M::CTRL),
+                        (CTRL_SHIFT, 'p') => E(K::Char('0'), M::CTRL_SHIFT),
+                        (CTRL_SHIFT, 'q') => E(K::Char('1'), M::CTRL_SHIFT),
+                        (CTRL_SHIFT, 'r') => E(K::Char('2'), M::CTRL_SHIFT),
+                        (CTRL_SHIFT, 's') => E(K::Char('3'), M::CTRL_SHIFT),
+                        (CTRL_SHIFT, 't') => E(K::Char('4'), M::CTRL_SHIFT),
+                        (CTRL_SHIFT, 'u') => E(K::Char('5'), M::CTRL_SHIFT),
+                        (CTRL_SHIFT, 'v') => E(K::Char('6'), M::CTRL_SHIFT),
+                        (CTRL_SHIFT, 'w') => E(K::Char('7'), M::CTRL_SHIFT),
+                        (CTRL_SHIFT, 'x') => E(K::Char('8'), M::CTRL_SHIFT),
+                        (CTRL_SHIFT, 'y') => E(K::Char('9'), M::CTRL_SHIFT),
+                        (CTRL_ALT, UP) => E(K::Up, M::CTRL_ALT),
+                        (CTRL_ALT, DOWN) => E(K::Down, M::CTRL_ALT),
+                        (CTRL_ALT, RIGHT) => E(K::Right, M::CTRL_ALT),
+                        (CTRL_ALT, LEFT) => E(K::Left, M::CTRL_ALT),
+                        (CTRL_ALT, END) => E(K::End, M::CTRL_ALT),
+                        (CTRL_ALT, HOME) => E(K::Home, M::CTRL_ALT),
+                        (CTRL_ALT, 'p') => E(K::Char('0'), M::CTRL_ALT),
+                        (CTRL_ALT, 'q') => E(K::Char('1'), M::CTRL_ALT),
+                        (CTRL_ALT, 'r') => E(K::Char('2'), M::CTRL_ALT),
+                        (CTRL_ALT, 's') => E(K::Char('3'), M::CTRL_ALT),
+                        (CTRL_ALT, 't') => E(K::Char('4'), M::CTRL_ALT),
+                        (CTRL_ALT, 'u') => E(K::Char('5'), M::CTRL_ALT),
+                        (CTRL_ALT, 'v') => E(K::Char('6'), M::CTRL_ALT),
+                        (CTRL_ALT, 'w') => E(K::Char('7'), M::CTRL_ALT),
+                        (CTRL_ALT, 'x') => E(K::Char('8'), M::CTRL_ALT),
+                        (CTRL_ALT, 'y') => E(K::Char('9'), M::CTRL_ALT),
+                        (CTRL_ALT_SHIFT, UP) => E(K::Up, M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, DOWN) => E(K::Down, M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, RIGHT) => E(K::Right, M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, LEFT) => E(K::Left, M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, END) => E(K::End, M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, HOME) => E(K::Home, M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, 'p') => E(K::Char('0'), M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, 'q') => E(K::Char('1'), M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, 'r') => E(K::Char('2'), M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, 's') => E(K::Char('3'), M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, 't') => E(K::Char('4'), M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, 'u') => E(K::Char('5'), M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, 'v') => E(K::Char('6'), M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, 'w') => E(K::Char('7'), M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, 'x') => E(K::Char('8'), M::CTRL_ALT_SHIFT),
+                        (CTRL_ALT_SHIFT, 'y') => E(K::Char('9'), M::CTRL_ALT_SHIFT),
+                        // Meta + arrow on (some?) Macs when using iTerm defaults
+                        ('9', UP) => E(K::Up, M::ALT),
+                        ('9', DOWN) => E(K::Down, M::ALT),
+                        ('9', RIGHT) => E(K::Right, M::ALT),
+                        ('9', LEFT) => E(K::Left, M::ALT),
+                        _ => {
+                            debug!(target: "rustyline",
+                                   "unsupported esc sequence: \\E[1;{seq4}{seq5:?}");
+                            E(K::UnknownEscSeq, M::NONE)
+                        }
+                    })
+                } else if seq5 == '~' {
+                    Ok(match (seq2, seq4) {
+                        (INSERT, SHIFT) => E(K::Insert, M::SHIFT),
+                        (INSERT, ALT) => E(K::Insert, M::ALT),
+                        (INSERT, ALT_SHIFT) => E(K::Insert, M::ALT_SHIFT),
+                        (INSERT, CTRL) => E(K::Insert, M::CTRL),
+                        (INSERT, CTRL_SHIFT) => E(K::Insert, M::CTRL_SHIFT),
+                        (INSERT, CTRL_ALT) => E(K::Insert, M::CTRL_ALT),
+                        (INSERT, CTRL_ALT_SHIFT) => E(K::Insert, M::CTRL_ALT_SHIFT),
+                        (DELETE, SHIFT) => E(K::Delete, M::SHIFT),
+                        (DELETE, ALT) => E(K::Delete, M::ALT),
+                        (DELETE, ALT_SHIFT) => E(K::Delete, M::ALT_SHIFT),
+                        (DELETE, CTRL) => E(K::Delete, M::CTRL),
+                        (DELETE, CTRL_SHIFT) => E(K::Delete, M::CTRL_SHIFT),
+                        (DELETE, CTRL_ALT) => E(K::Delete, M::CTRL_ALT),
+                        (DELETE, CTRL_ALT_SHIFT) => E(K::Delete, M::CTRL_ALT_SHIFT),
+                        (PAGE_UP, SHIFT) => E(K::PageUp, M::SHIFT),
+                        (PAGE_UP, ALT) => E(K::PageUp, M::ALT),
+                        (PAGE_UP, ALT_SHIFT) => E(K::PageUp, M::ALT_SHIFT),
+                        (PAGE_UP, CTRL) => E(K::PageUp, M::CTRL),
+                        (PAGE_UP, CTRL_SHIFT) => E(K::PageUp, M::CTRL_SHIFT),
+                        (PAGE_UP, CTRL_ALT) => E(K::PageUp, M::CTRL_ALT),
+                        (PAGE_UP, CTRL_ALT_SHIFT) => E(K::PageUp, M::CTRL_ALT_SHIFT),
+                        (PAGE_DOWN, SHIFT) => E(K::PageDown, M::SHIFT),
+                        (PAGE_DOWN, ALT) => E(K::PageDown, M::ALT),
+                        (PAGE_DOWN, ALT_SHIFT) => E(K::PageDown, M::ALT_SHIFT),
+                        (PAGE_DOWN, CTRL) => E(K::PageDown, M::CTRL),
+                        (PAGE_DOWN, CTRL_SHIFT) => E(K::PageDown, M::CTRL_SHIFT),
+                        (PAGE_DOWN, CTRL_ALT) => E(K::PageDown, M::CTRL_ALT),
+                        (PAGE_DOWN, CTRL_ALT_SHIFT) => E(K::PageDown, M::CTRL_ALT_SHIFT),
+                        _ => {
+                            debug!(target: "rustyline",
+                                   "unsupported esc sequence: \\E[{seq2};{seq4:?}~");
+                            E(K::UnknownEscSeq, M::NONE)
+                        }
+                    })
+                } else {
+                    debug!(target: "rustyline",
+                           "unsupported esc sequence: \\E[{seq2};{seq4}{seq5:?}");
+                    Ok(E(K::UnknownEscSeq, M::NONE))
+                }
+            } else {
+                debug!(target: "rustyline",
+                       "unsupported esc sequence: \\E[{seq2};{seq4:?}");
+                Ok(E(K::UnknownEscSeq, M::NONE))
+            }
+        } else {
+            Ok(match (seq2, seq3) {
+                (DELETE, RXVT_CTRL) => E(K::Delete, M::CTRL),
+                (DELETE, RXVT_CTRL_SHIFT) => E(K::Delete, M::CTRL_SHIFT),
+                (CTRL, UP) => E(K::Up, M::CTRL),
+                (CTRL, DOWN) => E(K::Down, M::CTRL),
+                (CTRL, RIGHT) => E(K::Right, M::CTRL),
+                (CTRL, LEFT) => E(K::Left, M::CTRL),
+                (PAGE_UP, RXVT_CTRL) => E(K::PageUp, M::CTRL),
+                (PAGE_UP, RXVT_SHIFT) => E(K::PageUp, M::SHIFT),
+                (PAGE_UP, RXVT_CTRL_SHIFT) => E(K::PageUp, M::CTRL_SHIFT),
+                (PAGE_DOWN, RXVT_CTRL) => E(K::PageDown, M::CTRL),
+                (PAGE_DOWN, RXVT_SHIFT) => E(K::PageDown, M::SHIFT),
+                (PAGE_DOWN, RXVT_CTRL_SHIFT) => E(K::PageDown, M::CTRL_SHIFT),
+                (RXVT_HOME, RXVT_CTRL) => E(K::Home, M::CTRL),
+                (RXVT_HOME, RXVT_SHIFT) => E(K::Home, M::SHIFT),
+                (RXVT_HOME, RXVT_CTRL_SHIFT) => E(K::Home, M::CTRL_SHIFT),
+                (RXVT_END, RXVT_CTRL) => E(K::End, M::CTRL), // kEND5 or kel
+                (RXVT_END, RXVT_SHIFT) => E(K::End, M::SHIFT),
+                (RXVT_END, RXVT_CTRL_SHIFT) => E(K::End, M::CTRL_SHIFT),
+                _ => {
+                    debug!(target: "rustyline",
+                           "unsupported esc sequence: \\E[{seq2}{seq3:?}");
+                    E(K::UnknownEscSeq, M::NONE)
+                }
+            })
+        }
+    }
+
+    /// Handle \EO <seq2> escape sequences
+    fn escape_o(&mut self) -> Result<KeyEvent> {
+        let seq2 = self.read_char()?;
+        Ok(match seq2 {
+            UP => E(K::Up, M::NONE),
+            DOWN => E(K::Down, M::NONE),
+            RIGHT => E(K::Right, M::NONE),
+            LEFT => E(K::Left, M::NONE),
+            //'E' => E(K::, M::),// key_b2, kb2
+            END => E(K::End, M::NONE),   // kend
+            HOME => E(K::Home, M::NONE), // khome
+            'M' => E::ENTER,             // kent
+            'P' => E(K::F(1), M::NONE),  // kf1
+            'Q' => E(K::F(2), M::NONE),  // kf2
+            'R' => E(K::F(3), M::NONE),  // kf3
+            'S' => E(K::F(4), M::NONE),  // kf4
+            'a' => E(K::Up, M::CTRL),
+            'b' => E(K::Down, M::CTRL),
+            'c' => E(K::Right, M::CTRL), // rxvt
+            'd' => E(K::Left, M::CTRL),  // rxvt
+            'l' => E(K::F(8), M::NONE),
+            't' => E(K::F(5), M::NONE),  // kf5 or kb1
+            'u' => E(K::F(6), M::NONE),  // kf6 or kb2
+            'v' => E(K::F(7), M::NONE),  // kf7 or kb3
+            'w' => E(K::F(9), M::NONE),  // kf9 or ka1
+            'x' => E(K::F(10), M::NONE), // kf10 or ka2
+            _ => {
+                debug!(target: "rustyline", "unsupported esc sequence: \\EO{seq2:?}");
+                E(K::UnknownEscSeq, M::NONE)
+            }
+        })
+    }
+
+    /// Bracketed paste: read until `ESC [ 201 ~`
+    fn pasted_text(&mut self) -> Result<String> {
+        let mut buffer = String::new();
+        loop {
+            match self.read_char()? {
+                '\x1b' => {
+                    let key = self.escape_sequence()?;
+                    if key == E(K::BracketedPasteEnd, M::NONE) {
+                        break;
+                    } else {
+                        continue; // TODO validate
+                    }
+                }
+                c => buffer.push(c),
+            }
+        }
+        let buffer = buffer.replace("\r\n", "\n");
+        let buffer = buffer.replace('\r', "\n");
+        Ok(buffer)
+    }
+}
+
+/// Chars already read are returned before the next ones
+struct Replay<'s, 'd, D: ?Sized> {
+    chars: std::str::Chars<'s>,
+    decoder: &'d mut D,
+}
+
+impl<D: Vt100Decoder + ?Sized> Vt100Decoder for Replay<'_, '_, D> {
+    fn read_char(&mut self) -> Result<char> {
+        match self.chars.next() {
+            Some(c) => Ok(c),
+            None => self.decoder.read_char(),
+        }
+    }
+
+    fn poll_sequence(&mut self) -> bool {
+        self.decoder.poll_sequence()
+    }
+
+    fn key_released(&mut self, key: KeyEvent) {
+        self.decoder.key_released(key);
+    }
+}
+
+/// Decode `code[:alternates][;modifiers[:event]][;text]` parameters of
+/// `\E[...u` sequences (kitty keyboard protocol)
+// https://sw.kovidgoyal.net/kitty/keyboard-protocol/
+/// and tell if it is a key release
+fn kitty_key(params: &str) -> (KeyEvent, bool) {
+    let mut fields = params.split(';');
+    let code = fields
+        .next()
+        .and_then(|f| f.split(':').next())
+        .and_then(|code| code.parse::<u32>().ok());
+    let mut modifiers = fields.next().unwrap_or_default().split(':');
+    let mods = match modifiers.next() {
+        None | Some("") => Some(1),
+        Some(mods) => mods.parse::<u16>().ok(),
+    };
+    let released = match modifiers.next() {
+        None | Some("1" | "2") => Some(false), // press, repeat
+        Some("3") => Some(true),
+        Some(_) => None,
+    };
+    let (Some(code), Some(mods), Some(released)) = (code, mods, released) else {
+        debug!(target: "rustyline", "unsupported esc sequence: \\E[{params}u");
+        return (E(K::UnknownEscSeq, M::NONE), false);
+    };
+    // super, hyper, meta, caps lock and num lock are ignored
+    let bits = mods.saturating_sub(1);
+    let mut m = M::NONE;
+    if bits & 1 != 0 {
+        m |= M::SHIFT;
+    }
+    if bits & 2 != 0 {
+        m |= M::ALT;
+    }
+    if bits & 4 != 0 {
+        m |= M::CTRL;
+    }
+    let key = match code {
+        8 | 127 => K::Backspace,
+        9 if m.contains(M::SHIFT) => {
+            m.remove(M::SHIFT);
+            K::BackTab
+        }
+        9 => K::Tab,
+        13 | 57414 => K::Enter, // KP_ENTER
+        27 => K::Esc,
+        57399..=57408 => K::Char(char::from_digit(code - 57399, 10).unwrap()), // KP_0..KP_9
+        57409 => K::Char('.'),
+        57410 => K::Char('/'),
+        57411 => K::Char('*'),
+        57412 => K::Char('-'),
+        57413 => K::Char('+'),
+        57415 => K::Char('='),
+        _ => match char::from_u32(code) {
+            // private use area: functional keys (F13+, media, modifiers)
+            Some(c) if !c.is_control() && !('\u{e000}'..='\u{f8ff}').contains(&c) => {
+                if m.contains(M::SHIFT) && !m.contains(M::CTRL) && c.is_ascii_lowercase() {
+                    // shifted text
+                    K::Char(c.to_ascii_uppercase())
+                } else {
+                    K::Char(c)
+                }
+            }
+            _ => {
+                debug!(target: "rustyline", "unsupported esc sequence: \\E[{params}u");
+                return (E(K::UnknownEscSeq, M::NONE), false);
+            }
+        },
+    };
+    (KeyEvent::normalize(E(key, m)), released)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Vt100Decoder;
+    use crate::Result;
+    use crate::error::ReadlineError;
+    use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M, Mouse, MouseButton};
+
+    struct Chars<'s>(std::str::Chars<'s>, Option<E>);
+
+    impl Vt100Decoder for Chars<'_> {
+        fn read_char(&mut self) -> Result<char> {
+            self.0.next().ok_or(ReadlineError::Eof)
+        }
+
+        fn poll_sequence(&mut self) -> bool {
+            false
+        }
+
+        fn key_released(&mut self, key: E) {
+            self.1 = Some(key);
+        }
+    }
+
+    /// Decode `\E` + `seq` which must be entirely consumed
+    fn decode(seq: &str) -> E {
+        let mut chars = Chars(seq.chars(), None);
+        let key = chars.escape_sequence().unwrap();
+        assert_eq!("", chars.0.as_str(), "{seq:?}");
+        key
+    }
+
+    #[test]
+    fn legacy() {
+        assert_eq!(E(K::Up, M::CTRL), decode("[1;5A"));
+        assert_eq!(E(K::Delete, M::NONE), decode("[3~"));
+        assert_eq!(E(K::F(5), M::CTRL), decode("[15;5~"));
+        assert_eq!(E(K::BracketedPasteStart, M::NONE), decode("[200~"));
+        assert_eq!(E(K::UnknownEscSeq, M::NONE), decode("[12;34R"));
+        assert_eq!(E(K::UnknownEscSeq, M::NONE), decode("[0~"));
+    }
+
+    #[test]
+    fn kitty_keyboard() {
+        assert_eq!(E::ESC, decode("[27u"));
+        assert_eq!(E::ctrl('A'), decode("[97;5u"));
+        assert_eq!(E(K::Char('A'), M::CTRL_SHIFT), decode("[97;6u"));
+        assert_eq!(E::alt('a'), decode("[97;3u"));
+        assert_eq!(E(K::Char('A'), M::ALT), decode("[97;4u"));
+        assert_eq!(E(K::Enter, M::CTRL), decode("[13;5u"));
+        assert_eq!(E(K::Tab, M::CTRL), decode("[9;5u"));
+        assert_eq!(E(K::BackTab, M::CTRL), decode("[9;6u"));
+        assert_eq!(E(K::Backspace, M::ALT), decode("[127;3u"));
+        assert_eq!(E(K::Char('1'), M::CTRL), decode("[57400;5u"));
+        // repeat, alternates, lock modifiers (ignored)
+        assert_eq!(E::ctrl('A'), decode("[97:65;5:2u"));
+        assert_eq!(E::ctrl('A'), decode("[97;69u"));
+        assert_eq!(E(K::Char('A'), M::CTRL_ALT_SHIFT), decode("[97;256u"));
+        // release
+        let mut chars = Chars("[97;5:3u".chars(), None);
+        assert_eq!(
+            E(K::UnknownEscSeq, M::NONE),
+            chars.escape_sequence().unwrap()
+        );
+        assert_eq!(Some(E::ctrl('A')), chars.1);
+        // functional key, malformed
+        assert_eq!(E(K::UnknownEscSeq, M::NONE), decode("[97;5:4u"));
+        assert_eq!(E(K::UnknownEscSeq, M::NONE), decode("[57441u"));
+        assert_eq!(E(K::UnknownEscSeq, M::NONE), decode("[99999999999u"));
+    }
+
+    #[test]
+    fn sgr_mouse() {
+        let mouse = Mouse {
+            button: MouseButton::Left,
+            pressed: true,
+            col: 9,
+            row: 4,
+        };
+        assert_eq!(E(K::Mouse(mouse), M::NONE), decode("[<0;10;5M"));
+        let mouse = Mouse {
+            pressed: false,
+            ..mouse
+        };
+        assert_eq!(E(K::Mouse(mouse), M::CTRL), decode("[<16;10;5m"));
+        let mouse = Mouse {
+            button: MouseButton::WheelDown,
+            pressed: true,
+            ..mouse
+        };
+        assert_eq!(E(K::Mouse(mouse), M::NONE), decode("[<65;10;5M"));
+        assert_eq!(E(K::UnknownEscSeq, M::NONE), decode("[<35;10;5M"));
+        assert_eq!(E(K::UnknownEscSeq, M::NONE), decode("[<0;0;5M"));
+    }
+}
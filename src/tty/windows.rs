@@ -1,6 +1,7 @@
 //! Windows specific definitions
 #![expect(clippy::try_err)] // suggested fix does not work (cannot infer...)
 
+use std::borrow::Cow;
 use std::fs::OpenOptions;
 use std::os::windows::io::IntoRawHandle as _;
 use std::rc::Rc;
@@ -66,15 +67,8 @@ fn get_console_mode(handle: HANDLE) -> Result<console::CONSOLE_MODE> {
 }
 
 type ConsoleBuffer = ();
-#[cfg(not(test))]
-pub type Buffer = ConsoleBuffer;
 
 type ConsoleKeyMap = ();
-#[cfg(not(test))]
-pub type KeyMap = ConsoleKeyMap;
-
-#[cfg(not(test))]
-pub type Mode = ConsoleMode;
 
 #[must_use = "You must restore default mode (disable_raw_mode)"]
 #[derive(Clone, Debug)]
@@ -287,6 +281,7 @@ pub struct ConsoleRenderer {
     buffer: String,
     utf16: Vec<u16>,
     colors_enabled: bool,
+    horizontal_scroll: bool,
     grapheme_cluster_mode: GraphemeClusterMode,
     bell_style: BellStyle,
 }
@@ -295,6 +290,7 @@ impl ConsoleRenderer {
     fn new(
         conout: HANDLE,
         colors_enabled: bool,
+        horizontal_scroll: bool,
         grapheme_cluster_mode: GraphemeClusterMode,
         bell_style: BellStyle,
     ) -> Self {
@@ -306,6 +302,7 @@ impl ConsoleRenderer {
             buffer: String::with_capacity(1024),
             utf16: Vec::with_capacity(1024),
             colors_enabled,
+            horizontal_scroll,
             grapheme_cluster_mode,
             bell_style,
         }
@@ -375,8 +372,8 @@ impl ConsoleRenderer {
         info: &console::CONSOLE_SCREEN_BUFFER_INFO,
         layout: &Layout,
     ) -> Result<()> {
-        let current_row = layout.cursor.row;
-        let old_rows = layout.end.row;
+        let current_row = layout.screen_cursor_row();
+        let old_rows = layout.screen_end_row();
         let mut coord = info.dwCursorPosition;
         coord.X = 0;
         coord.Y -= current_row as i16;
@@ -444,29 +441,39 @@ impl Renderer for ConsoleRenderer {
     ) -> Result<()> {
         let default_prompt = new_layout.default_prompt;
         let cursor = new_layout.cursor;
-        let end_pos = new_layout.end;
 
         self.buffer.clear();
         let mut col = 0;
+        // append the prompt
         if let Some(highlighter) = highlighter {
             // TODO handle ansi escape code (SetConsoleTextAttribute)
-            // append the prompt
             col = self.wrap_at_eol(
                 &highlighter.highlight_prompt(prompt.styled(), default_prompt),
                 col,
             );
-            // append the input line
-            col = self.wrap_at_eol(&highlighter.highlight(line, line.pos()), col);
         } else if self.colors_enabled {
-            // append the prompt
             col = self.wrap_at_eol(prompt.styled(), col);
-            // append the input line
-            col = self.wrap_at_eol(line, col);
         } else {
-            // append the prompt
             self.buffer.push_str(prompt.raw());
-            // append the input line
-            self.buffer.push_str(line);
+        }
+        let line_start = self.buffer.len();
+        // append the input line (and continuation prompts)
+        let input = if let Some(highlighter) = highlighter {
+            highlighter.highlight(line, line.pos())
+        } else {
+            Cow::Borrowed(line.as_str())
+        };
+        let input = super::insert_continuation(
+            prompt,
+            input,
+            default_prompt,
+            self.colors_enabled,
+            highlighter,
+        );
+        if self.colors_enabled {
+            col = self.wrap_at_eol(&input, col);
+        } else {
+            self.buffer.push_str(&input);
         }
         // append hint
         if let Some(hint) = hint {
@@ -478,6 +485,17 @@ impl Renderer for ConsoleRenderer {
                 self.buffer.push_str(hint);
             }
         }
+        if let Some(hscroll) = new_layout.hscroll {
+            // display only the columns around the cursor
+            let content = self.buffer.split_off(line_start);
+            self.buffer
+                .push_str(&super::clip_to_hscroll(&content, new_layout, hscroll));
+        } else if new_layout.viewport.is_some() {
+            // display only the rows around the cursor
+            let content = mem::take(&mut self.buffer);
+            self.buffer =
+                super::clip_to_viewport(&content, new_layout, self.cols, None, highlighter);
+        }
         let info = self.get_console_screen_buffer_info()?;
         // just to avoid flickering
         let mut guard = self.set_cursor_visibility(false)?;
@@ -488,11 +506,26 @@ impl Renderer for ConsoleRenderer {
         // display prompt, input line and hint
         write_to_console(self.conout, self.buffer.as_str(), &mut self.utf16)?;
 
-        // position the cursor
         let info = self.get_console_screen_buffer_info()?;
         let mut coord = info.dwCursorPosition;
+        // display the right prompt
+        let right_prompt = if self.colors_enabled {
+            prompt.right_styled()
+        } else {
+            prompt.right_raw()
+        };
+        if let Some((pos, right_prompt)) = new_layout.right_prompt.zip(right_prompt) {
+            let row = new_layout.screen_row(pos.row);
+            let right_coord = console::COORD {
+                X: pos.col as i16,
+                Y: coord.Y - (new_layout.screen_end_row() - row) as i16,
+            };
+            self.set_console_cursor_position(right_coord, info.dwSize)?;
+            write_to_console(self.conout, right_prompt, &mut self.utf16)?;
+        }
+        // position the cursor
         coord.X = cursor.col as i16;
-        coord.Y -= (end_pos.row - cursor.row) as i16;
+        coord.Y -= (new_layout.screen_end_row() - new_layout.screen_cursor_row()) as i16;
         self.set_console_cursor_position(coord, info.dwSize)?;
         guard.take();
         Ok(())
@@ -580,6 +613,10 @@ impl Renderer for ConsoleRenderer {
         self.colors_enabled
     }
 
+    fn horizontal_scroll(&self) -> bool {
+        self.horizontal_scroll
+    }
+
     fn grapheme_cluster_mode(&self) -> GraphemeClusterMode {
         self.grapheme_cluster_mode
     }
@@ -839,6 +876,7 @@ impl Term for Console {
         ConsoleRenderer::new(
             self.conout,
             self.colors_enabled(c),
+            c.horizontal_scroll(),
             c.grapheme_cluster_mode(),
             c.bell_style(),
         )